@@ -0,0 +1,1943 @@
+use crate::audit::{AuditEvent, Verdict};
+use crate::auditlog::LogPane;
+use crate::backend::{BatchResult, PolicyBackend};
+use crate::compile::{self, Validation};
+use crate::detail::Detail;
+use crate::diff::Diff;
+use crate::filter::{self, Filter};
+use crate::flags::{self, Checklist};
+use crate::listing::{Layout, ListRow, SortKey};
+use crate::logprof::{self, Review};
+use crate::messages::Messages;
+use crate::procs::ProcessInfo;
+use crate::profile::{Mode, Profile};
+use crate::textedit::{EditorAction, TextEditor};
+use crate::unconfined::{self, Finding};
+use anyhow::{anyhow, bail, Context, Result};
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::widgets::ListState;
+use std::collections::{btree_map, BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum View {
+    Profiles,
+    Messages,
+    /// Reviewing rules proposed from denials.
+    Logprof,
+    /// An edited file failed validation; choosing how to proceed.
+    CheckFailed,
+    /// The built-in editor.
+    Editor,
+    /// Reviewing a proposed file change before it is written.
+    Diff,
+    /// Per-profile results of a batch action.
+    Batch,
+    /// Processes and the profiles they run under.
+    Processes,
+    /// Processes that should be confined but aren't.
+    Unconfined,
+    /// Choosing a mode for the selected or marked profiles.
+    ModeMenu,
+    /// Toggling the header flags of the selected profile.
+    Flags,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PromptKind {
+    /// `/`: fuzzy search that moves the selection.
+    Search,
+    /// `f`: persistent filter over the list.
+    Filter,
+}
+
+/// Text input shown in the status bar.
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub input: String,
+    /// Filter text to go back to on Esc.
+    restore: String,
+    /// Why the filter text doesn't parse.
+    pub error: Option<String>,
+}
+
+/// What happens once a reviewed change has been written.
+#[derive(Clone, PartialEq, Debug)]
+pub enum AfterWrite {
+    /// Validate first and load only if that passes (user edits).
+    Check,
+    /// Load this policy file straight away (changes the tool generated).
+    Reload(PathBuf),
+    /// Load the file and check the profile came up in `mode`; put
+    /// `original` back if it didn't. `done` is reported on success.
+    SwitchMode { profile: String, mode: Mode, original: String, done: String },
+    /// Part of a batch mode switch: written and loaded together with the
+    /// other files of the batch once all of them are reviewed.
+    Batch,
+}
+
+/// A mode switch of the marked profiles that waits for its flag rewrites
+/// to be reviewed.
+struct BatchSwitch {
+    mode: Mode,
+    /// Outcomes known before anything is written, e.g. from the `aa-*` tool.
+    results: Vec<BatchResult>,
+    files: BTreeMap<PathBuf, BatchFile>,
+}
+
+/// A policy file whose mode flags a batch switch rewrites.
+struct BatchFile {
+    original: String,
+    content: String,
+    /// Marked profiles defined in the file.
+    profiles: Vec<String>,
+    /// Those of `profiles` that weren't loaded before.
+    disabled: Vec<String>,
+    reviewed: bool,
+}
+
+/// A proposed change to one file, shown in the diff view.
+pub struct DiffReview {
+    pub path: PathBuf,
+    pub diff: Diff,
+    /// Per hunk: take the new side when writing.
+    pub accepted: Vec<bool>,
+    /// Hunk under the cursor.
+    pub selected: usize,
+    pub side_by_side: bool,
+    /// First row shown; `None` scrolls to the selected hunk.
+    pub scroll: Option<usize>,
+    pub then: AfterWrite,
+}
+
+/// Outcome of an action on the marked profiles.
+pub struct BatchSummary {
+    /// What was done, e.g. "enforce" or "reload".
+    pub action: String,
+    pub results: Vec<BatchResult>,
+    pub scroll: usize,
+}
+
+/// A file edited from the TUI that hasn't been loaded yet.
+pub struct PendingEdit {
+    pub path: PathBuf,
+    pub line: usize,
+    /// File contents before the first edit, restored on discard.
+    pub backup: String,
+    pub validation: Validation,
+}
+
+/// Keys of the mode menu.
+pub const MODE_KEYS: [(char, Mode); 7] = [
+    ('e', Mode::Enforce),
+    ('c', Mode::Complain),
+    ('p', Mode::Prompt),
+    ('a', Mode::Audit),
+    ('k', Mode::Kill),
+    ('u', Mode::Unconfined),
+    ('d', Mode::Disable),
+];
+
+/// Which pane of the profiles view receives keys.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Focus {
+    Profiles,
+    Log,
+}
+
+pub struct App {
+    pub profiles: Vec<Profile>,
+    /// Indices into `profiles` that pass the filter.
+    pub visible: Vec<usize>,
+    /// Displayed rows: the visible profiles sorted and maybe grouped by
+    /// mode. `state` selects among these.
+    pub rows: Vec<ListRow>,
+    pub layout: Layout,
+    pub filter: Filter,
+    /// Fuzzy search text; matches are highlighted and reachable with n/N.
+    pub search: String,
+    pub prompt: Option<Prompt>,
+    /// Profiles marked for batch actions, by name.
+    pub marked: BTreeSet<String>,
+    pub batch: Option<BatchSummary>,
+    /// Snapshot shown in the processes view.
+    pub processes: Vec<ProcessInfo>,
+    pub show_unconfined: bool,
+    pub process_state: ListState,
+    pub findings: Vec<Finding>,
+    pub finding_state: ListState,
+    /// Unconfined processes whose sockets couldn't be listed.
+    pub uninspected: usize,
+    /// Show what it takes to confine the selected finding.
+    pub show_restart: bool,
+    pub checklist: Option<Checklist>,
+    pub state: ListState,
+    pub messages: Messages,
+    pub view: View,
+    pub focus: Focus,
+    pub log: LogPane,
+    pub review: Review,
+    /// Detail pane contents for the selected profile.
+    pub detail: Option<Detail>,
+    /// First source line shown in the detail pane.
+    pub detail_scroll: u16,
+    pub pending_edit: Option<PendingEdit>,
+    /// Buffer of the built-in editor while it is open.
+    pub editor: Option<TextEditor>,
+    /// Changes waiting for review; the first one is shown.
+    pub writes: VecDeque<DiffReview>,
+    switch: Option<BatchSwitch>,
+    pub should_quit: bool,
+    backend: Box<dyn PolicyBackend>,
+}
+
+impl App {
+    pub fn new(backend: Box<dyn PolicyBackend>) -> App {
+        App {
+            profiles: Vec::new(),
+            visible: Vec::new(),
+            rows: Vec::new(),
+            layout: Layout::default(),
+            filter: Filter::default(),
+            search: String::new(),
+            prompt: None,
+            marked: BTreeSet::new(),
+            batch: None,
+            processes: Vec::new(),
+            show_unconfined: false,
+            process_state: ListState::default(),
+            findings: Vec::new(),
+            finding_state: ListState::default(),
+            uninspected: 0,
+            show_restart: false,
+            checklist: None,
+            state: ListState::default(),
+            messages: Messages::default(),
+            view: View::Profiles,
+            focus: Focus::Profiles,
+            log: LogPane::default(),
+            review: Review::default(),
+            detail: None,
+            detail_scroll: 0,
+            pending_edit: None,
+            editor: None,
+            writes: VecDeque::new(),
+            switch: None,
+            should_quit: false,
+            backend,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        if self.prompt.is_some() {
+            return self.handle_prompt_key(key);
+        }
+        match self.view {
+            View::Profiles if self.focus == Focus::Log => self.handle_log_key(key),
+            View::Profiles => self.handle_profiles_key(key),
+            View::Messages => self.handle_messages_key(key),
+            View::Logprof => self.handle_logprof_key(key),
+            View::CheckFailed => self.handle_check_failed_key(key),
+            View::Editor => self.handle_editor_key(key),
+            View::Diff => self.handle_diff_key(key),
+            View::Batch => self.handle_batch_key(key),
+            View::Processes => self.handle_processes_key(key),
+            View::Unconfined => self.handle_unconfined_key(key),
+            View::ModeMenu => self.handle_mode_menu_key(key),
+            View::Flags => self.handle_flags_key(key),
+        }
+    }
+
+    /// Called once per frame to pick up background work.
+    pub fn tick(&mut self) {
+        if self.log.poll() > 0 && self.layout.sort == SortKey::Denials {
+            self.relayout();
+        }
+
+        let selected = self.selected().map(|p| p.name.clone());
+        if self.detail.as_ref().map(|d| &d.profile) != selected.as_ref() {
+            self.detail = selected.map(|name| Detail::load(self.backend.as_mut(), &name));
+            let line = self.detail.as_ref().and_then(|d| d.location.as_ref()).map_or(1, |l| l.line);
+            self.detail_scroll = line.saturating_sub(1) as u16;
+        }
+    }
+
+    fn handle_profiles_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('q') => self.should_quit = true,
+            KeyCode::Down => self.next(),
+            KeyCode::Up => self.previous(),
+            KeyCode::Char('m') => self.view = View::Messages,
+            KeyCode::Char('l') => self.run(App::toggle_log),
+            KeyCode::Tab if self.log.visible => self.focus = Focus::Log,
+            KeyCode::PageDown => self.detail_scroll = self.detail_scroll.saturating_add(10),
+            KeyCode::PageUp => self.detail_scroll = self.detail_scroll.saturating_sub(10),
+            KeyCode::Char('e') => self.run(|app| app.change_mode(Mode::Enforce)),
+            KeyCode::Char('c') => self.run(|app| app.change_mode(Mode::Complain)),
+            KeyCode::Char('a') => self.run(|app| app.change_mode(Mode::Audit)),
+            KeyCode::Char('d') => self.run(|app| app.change_mode(Mode::Disable)),
+            KeyCode::Char('M') if self.selected().is_some() || !self.marked.is_empty() => self.view = View::ModeMenu,
+            KeyCode::Char('r') => self.run(App::refresh),
+            KeyCode::Char('R') => self.run(App::reload_all),
+            KeyCode::Char('L') => self.run(App::reload_selected),
+            KeyCode::Char('v') => self.run(App::edit_profile),
+            KeyCode::Char('i') => self.run(App::open_editor),
+            KeyCode::Char('/') => self.open_prompt(PromptKind::Search),
+            KeyCode::Char('n') => self.jump_match(true),
+            KeyCode::Char('N') => self.jump_match(false),
+            KeyCode::Char('f') => self.open_prompt(PromptKind::Filter),
+            KeyCode::Char('F') => self.set_filter(Filter::default()),
+            KeyCode::Char('s') => self.run(App::cycle_sort),
+            KeyCode::Char('G') => self.toggle_grouping(),
+            KeyCode::Enter => self.toggle_group(),
+            KeyCode::Char(' ') => self.toggle_mark(),
+            KeyCode::Char('A') => self.mark_visible(),
+            KeyCode::Char('I') => self.invert_marks(),
+            KeyCode::Esc => self.marked.clear(),
+            KeyCode::Char('p') => self.run(App::open_processes),
+            KeyCode::Char('U') => self.run(App::open_unconfined),
+            KeyCode::Char('t') => self.run(App::open_flags),
+            KeyCode::Char('P') => self.run(App::persist_mode),
+            KeyCode::Char('D') => self.run(App::revert_mode),
+            _ => {}
+        }
+    }
+
+    fn handle_unconfined_key(&mut self, key: KeyEvent) {
+        let count = self.findings.len();
+        let selected = self.finding_state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('U') => self.view = View::Profiles,
+            KeyCode::Down if count > 0 => self.finding_state.select(Some((selected + 1) % count)),
+            KeyCode::Up if count > 0 => self.finding_state.select(Some((selected + count - 1) % count)),
+            KeyCode::Enter => self.show_restart = !self.show_restart,
+            KeyCode::Char('r') => self.run(App::open_unconfined),
+            _ => {}
+        }
+    }
+
+    fn handle_processes_key(&mut self, key: KeyEvent) {
+        let count = self.shown_processes().len();
+        let selected = self.process_state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('p') => self.view = View::Profiles,
+            KeyCode::Down if count > 0 => self.process_state.select(Some((selected + 1) % count)),
+            KeyCode::Up if count > 0 => self.process_state.select(Some((selected + count - 1) % count)),
+            KeyCode::Enter => self.run(App::jump_to_process_profile),
+            KeyCode::Char('u') => {
+                let pid = self.selected_process().map(|p| p.pid);
+                self.show_unconfined = !self.show_unconfined;
+                self.select_process(|p| Some(p.pid) == pid);
+            }
+            KeyCode::Char('r') => self.run(App::refresh_processes),
+            _ => {}
+        }
+    }
+
+    fn handle_mode_menu_key(&mut self, key: KeyEvent) {
+        let chosen = MODE_KEYS.iter().find(|(c, _)| key.code == KeyCode::Char(*c));
+        match (key.code, chosen) {
+            (_, Some(&(_, mode))) => {
+                self.view = View::Profiles;
+                self.run(|app| app.change_mode(mode));
+            }
+            (KeyCode::Esc | KeyCode::Char('q'), _) => self.view = View::Profiles,
+            _ => {}
+        }
+    }
+
+    fn handle_flags_key(&mut self, key: KeyEvent) {
+        let Some(checklist) = &mut self.checklist else { return };
+        let len = checklist.flags.len();
+        match key.code {
+            KeyCode::Down => checklist.selected = (checklist.selected + 1) % len,
+            KeyCode::Up => checklist.selected = (checklist.selected + len - 1) % len,
+            KeyCode::Char(' ') => checklist.toggle(),
+            KeyCode::Enter | KeyCode::Char('w') => self.run(App::apply_flags),
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.checklist = None;
+                self.view = View::Profiles;
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_batch_key(&mut self, key: KeyEvent) {
+        let Some(batch) = &mut self.batch else { return };
+        match key.code {
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => {
+                self.batch = None;
+                self.view = View::Profiles;
+            }
+            KeyCode::Up => batch.scroll = batch.scroll.saturating_sub(1),
+            KeyCode::Down => batch.scroll = (batch.scroll + 1).min(batch.results.len().saturating_sub(1)),
+            _ => {}
+        }
+    }
+
+    fn handle_messages_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Char('m') | KeyCode::Esc => self.view = View::Profiles,
+            KeyCode::Up => self.messages.scroll_up(),
+            KeyCode::Down => self.messages.scroll_down(),
+            _ => {}
+        }
+    }
+
+    fn handle_log_key(&mut self, key: KeyEvent) {
+        let len = self.log_events().len();
+        match key.code {
+            KeyCode::Tab | KeyCode::Esc => self.focus = Focus::Profiles,
+            KeyCode::Char('l') => self.run(App::toggle_log),
+            KeyCode::Char('f') => {
+                self.log.show_all = !self.log.show_all;
+                self.log.state.select(None);
+            }
+            KeyCode::Char(' ') => {
+                if let Some(seq) = self.log.state.selected().and_then(|i| self.log_events().get(i).map(|(seq, _)| *seq)) {
+                    self.log.toggle_mark(seq);
+                }
+            }
+            KeyCode::Char('g') => self.run(App::suggest_rules),
+            KeyCode::Down if len > 0 => {
+                let i = self.log.state.selected().map_or(0, |i| (i + 1).min(len - 1));
+                self.log.state.select(Some(i));
+            }
+            KeyCode::Up if len > 0 => {
+                let i = self.log.state.selected().map_or(len - 1, |i| i.saturating_sub(1));
+                self.log.state.select(Some(i));
+            }
+            _ => {}
+        }
+    }
+
+    /// Shows or hides the audit log pane, starting to tail the log the
+    /// first time it is shown.
+    pub fn toggle_log(&mut self) -> Result<()> {
+        if self.log.visible {
+            self.log.visible = false;
+            self.focus = Focus::Profiles;
+            return Ok(());
+        }
+        if !self.log.is_tailing() {
+            let rx = self.backend.audit_log()?;
+            self.log.attach(rx);
+        }
+        self.log.visible = true;
+        Ok(())
+    }
+
+    /// Audit events shown in the log pane for the current selection.
+    pub fn log_events(&self) -> Vec<(u64, &AuditEvent)> {
+        self.log.filtered(self.selected().map(|p| p.name.as_str()))
+    }
+
+    /// Proposes rules for the marked events, or for every shown event
+    /// when nothing is marked.
+    pub fn suggest_rules(&mut self) -> Result<()> {
+        let shown = self.log_events();
+        let marked: Vec<&AuditEvent> =
+            shown.iter().filter(|(seq, _)| self.log.marked.contains(seq)).map(|(_, e)| *e).collect();
+        let events = if marked.is_empty() { shown.iter().map(|(_, e)| *e).collect() } else { marked };
+
+        let suggestions = logprof::suggest(&events);
+        if suggestions.is_empty() {
+            bail!("No denials to generate rules from");
+        }
+        self.review = Review {
+            suggestions,
+            ..Review::default()
+        };
+        self.review.state.select(Some(0));
+        self.view = View::Logprof;
+        Ok(())
+    }
+
+    fn handle_logprof_key(&mut self, key: KeyEvent) {
+        let len = self.review.suggestions.len();
+        let selected = self.review.state.selected().filter(|&i| i < len);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.view = View::Profiles,
+            KeyCode::Down if len > 0 => self.review.state.select(Some(selected.map_or(0, |i| (i + 1) % len))),
+            KeyCode::Up if len > 0 => self.review.state.select(Some(selected.map_or(0, |i| (i + len - 1) % len))),
+            KeyCode::Char(' ') => {
+                if let Some(i) = selected {
+                    self.review.suggestions[i].accepted ^= true;
+                }
+            }
+            KeyCode::Left | KeyCode::Right => {
+                if let Some(i) = selected {
+                    self.review.suggestions[i].cycle(key.code == KeyCode::Right);
+                }
+            }
+            KeyCode::Char('t') => self.review.to_local = !self.review.to_local,
+            KeyCode::Char('w') => self.run(App::apply_suggestions),
+            _ => {}
+        }
+    }
+
+    /// Writes the accepted suggestions into each profile, or its `local/`
+    /// override, and reloads the policy.
+    pub fn apply_suggestions(&mut self) -> Result<()> {
+        let mut by_profile: Vec<(String, Vec<String>)> = Vec::new();
+        for suggestion in self.review.suggestions.iter().filter(|s| s.accepted) {
+            let rule = suggestion.rule().to_string();
+            match by_profile.iter_mut().find(|(name, _)| *name == suggestion.profile) {
+                Some((_, rules)) if rules.contains(&rule) => {}
+                Some((_, rules)) => rules.push(rule),
+                None => by_profile.push((suggestion.profile.clone(), vec![rule])),
+            }
+        }
+        if by_profile.is_empty() {
+            bail!("No rules accepted");
+        }
+
+        // Several profiles can live in one file, so changes accumulate per
+        // file and each file is reviewed once. Values are the new contents
+        // and the profile file to load afterwards.
+        let mut written = 0;
+        let mut updates: BTreeMap<PathBuf, (String, PathBuf)> = BTreeMap::new();
+        for (profile, rules) in &by_profile {
+            let rules: Vec<&str> = rules.iter().map(String::as_str).collect();
+            // Hats and child profiles live in their parent's file, and the
+            // `local/` override is included by the parent only, so their
+            // rules always go into the hat's own block.
+            let local = self.review.to_local && !profile.contains("//");
+            let path = self.backend.locate_profile(profile)?.path;
+            let target = if local {
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                path.with_file_name("local").join(file_name)
+            } else {
+                path.clone()
+            };
+            let current = match updates.get(&target) {
+                Some((content, _)) => Some(content.clone()),
+                None => self.backend.read_file(&target).ok(),
+            };
+            let updated = if local {
+                let source = self.backend.read_file(&path)?;
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                if !logprof::includes_local(&source, profile, &file_name) {
+                    bail!("{} does not include local/{}", path.display(), file_name);
+                }
+                logprof::append_rules(&current.unwrap_or_default(), &rules)
+            } else {
+                let source = current.with_context(|| format!("Failed to read {}", path.display()))?;
+                logprof::insert_rules(&source, profile, &rules)
+                    .with_context(|| format!("Failed to update {}", path.display()))?
+            };
+            updates.insert(target, (updated, path));
+            written += rules.len();
+        }
+
+        self.log.marked.clear();
+        self.view = View::Profiles;
+        self.messages.info(format!("Proposed {} rules for {} profiles", written, by_profile.len()));
+        for (path, (content, profile_file)) in updates {
+            // A local override is loaded through the profile that includes it.
+            self.propose_write(&path, content, AfterWrite::Reload(profile_file))?;
+        }
+        Ok(())
+    }
+
+    /// Runs an action and puts its error, if any, into the status bar.
+    pub fn run(&mut self, action: impl FnOnce(&mut App) -> Result<()>) {
+        if let Err(err) = action(self) {
+            self.messages.error(&err);
+        }
+    }
+
+    pub fn refresh(&mut self) -> Result<()> {
+        self.load_profiles()?;
+        self.messages.info(format!("Loaded {} profiles", self.profiles.len()));
+        Ok(())
+    }
+
+    pub fn load_profiles(&mut self) -> Result<()> {
+        let selected = self.selected().map(|p| p.name.clone());
+        self.profiles = self.backend.list_profiles()?;
+        self.marked.retain(|name| self.profiles.iter().any(|p| p.name == *name));
+        self.detail = None;
+        self.update_visible(selected.as_deref());
+        Ok(())
+    }
+
+    /// Recomputes `visible` and `rows`. `keep` stays selected if it is
+    /// still shown; otherwise the selection stays on the same row.
+    fn update_visible(&mut self, keep: Option<&str>) {
+        self.visible = (0..self.profiles.len()).filter(|&i| self.filter.matches(&self.profiles[i])).collect();
+        self.rows = self.layout.rows(&self.profiles, &self.visible, &self.denial_counts());
+        let by_name = keep.and_then(|name| {
+            self.rows.iter().position(|row| matches!(row, ListRow::Profile(i) if self.profiles[*i].name == name))
+        });
+        let selected = match (by_name, self.state.selected()) {
+            _ if self.rows.is_empty() => None,
+            (Some(row), _) => Some(row),
+            (None, Some(row)) => Some(row.min(self.rows.len() - 1)),
+            (None, None) => Some(0),
+        };
+        self.state.select(selected);
+    }
+
+    /// Re-sorts and regroups the list, keeping the selected profile.
+    fn relayout(&mut self) {
+        let keep = self.selected().map(|p| p.name.clone());
+        self.update_visible(keep.as_deref());
+    }
+
+    /// Denials per profile in the audit log buffer; hats count towards
+    /// their profile.
+    pub fn denial_counts(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for event in self.log.events.iter().filter(|e| e.verdict == Verdict::Denied) {
+            let profile = event.profile.split("//").next().unwrap_or_default();
+            *counts.entry(profile.to_string()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Profile rows with their row numbers, in display order.
+    pub fn profile_rows(&self) -> impl Iterator<Item = (usize, &Profile)> {
+        self.rows.iter().enumerate().filter_map(|(row, entry)| match entry {
+            ListRow::Profile(i) => Some((row, &self.profiles[*i])),
+            ListRow::Group { .. } => None,
+        })
+    }
+
+    pub fn selected(&self) -> Option<&Profile> {
+        match self.rows.get(self.state.selected()?)? {
+            ListRow::Profile(i) => Some(&self.profiles[*i]),
+            ListRow::Group { .. } => None,
+        }
+    }
+
+    pub fn next(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) => if i >= self.rows.len() - 1 { 0 } else { i + 1 },
+            None => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub fn previous(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) => if i == 0 { self.rows.len() - 1 } else { i - 1 },
+            None => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    /// Cycles the sort order. Sorting by denials starts following the audit
+    /// log if it isn't already.
+    fn cycle_sort(&mut self) -> Result<()> {
+        self.layout.sort = self.layout.sort.next();
+        if self.layout.sort == SortKey::Denials && !self.log.is_tailing() {
+            let rx = self.backend.audit_log()?;
+            self.log.attach(rx);
+        }
+        self.relayout();
+        self.messages.info(format!("Sorted by {}", self.layout.sort.as_str()));
+        Ok(())
+    }
+
+    fn toggle_grouping(&mut self) {
+        self.layout.group_by_mode = !self.layout.group_by_mode;
+        self.relayout();
+    }
+
+    /// Collapses or expands the mode group under the cursor.
+    fn toggle_group(&mut self) {
+        let Some(row) = self.state.selected() else { return };
+        if let Some(ListRow::Group { mode, .. }) = self.rows.get(row)
+            && !self.layout.collapsed.remove(mode)
+        {
+            self.layout.collapsed.insert(*mode);
+        }
+        self.update_visible(None);
+    }
+
+    fn set_filter(&mut self, filter: Filter) {
+        let keep = self.selected().map(|p| p.name.clone());
+        self.filter = filter;
+        self.update_visible(keep.as_deref());
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind) {
+        let input = match kind {
+            PromptKind::Search => String::new(),
+            PromptKind::Filter => self.filter.text.clone(),
+        };
+        if kind == PromptKind::Search {
+            self.search.clear();
+        }
+        self.prompt = Some(Prompt { kind, restore: input.clone(), input, error: None });
+    }
+
+    fn handle_prompt_key(&mut self, key: KeyEvent) {
+        let Some(prompt) = &mut self.prompt else { return };
+        match key.code {
+            KeyCode::Esc => {
+                let restore = std::mem::take(&mut prompt.restore);
+                let kind = prompt.kind;
+                self.prompt = None;
+                match kind {
+                    PromptKind::Search => self.search.clear(),
+                    PromptKind::Filter => self.set_filter(Filter::parse(&restore).unwrap_or_default()),
+                }
+            }
+            // A filter that doesn't parse keeps the prompt open.
+            KeyCode::Enter if prompt.error.is_none() => self.prompt = None,
+            KeyCode::Backspace => {
+                prompt.input.pop();
+                self.prompt_changed();
+            }
+            KeyCode::Char(c) => {
+                prompt.input.push(c);
+                self.prompt_changed();
+            }
+            _ => {}
+        }
+    }
+
+    /// Applies the prompt as it is typed.
+    fn prompt_changed(&mut self) {
+        let Some(prompt) = &mut self.prompt else { return };
+        match prompt.kind {
+            PromptKind::Search => {
+                self.search = prompt.input.clone();
+                self.jump_to_best_match();
+            }
+            PromptKind::Filter => match Filter::parse(&prompt.input) {
+                Ok(filter) => {
+                    prompt.error = None;
+                    self.set_filter(filter);
+                }
+                Err(err) => prompt.error = Some(err.to_string()),
+            },
+        }
+    }
+
+    /// Visible rows whose name fuzzy-matches the search, in list order,
+    /// with their scores.
+    fn search_matches(&self) -> Vec<(usize, i32)> {
+        if self.search.is_empty() {
+            return Vec::new();
+        }
+        self.profile_rows()
+            .filter_map(|(row, profile)| Some((row, filter::fuzzy_match(&self.search, &profile.name)?.0)))
+            .collect()
+    }
+
+    fn jump_to_best_match(&mut self) {
+        let best = self.search_matches().into_iter().max_by_key(|&(row, score)| (score, std::cmp::Reverse(row)));
+        if let Some((row, _)) = best {
+            self.state.select(Some(row));
+        }
+    }
+
+    /// Moves to the next (or previous) search match in list order, wrapping.
+    fn jump_match(&mut self, forward: bool) {
+        let rows: Vec<usize> = self.search_matches().into_iter().map(|(row, _)| row).collect();
+        let current = self.state.selected().unwrap_or(0);
+        let target = if forward {
+            rows.iter().find(|&&row| row > current).or(rows.first())
+        } else {
+            rows.iter().rev().find(|&&row| row < current).or(rows.last())
+        };
+        if let Some(&row) = target {
+            self.state.select(Some(row));
+        }
+    }
+
+    pub fn change_mode(&mut self, new_mode: Mode) -> Result<()> {
+        if !self.marked.is_empty() {
+            return self.set_marked_mode(new_mode);
+        }
+        if let Some(profile) = self.selected().map(|p| p.name.clone()) {
+            let done = format!("{} → {}", profile, new_mode);
+            self.switch_mode(&profile, new_mode, done)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the selected profile's loaded mode to its policy files.
+    pub fn persist_mode(&mut self) -> Result<()> {
+        let Some(profile) = self.selected().filter(|p| p.mode_differs()) else {
+            bail!("The loaded mode already matches the policy files");
+        };
+        let (name, mode) = (profile.name.clone(), profile.mode);
+        let done = format!("{}: {} mode persisted", name, mode);
+        self.switch_mode(&name, mode, done)
+    }
+
+    /// Puts the selected profile back in the mode its policy files give.
+    pub fn revert_mode(&mut self) -> Result<()> {
+        let Some((name, disk)) = self.selected().filter(|p| p.mode_differs()).and_then(|p| Some((p.name.clone(), p.disk_mode?)))
+        else {
+            bail!("The loaded mode already matches the policy files");
+        };
+        let done = format!("{}: back to {} from disk", name, disk);
+        self.switch_mode(&name, disk, done)
+    }
+
+    /// Switches `profile` to `mode`. Modes without an `aa-*` tool are set
+    /// by rewriting the profile flags, which goes through the diff view
+    /// like every other change the tool makes to a policy file.
+    fn switch_mode(&mut self, profile: &str, mode: Mode, done: String) -> Result<()> {
+        if mode == Mode::Unknown {
+            bail!("Can't switch {} to an unknown mode", profile);
+        }
+        let from = self.profiles.iter().find(|p| p.name == profile).map(|p| (p.mode, p.disk_mode));
+        if !flags::needs_rewrite(from.map(|(mode, _)| mode), mode) {
+            self.backend.set_mode(profile, mode)?;
+            self.load_profiles()?; // Reload to update list and modes
+            self.messages.info(done);
+            return Ok(());
+        }
+        if let Some((Mode::Disable, disk)) = from {
+            // Load it as its files say first, which also drops the
+            // `disable/` link.
+            self.backend.set_mode(profile, disk.filter(|&disk| disk != Mode::Disable).unwrap_or(Mode::Enforce))?;
+        }
+        let path = self.backend.locate_profile(profile)?.path;
+        let original = self.backend.read_file(&path)?;
+        let content = flags::rewrite_mode(&original, profile, mode)?;
+        let then = AfterWrite::SwitchMode { profile: profile.to_string(), mode, original: original.clone(), done };
+        if content == original {
+            // The file already says so; it only needs loading.
+            return self.after_write(then, &path);
+        }
+        self.propose_write(&path, content, then)
+    }
+
+    pub fn reload_all(&mut self) -> Result<()> {
+        self.backend.reload()?;
+        self.load_profiles()?;
+        self.messages.info("Reloaded AppArmor policy");
+        Ok(())
+    }
+
+    /// Replaces the selected profile's file in the kernel without
+    /// reloading the whole policy.
+    pub fn reload_selected(&mut self) -> Result<()> {
+        if !self.marked.is_empty() {
+            return self.reload_marked();
+        }
+        if let Some(profile) = self.selected().map(|p| p.name.clone()) {
+            let location = self.backend.locate_profile(&profile)?;
+            self.reload_file(&location.path)?;
+        }
+        Ok(())
+    }
+
+    /// Processes listed in the processes view.
+    pub fn shown_processes(&self) -> Vec<&ProcessInfo> {
+        self.processes.iter().filter(|p| self.show_unconfined || p.is_confined()).collect()
+    }
+
+    pub fn selected_process(&self) -> Option<&ProcessInfo> {
+        self.shown_processes().get(self.process_state.selected()?).copied()
+    }
+
+    /// Selects the first shown process matching `pred`, or the first one.
+    fn select_process(&mut self, pred: impl Fn(&ProcessInfo) -> bool) {
+        let shown = self.shown_processes();
+        let row = shown.iter().position(|p| pred(p)).or((!shown.is_empty()).then_some(0));
+        self.process_state.select(row);
+    }
+
+    /// Shows the processes view with the selected profile's first process
+    /// selected.
+    pub fn open_processes(&mut self) -> Result<()> {
+        let profile = self.selected().map(|p| p.name.clone());
+        self.processes = self.backend.list_processes()?;
+        let first = self.shown_processes().into_iter().find(|p| p.profile(&self.profiles).map(|found| &found.name) == profile.as_ref());
+        let pid = first.map(|p| p.pid);
+        self.select_process(|p| Some(p.pid) == pid);
+        self.view = View::Processes;
+        Ok(())
+    }
+
+    fn refresh_processes(&mut self) -> Result<()> {
+        let pid = self.selected_process().map(|p| p.pid);
+        self.processes = self.backend.list_processes()?;
+        self.select_process(|p| Some(p.pid) == pid);
+        Ok(())
+    }
+
+    /// Opens the flags checklist for the selected profile.
+    pub fn open_flags(&mut self) -> Result<()> {
+        let Some(profile) = self.selected().map(|p| p.name.clone()) else { return Ok(()) };
+        let location = self.backend.locate_profile(&profile)?;
+        let source = self.backend.read_file(&location.path)?;
+        self.checklist = Some(Checklist::new(&profile, location.path, source)?);
+        self.view = View::Flags;
+        Ok(())
+    }
+
+    /// Proposes the header with the chosen flags; the file is reloaded
+    /// once the change is written.
+    fn apply_flags(&mut self) -> Result<()> {
+        let Some(checklist) = self.checklist.take() else { return Ok(()) };
+        self.view = View::Profiles;
+        let content = checklist.apply()?;
+        self.propose_write(&checklist.path, content, AfterWrite::Reload(checklist.path.clone()))
+    }
+
+    /// Builds the report of processes that should be confined but aren't.
+    pub fn open_unconfined(&mut self) -> Result<()> {
+        let processes = self.backend.list_processes()?;
+        let listeners = self.backend.list_listeners()?;
+        let attachments = self.attachments();
+        self.findings = unconfined::report(&processes, &listeners, &attachments);
+        self.uninspected = processes.iter().filter(|p| !p.is_confined() && p.exe.is_some() && !p.inspected).count();
+        let selected = self.finding_state.selected().unwrap_or(0);
+        self.finding_state.select((!self.findings.is_empty()).then(|| selected.min(self.findings.len() - 1)));
+        self.view = View::Unconfined;
+        Ok(())
+    }
+
+    /// `(profile, attachment)` for every loaded top-level profile. Path
+    /// profiles attach to their name; named ones declare it in their file.
+    fn attachments(&mut self) -> Vec<(String, String)> {
+        let mut attachments = Vec::new();
+        for profile in self.profiles.iter().filter(|p| p.mode != Mode::Disable && !p.name.contains("//")) {
+            let attachment = if profile.name.starts_with('/') {
+                Some(profile.name.clone())
+            } else {
+                Detail::load(self.backend.as_mut(), &profile.name).attachment
+            };
+            if let Some(attachment) = attachment {
+                attachments.push((profile.name.clone(), attachment));
+            }
+        }
+        attachments
+    }
+
+    /// Goes back to the list with the selected process's profile selected.
+    fn jump_to_process_profile(&mut self) -> Result<()> {
+        let Some(process) = self.selected_process() else { return Ok(()) };
+        let profile = process
+            .profile(&self.profiles)
+            .map(|p| p.name.clone())
+            .ok_or_else(|| anyhow!("{} ({}) is not confined by a loaded profile", process.command, process.pid))?;
+        self.select_profile(&profile);
+        self.view = View::Profiles;
+        Ok(())
+    }
+
+    /// Selects `name` in the list, clearing the filter or unfolding its
+    /// group if that hides it.
+    fn select_profile(&mut self, name: &str) {
+        let Some(profile) = self.profiles.iter().find(|p| p.name == name) else { return };
+        if !self.filter.matches(profile) {
+            self.filter = Filter::default();
+        }
+        self.layout.collapsed.remove(&profile.mode);
+        self.update_visible(Some(name));
+    }
+
+    /// Marks or unmarks the selected profile and moves to the next row.
+    fn toggle_mark(&mut self) {
+        let Some(name) = self.selected().map(|p| p.name.clone()) else { return };
+        if !self.marked.remove(&name) {
+            self.marked.insert(name);
+        }
+        self.next();
+    }
+
+    /// Marks every profile that passes the filter.
+    fn mark_visible(&mut self) {
+        let names: Vec<String> = self.visible.iter().map(|&i| self.profiles[i].name.clone()).collect();
+        self.marked.extend(names);
+    }
+
+    /// Flips the marks of the profiles that pass the filter; marks on
+    /// filtered-out profiles are kept.
+    fn invert_marks(&mut self) {
+        for &i in &self.visible {
+            let name = &self.profiles[i].name;
+            if !self.marked.remove(name) {
+                self.marked.insert(name.clone());
+            }
+        }
+    }
+
+    /// Switches every marked profile. Modes an `aa-*` tool sets go through
+    /// it with a single escalation. Flag rewrites are reviewed in the diff
+    /// view, then written and loaded together.
+    fn set_marked_mode(&mut self, mode: Mode) -> Result<()> {
+        if mode == Mode::Unknown {
+            bail!("Can't switch profiles to an unknown mode");
+        }
+        let mut switch = BatchSwitch { mode, results: Vec::new(), files: BTreeMap::new() };
+        let mut tool = Vec::new();
+        for profile in self.marked.clone() {
+            let from = self.profiles.iter().find(|p| p.name == profile).map(|p| p.mode);
+            let enable = mode != Mode::Disable && from == Some(Mode::Disable);
+            if !enable && !flags::needs_rewrite(from, mode) {
+                tool.push(profile);
+            } else if let Err(err) = self.stage_rewrite(&mut switch, &profile, enable) {
+                switch.results.push(BatchResult { profile, ok: false, message: format!("{:#}", err) });
+            }
+        }
+        if !tool.is_empty() {
+            switch.results.extend(self.backend.set_modes(&tool, mode)?);
+        }
+        for (path, file) in &mut switch.files {
+            // Unchanged files only need loading, e.g. to enable them.
+            file.reviewed = file.content == file.original;
+            if !file.reviewed {
+                self.propose_write(path, file.content.clone(), AfterWrite::Batch)?;
+            }
+        }
+        self.switch = Some(switch);
+        self.finish_switch()
+    }
+
+    /// Adds the flag rewrite of `profile` to its file in `switch`.
+    fn stage_rewrite(&mut self, switch: &mut BatchSwitch, profile: &str, enable: bool) -> Result<()> {
+        let path = self.backend.locate_profile(profile)?.path;
+        let file = match switch.files.entry(path) {
+            btree_map::Entry::Occupied(entry) => entry.into_mut(),
+            btree_map::Entry::Vacant(entry) => {
+                let original = self.backend.read_file(entry.key())?;
+                entry.insert(BatchFile {
+                    content: original.clone(),
+                    original,
+                    profiles: Vec::new(),
+                    disabled: Vec::new(),
+                    reviewed: false,
+                })
+            }
+        };
+        file.content = flags::rewrite_mode(&file.content, profile, switch.mode)?;
+        file.profiles.push(profile.to_string());
+        if enable {
+            file.disabled.push(profile.to_string());
+        }
+        Ok(())
+    }
+
+    /// Records the reviewed content of a file of the batch switch, or that
+    /// it was left alone when `content` is `None`.
+    fn review_batch_file(&mut self, path: &Path, content: Option<String>) -> Result<()> {
+        let Some(switch) = &mut self.switch else { return Ok(()) };
+        match content {
+            Some(content) => {
+                if let Some(file) = switch.files.get_mut(path) {
+                    file.content = content;
+                    file.reviewed = true;
+                }
+            }
+            None => {
+                for profile in switch.files.remove(path).map(|file| file.profiles).unwrap_or_default() {
+                    let message = format!("Left {} unchanged", path.display());
+                    switch.results.push(BatchResult { profile, ok: false, message });
+                }
+            }
+        }
+        self.finish_switch()
+    }
+
+    /// Once every file of the batch switch is reviewed, writes and loads
+    /// them with a single escalation and checks each profile came up in the
+    /// new mode. Files with a profile that didn't are put back.
+    fn finish_switch(&mut self) -> Result<()> {
+        if self.switch.as_ref().is_none_or(|switch| switch.files.values().any(|file| !file.reviewed)) {
+            return Ok(());
+        }
+        let Some(BatchSwitch { mode, mut results, files }) = self.switch.take() else { return Ok(()) };
+        if !files.is_empty() {
+            let writes: Vec<(PathBuf, String)> = files.iter().map(|(path, file)| (path.clone(), file.content.clone())).collect();
+            let reports = self.backend.write_profiles(&writes)?;
+            let loaded = self.backend.list_profiles()?;
+            let (mut restore, mut disable) = (Vec::new(), Vec::new());
+            for ((path, file), report) in files.into_iter().zip(reports) {
+                let refused = report.diagnostics.iter().find(|d| !d.warning).map_or("apparmor_parser failed", |d| d.message.as_str());
+                let mut failed = false;
+                for profile in file.profiles {
+                    let now = loaded.iter().find(|p| p.name == profile).map(|p| p.mode);
+                    let message = if !report.success {
+                        format!("apparmor_parser refused {} mode for {}: {}", mode, profile, refused)
+                    } else if now != Some(mode) {
+                        let now = now.map_or("not loaded", Mode::as_str);
+                        format!("The running kernel doesn't support {} mode ({} came back as {})", mode, profile, now)
+                    } else {
+                        results.push(BatchResult { profile, ok: true, message: String::new() });
+                        continue;
+                    };
+                    failed = true;
+                    results.push(BatchResult { profile, ok: false, message });
+                }
+                if failed {
+                    restore.push((path, file.original));
+                    disable.extend(file.disabled);
+                }
+            }
+            if !restore.is_empty() {
+                self.backend.write_profiles(&restore)?;
+            }
+            if !disable.is_empty() {
+                self.backend.set_modes(&disable, Mode::Disable)?;
+            }
+        }
+        results.sort_by(|a, b| a.profile.cmp(&b.profile));
+        self.finish_batch(mode.to_string(), results)
+    }
+
+    /// Reloads the files defining the marked profiles, each file once.
+    fn reload_marked(&mut self) -> Result<()> {
+        let mut results = Vec::new();
+        let mut files: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+        for profile in &self.marked {
+            match self.backend.locate_profile(profile) {
+                Ok(location) => files.entry(location.path).or_default().push(profile.clone()),
+                Err(err) => results.push(BatchResult { profile: profile.clone(), ok: false, message: err.to_string() }),
+            }
+        }
+        let paths: Vec<PathBuf> = files.keys().cloned().collect();
+        let reports = if paths.is_empty() { Vec::new() } else { self.backend.reload_profiles(&paths)? };
+        for ((path, profiles), report) in files.into_iter().zip(reports) {
+            let message = match report.diagnostics.iter().find(|d| !d.warning) {
+                Some(diagnostic) => diagnostic.to_string(),
+                None if report.success => format!("Reloaded {}", path.display()),
+                None => format!("apparmor_parser failed on {}", path.display()),
+            };
+            for profile in profiles {
+                results.push(BatchResult { profile, ok: report.success, message: message.clone() });
+            }
+        }
+        results.sort_by(|a, b| a.profile.cmp(&b.profile));
+        self.finish_batch("reload".to_string(), results)
+    }
+
+    /// Refreshes the list once and shows the per-profile results.
+    fn finish_batch(&mut self, action: String, results: Vec<BatchResult>) -> Result<()> {
+        self.load_profiles()?;
+        for result in results.iter().filter(|r| !r.ok) {
+            self.messages.error(&anyhow!("{}: {}", result.profile, result.message));
+        }
+        let failed = results.iter().filter(|r| !r.ok).count();
+        let summary = format!("{}: {} of {} profiles done", action, results.len() - failed, results.len());
+        if failed > 0 {
+            self.messages.error(&anyhow!("{}, {} failed", summary, failed));
+        } else {
+            self.messages.info(summary);
+        }
+        self.batch = Some(BatchSummary { action, results, scroll: 0 });
+        self.view = View::Batch;
+        Ok(())
+    }
+
+    /// Runs `apparmor_parser -r` on one file. Every diagnostic goes to the
+    /// message history; the first error also becomes the returned error.
+    pub fn reload_file(&mut self, path: &Path) -> Result<()> {
+        let report = self.backend.reload_profile(path)?;
+        for diagnostic in &report.diagnostics {
+            if diagnostic.warning {
+                self.messages.info(diagnostic.to_string());
+            } else {
+                self.messages.error(&anyhow!("{}", diagnostic));
+            }
+        }
+        if !report.success {
+            let first = report.diagnostics.iter().find(|d| !d.warning);
+            let reason = first.map_or_else(|| "apparmor_parser failed".to_string(), |d| d.to_string());
+            return Err(anyhow!(reason).context(format!("Failed to load {}", path.display())));
+        }
+        self.load_profiles()?;
+        self.messages.info(format!("Reloaded {}", path.display()));
+        Ok(())
+    }
+
+    /// Opens the selected profile in the editor. The result is validated
+    /// before anything is loaded into the kernel.
+    pub fn edit_profile(&mut self) -> Result<()> {
+        if let Some(profile) = self.selected().map(|p| p.name.clone()) {
+            let location = self.backend.locate_profile(&profile)?;
+            let backup = self.backend.read_file(&location.path)?;
+            self.pending_edit = Some(PendingEdit {
+                path: location.path,
+                line: location.line,
+                backup,
+                validation: Validation::default(),
+            });
+            self.continue_edit()?;
+        }
+        Ok(())
+    }
+
+    fn continue_edit(&mut self) -> Result<()> {
+        if self.editor.is_some() {
+            self.view = View::Editor;
+            return Ok(());
+        }
+        let Some(edit) = &self.pending_edit else { return Ok(()) };
+        let (path, line) = (edit.path.clone(), edit.line);
+        let Some(edited) = self.backend.edit_file(&path, line)? else {
+            // Nothing changed since the last check; keep a failed check open.
+            if self.view != View::CheckFailed {
+                self.pending_edit = None;
+                self.messages.info(format!("No changes to {}", path.display()));
+            }
+            return Ok(());
+        };
+        self.propose_write(&path, edited, AfterWrite::Check)
+    }
+
+    /// Validates the pending edit and loads it, or opens the failure dialog.
+    fn check_and_load(&mut self) -> Result<()> {
+        let Some(edit) = &self.pending_edit else { return Ok(()) };
+        let path = edit.path.clone();
+        let validation = compile::validate(self.backend.as_mut(), &path)?;
+        if validation.passed() {
+            self.pending_edit = None;
+            self.close_dialog();
+            return self.reload_file(&path);
+        }
+        if let Some(edit) = &mut self.pending_edit {
+            edit.validation = validation;
+        }
+        self.view = View::CheckFailed;
+        Ok(())
+    }
+
+    /// Leaves a dialog for the built-in editor if it is open, else the list.
+    fn close_dialog(&mut self) {
+        self.view = if self.editor.is_some() { View::Editor } else { View::Profiles };
+    }
+
+    /// Opens the selected profile in the built-in editor.
+    pub fn open_editor(&mut self) -> Result<()> {
+        if let Some(profile) = self.selected().map(|p| p.name.clone()) {
+            let location = self.backend.locate_profile(&profile)?;
+            let content = self.backend.read_file(&location.path)?;
+            // Completion still works without the abstraction names.
+            let abstractions = self.backend.list_abstractions().unwrap_or_default();
+            self.editor = Some(TextEditor::new(location.path, &content, location.line, &abstractions));
+            self.view = View::Editor;
+        }
+        Ok(())
+    }
+
+    fn handle_editor_key(&mut self, key: KeyEvent) {
+        let Some(editor) = &mut self.editor else { return };
+        match editor.handle_key(key) {
+            EditorAction::None => {}
+            EditorAction::Save => self.run(App::save_editor),
+            EditorAction::Close => {
+                self.editor = None;
+                self.view = View::Profiles;
+            }
+        }
+    }
+
+    /// Writes the built-in editor's buffer, then validates and reloads it.
+    fn save_editor(&mut self) -> Result<()> {
+        let Some(editor) = &mut self.editor else { return Ok(()) };
+        let (path, line, text) = (editor.path.clone(), editor.cursor.0 + 1, editor.text());
+        let on_disk = self.backend.read_file(&path)?;
+        if text == on_disk {
+            editor.mark_saved();
+            self.messages.info(format!("No changes to {}", path.display()));
+            return Ok(());
+        }
+        match &mut self.pending_edit {
+            // A re-edit after a failed check: the file on disk is the broken
+            // attempt, so keep the backup taken before the first one.
+            Some(edit) if edit.path == path => edit.line = line,
+            pending => *pending = Some(PendingEdit { path: path.clone(), line, backup: on_disk, validation: Validation::default() }),
+        }
+        self.propose_write(&path, text, AfterWrite::Check)
+    }
+
+    /// Queues a change to `path` for review in the diff view. Nothing is
+    /// written until the reviewed hunks are accepted.
+    fn propose_write(&mut self, path: &Path, content: String, then: AfterWrite) -> Result<()> {
+        // A missing file (e.g. a new local override) diffs against nothing.
+        let original = self.backend.read_file(path).unwrap_or_default();
+        let diff = Diff::new(&original, &content);
+        if diff.hunks.is_empty() {
+            self.messages.info(format!("No changes to {}", path.display()));
+            return Ok(());
+        }
+        let accepted = vec![true; diff.hunks.len()];
+        self.writes.push_back(DiffReview {
+            path: path.to_path_buf(),
+            diff,
+            accepted,
+            selected: 0,
+            side_by_side: false,
+            scroll: None,
+            then,
+        });
+        self.view = View::Diff;
+        Ok(())
+    }
+
+    fn handle_diff_key(&mut self, key: KeyEvent) {
+        let Some(review) = self.writes.front_mut() else { return };
+        let len = review.accepted.len();
+        match key.code {
+            KeyCode::Down => {
+                review.selected = (review.selected + 1) % len;
+                review.scroll = None;
+            }
+            KeyCode::Up => {
+                review.selected = (review.selected + len - 1) % len;
+                review.scroll = None;
+            }
+            KeyCode::Char(' ') => review.accepted[review.selected] ^= true,
+            KeyCode::Char('a') => {
+                let all = !review.accepted.iter().all(|&a| a);
+                review.accepted.fill(all);
+            }
+            KeyCode::Char('s') => review.side_by_side = !review.side_by_side,
+            KeyCode::PageDown => review.scroll = review.scroll.map(|s| s + 10),
+            KeyCode::PageUp => review.scroll = review.scroll.map(|s| s.saturating_sub(10)),
+            KeyCode::Char('w') | KeyCode::Enter => self.run(App::commit_write),
+            KeyCode::Esc => self.run(App::cancel_write),
+            _ => {}
+        }
+    }
+
+    /// Writes the accepted hunks of the reviewed change and loads the file.
+    fn commit_write(&mut self) -> Result<()> {
+        let Some(review) = self.writes.pop_front() else { return Ok(()) };
+        self.next_review();
+        let content = review.diff.apply(&review.accepted);
+        if !review.accepted.contains(&true) {
+            return self.skip_write(&review);
+        }
+        if review.then == AfterWrite::Batch {
+            return self.review_batch_file(&review.path, Some(content));
+        }
+        self.backend.write_file(&review.path, &content)?;
+        if let Some(editor) = &mut self.editor
+            && editor.path == review.path
+            && editor.text() == content
+        {
+            editor.mark_saved();
+        }
+        self.after_write(review.then, &review.path)
+    }
+
+    fn after_write(&mut self, then: AfterWrite, written: &Path) -> Result<()> {
+        match then {
+            AfterWrite::Check => self.check_and_load(),
+            AfterWrite::Reload(path) => self.reload_file(&path),
+            // Written by `finish_switch` together with the rest of the batch.
+            AfterWrite::Batch => Ok(()),
+            AfterWrite::SwitchMode { profile, mode, original, done } => {
+                let loaded = flags::load_mode(self.backend.as_mut(), written, &profile, mode, &original);
+                self.load_profiles()?;
+                loaded?;
+                self.messages.info(done);
+                Ok(())
+            }
+        }
+    }
+
+    fn cancel_write(&mut self) -> Result<()> {
+        let Some(review) = self.writes.pop_front() else { return Ok(()) };
+        self.next_review();
+        self.skip_write(&review)
+    }
+
+    fn skip_write(&mut self, review: &DiffReview) -> Result<()> {
+        if review.then == AfterWrite::Check {
+            // A re-edit after a failed check leaves the broken attempt on
+            // disk, so return to the failure dialog to re-edit or discard.
+            match &self.pending_edit {
+                Some(edit) if !edit.validation.passed() => self.view = View::CheckFailed,
+                _ => self.pending_edit = None,
+            }
+        }
+        self.messages.info(format!("Left {} unchanged", review.path.display()));
+        if review.then == AfterWrite::Batch {
+            return self.review_batch_file(&review.path, None);
+        }
+        Ok(())
+    }
+
+    /// Shows the next queued change, or leaves the diff view.
+    fn next_review(&mut self) {
+        if self.writes.is_empty() {
+            self.close_dialog();
+        } else {
+            self.view = View::Diff;
+        }
+    }
+
+    fn handle_check_failed_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('e') => self.run(App::continue_edit),
+            // The edit is already on disk, so backing out restores the old
+            // file rather than leaving one that doesn't compile.
+            KeyCode::Char('d') | KeyCode::Esc | KeyCode::Char('q') => self.run(App::discard_edit),
+            KeyCode::Char('l') => self.run(App::load_anyway),
+            _ => {}
+        }
+    }
+
+    fn discard_edit(&mut self) -> Result<()> {
+        if let Some(edit) = self.pending_edit.take() {
+            self.backend.write_file(&edit.path, &edit.backup)?;
+            self.messages.info(format!("Restored {}", edit.path.display()));
+        }
+        self.close_dialog();
+        Ok(())
+    }
+
+    /// Loads a file the built-in parser complained about. Files that
+    /// `apparmor_parser` rejected are never loaded.
+    fn load_anyway(&mut self) -> Result<()> {
+        let Some(edit) = &self.pending_edit else { return Ok(()) };
+        if edit.validation.parser_rejected {
+            bail!("apparmor_parser rejects {}; re-edit or discard it", edit.path.display());
+        }
+        let path = edit.path.clone();
+        self.pending_edit = None;
+        self.close_dialog();
+        self.reload_file(&path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use crate::compile::Diagnostic;
+    use crossterm::event::KeyModifiers;
+    use crate::messages::Level;
+    use crate::unconfined::{Listener, Reason};
+
+    fn app_with(profiles: &[(&str, Mode)]) -> App {
+        let mut app = App::new(Box::new(FakeBackend::with_profiles(profiles)));
+        app.load_profiles().unwrap();
+        app
+    }
+
+    #[test]
+    fn navigation_wraps_and_tolerates_empty_list() {
+        let mut app = app_with(&[("a", Mode::Enforce), ("b", Mode::Complain)]);
+        assert_eq!(app.state.selected(), Some(0));
+        app.previous();
+        assert_eq!(app.state.selected(), Some(1));
+        app.next();
+        assert_eq!(app.state.selected(), Some(0));
+
+        let mut empty = app_with(&[]);
+        empty.next();
+        empty.previous();
+        assert_eq!(empty.state.selected(), None);
+    }
+
+    #[test]
+    fn change_mode_updates_selected_profile() {
+        let mut app = app_with(&[("firefox", Mode::Enforce), ("/usr/bin/man", Mode::Enforce)]);
+        // Rows are sorted by name: "/usr/bin/man", then "firefox".
+        app.next();
+        app.change_mode(Mode::Complain).unwrap();
+        assert_eq!(app.profiles[0].mode, Mode::Complain);
+        assert_eq!(app.selected().unwrap().name, "firefox");
+        assert_eq!(app.state.selected(), Some(1));
+    }
+
+    #[test]
+    fn failed_change_mode_is_reported() {
+        let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Enforce)]);
+        backend.fail_with = Some("sudo: a password is required".to_string());
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        app.handle_key(KeyEvent::from(KeyCode::Char('c')));
+        assert_eq!(app.profiles[0].mode, Mode::Enforce);
+        let last = app.messages.last().unwrap();
+        assert_eq!(last.level, Level::Error);
+        assert!(last.text.contains("password"));
+    }
+
+    #[test]
+    fn log_pane_follows_selected_profile() {
+        let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Enforce), ("cupsd", Mode::Enforce)]);
+        backend.log_lines = vec![
+            r#"apparmor="DENIED" operation="open" profile="firefox" name="/etc/shadow" pid=1"#.to_string(),
+            r#"apparmor="DENIED" operation="open" profile="cupsd" name="/etc/passwd" pid=2"#.to_string(),
+        ];
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        app.handle_key(KeyEvent::from(KeyCode::Char('l')));
+        app.tick();
+        assert_eq!(app.log_events().len(), 1);
+        assert_eq!(app.log_events()[0].1.name.as_deref(), Some("/etc/passwd"));
+        app.next();
+        assert_eq!(app.log_events()[0].1.profile, "firefox");
+        app.log.show_all = true;
+        assert_eq!(app.log_events().len(), 2);
+    }
+
+    #[test]
+    fn local_rules_for_hats_go_into_the_hat() {
+        let path = PathBuf::from("/etc/apparmor.d/firefox");
+        let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Enforce), ("firefox//browser", Mode::Enforce)]);
+        backend.files.insert(
+            path.clone(),
+            "profile firefox {\n  include if exists <local/firefox>\n  ^browser {\n  }\n}\n".to_string(),
+        );
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        let suggestion = |profile: &str, rule: &str| logprof::Suggestion {
+            profile: profile.to_string(),
+            options: vec![logprof::RuleOption { label: "exact", rule: rule.to_string() }],
+            choice: 0,
+            accepted: true,
+            events: 1,
+        };
+        app.review = Review {
+            suggestions: vec![suggestion("firefox", "/tmp/a r,"), suggestion("firefox//browser", "/tmp/b r,")],
+            to_local: true,
+            ..Review::default()
+        };
+        app.apply_suggestions().unwrap();
+        let written: Vec<(PathBuf, String)> =
+            app.writes.iter().map(|w| (w.path.clone(), w.diff.apply(&w.accepted))).collect();
+        assert_eq!(
+            written,
+            [
+                (PathBuf::from("/etc/apparmor.d/firefox"), "profile firefox {\n  include if exists <local/firefox>\n  ^browser {\n    /tmp/b r,\n  }\n}\n".to_string()),
+                (PathBuf::from("/etc/apparmor.d/local/firefox"), "/tmp/a r,\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn edit_that_fails_to_compile_is_not_loaded() {
+        let path = PathBuf::from("/etc/apparmor.d/firefox");
+        let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Enforce)]);
+        backend.files.insert(path.clone(), "profile firefox {\n}\n".to_string());
+        backend.diagnostics = vec![Diagnostic {
+            file: Some(path.clone()),
+            line: Some(2),
+            message: "syntax error".to_string(),
+            warning: false,
+        }];
+        backend.edited = Some("profile firefox {\n  bogus\n}\n".to_string());
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        app.handle_key(KeyEvent::from(KeyCode::Char('v')));
+        assert_eq!(app.view, View::Diff);
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(app.view, View::CheckFailed);
+        app.handle_key(KeyEvent::from(KeyCode::Char('l')));
+        assert_eq!(app.view, View::CheckFailed);
+        assert!(app.messages.last().unwrap().text.contains("rejects"));
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert_eq!(app.view, View::Profiles);
+        assert!(app.pending_edit.is_none());
+        assert_eq!(app.messages.last().unwrap().text, "Restored /etc/apparmor.d/firefox");
+        assert_eq!(app.backend.read_file(&path).unwrap(), "profile firefox {\n}\n");
+    }
+
+    #[test]
+    fn unchanged_external_edit_is_not_loaded() {
+        let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Enforce)]);
+        backend.files.insert(PathBuf::from("/etc/apparmor.d/firefox"), "profile firefox {\n}\n".to_string());
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        app.handle_key(KeyEvent::from(KeyCode::Char('v')));
+        assert_eq!(app.view, View::Profiles);
+        assert!(app.writes.is_empty() && app.pending_edit.is_none());
+        assert_eq!(app.messages.last().unwrap().text, "No changes to /etc/apparmor.d/firefox");
+    }
+
+    #[test]
+    fn builtin_editor_saves_and_reloads() {
+        let path = PathBuf::from("/etc/apparmor.d/firefox");
+        let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Enforce)]);
+        backend.files.insert(path.clone(), "profile firefox {\n}\n".to_string());
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        app.handle_key(KeyEvent::from(KeyCode::Char('i')));
+        assert_eq!(app.view, View::Editor);
+        app.handle_key(KeyEvent::from(KeyCode::End));
+        app.handle_key(KeyEvent::from(KeyCode::Char('#')));
+        app.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+        assert_eq!(app.view, View::Diff);
+        app.handle_key(KeyEvent::from(KeyCode::Char('w')));
+        assert_eq!(app.view, View::Editor);
+        assert_eq!(app.messages.last().unwrap().text, "Reloaded /etc/apparmor.d/firefox");
+        assert!(!app.editor.as_ref().unwrap().modified());
+
+        // Rejecting every hunk writes nothing and keeps the buffer dirty.
+        app.handle_key(KeyEvent::from(KeyCode::Char('#')));
+        app.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+        app.handle_key(KeyEvent::from(KeyCode::Char(' ')));
+        app.handle_key(KeyEvent::from(KeyCode::Char('w')));
+        assert_eq!(app.view, View::Editor);
+        assert_eq!(app.messages.last().unwrap().text, "Left /etc/apparmor.d/firefox unchanged");
+        assert!(app.editor.as_ref().unwrap().modified());
+    }
+
+    #[test]
+    fn discarding_a_re_edit_restores_the_original() {
+        let path = PathBuf::from("/etc/apparmor.d/firefox");
+        let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Enforce)]);
+        backend.files.insert(path.clone(), "profile firefox {\n}\n".to_string());
+        backend.diagnostics = vec![Diagnostic { file: Some(path.clone()), line: Some(1), message: "syntax error".to_string(), warning: false }];
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        app.handle_key(KeyEvent::from(KeyCode::Char('i')));
+        let save = |app: &mut App| {
+            app.handle_key(KeyEvent::from(KeyCode::Char('#')));
+            app.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+            app.handle_key(KeyEvent::from(KeyCode::Char('w')));
+            assert_eq!(app.view, View::CheckFailed);
+        };
+        save(&mut app);
+        app.handle_key(KeyEvent::from(KeyCode::Char('e')));
+        assert_eq!(app.view, View::Editor);
+        save(&mut app);
+        assert_eq!(app.backend.read_file(&path).unwrap(), "##profile firefox {\n}\n");
+        app.handle_key(KeyEvent::from(KeyCode::Char('d')));
+        assert_eq!(app.backend.read_file(&path).unwrap(), "profile firefox {\n}\n");
+    }
+
+    #[test]
+    fn cancelled_re_edit_returns_to_the_failed_check() {
+        let path = PathBuf::from("/etc/apparmor.d/firefox");
+        let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Enforce)]);
+        backend.files.insert(path.clone(), "profile firefox {\n}\n".to_string());
+        backend.diagnostics = vec![Diagnostic { file: Some(path.clone()), line: Some(1), message: "syntax error".to_string(), warning: false }];
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        app.handle_key(KeyEvent::from(KeyCode::Char('i')));
+        app.handle_key(KeyEvent::from(KeyCode::Char('#')));
+        app.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+        app.handle_key(KeyEvent::from(KeyCode::Char('w')));
+        assert_eq!(app.view, View::CheckFailed);
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('e')));
+        app.handle_key(KeyEvent::from(KeyCode::Char('#')));
+        app.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+        assert_eq!(app.view, View::Diff);
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert_eq!(app.view, View::CheckFailed);
+        assert!(app.pending_edit.is_some());
+
+        // Rejecting every hunk backs out the same way.
+        app.handle_key(KeyEvent::from(KeyCode::Char('e')));
+        app.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+        app.handle_key(KeyEvent::from(KeyCode::Char(' ')));
+        app.handle_key(KeyEvent::from(KeyCode::Char('w')));
+        assert_eq!(app.view, View::CheckFailed);
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('d')));
+        assert_eq!(app.backend.read_file(&path).unwrap(), "profile firefox {\n}\n");
+    }
+
+    #[test]
+    fn filter_and_search_keep_a_valid_selection() {
+        let mut app = app_with(&[
+            ("/usr/sbin/cupsd", Mode::Enforce),
+            ("firefox", Mode::Complain),
+            ("snap.firefox.firefox", Mode::Enforce),
+            ("man_filter", Mode::Complain),
+        ]);
+        let keys = |app: &mut App, text: &str| {
+            for c in text.chars() {
+                app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+            }
+        };
+        app.next();
+        keys(&mut app, "fmode:complain");
+        assert_eq!(app.profile_rows().count(), 2);
+        assert_eq!(app.selected().unwrap().name, "firefox");
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(app.filter.text, "mode:complain");
+
+        keys(&mut app, "/mf");
+        assert_eq!(app.selected().unwrap().name, "man_filter");
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        keys(&mut app, "F");
+        assert_eq!(app.profile_rows().count(), 4);
+        assert_eq!(app.selected().unwrap().name, "man_filter");
+        keys(&mut app, "/fire");
+        assert_eq!(app.selected().unwrap().name, "firefox");
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        keys(&mut app, "n");
+        assert_eq!(app.selected().unwrap().name, "snap.firefox.firefox");
+        keys(&mut app, "n");
+        assert_eq!(app.selected().unwrap().name, "firefox");
+
+        keys(&mut app, "f/(/");
+        assert!(app.prompt.as_ref().unwrap().error.is_some());
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert!(app.filter.text.is_empty());
+        assert_eq!(app.profile_rows().count(), 4);
+    }
+
+    #[test]
+    fn grouped_list_collapses_and_sorts() {
+        let mut app = app_with(&[("b", Mode::Complain), ("a", Mode::Enforce), ("c", Mode::Complain)]);
+        app.handle_key(KeyEvent::from(KeyCode::Char('G')));
+        assert_eq!(app.rows.len(), 5);
+        assert_eq!(app.selected().unwrap().name, "a");
+        app.state.select(Some(2));
+        assert!(app.selected().is_none());
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(app.rows.len(), 3);
+        assert_eq!(app.rows[2], ListRow::Group { mode: Mode::Complain, count: 2, collapsed: true });
+        app.handle_key(KeyEvent::from(KeyCode::Char('s')));
+        assert_eq!(app.layout.sort, SortKey::Mode);
+    }
+
+    #[test]
+    fn successful_change_mode_is_confirmed() {
+        let mut app = app_with(&[("firefox", Mode::Enforce)]);
+        app.handle_key(KeyEvent::from(KeyCode::Char('c')));
+        assert_eq!(app.messages.last().unwrap().text, "firefox → complain");
+    }
+
+    #[test]
+    fn batch_actions_apply_to_marked_profiles() {
+        let mut backend = FakeBackend::with_profiles(&[("cupsd", Mode::Enforce), ("firefox", Mode::Enforce), ("man", Mode::Enforce)]);
+        backend.files.insert(PathBuf::from("/etc/apparmor.d/cupsd"), "profile cupsd {\n}\n".to_string());
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        app.handle_key(KeyEvent::from(KeyCode::Char(' ')));
+        assert_eq!(app.selected().unwrap().name, "firefox");
+        app.handle_key(KeyEvent::from(KeyCode::Char('I')));
+        assert_eq!(app.marked.iter().collect::<Vec<_>>(), ["firefox", "man"]);
+        app.handle_key(KeyEvent::from(KeyCode::Char('c')));
+        let modes: Vec<Mode> = app.profiles.iter().map(|p| p.mode).collect();
+        assert_eq!(modes, [Mode::Enforce, Mode::Complain, Mode::Complain]);
+        assert_eq!(app.view, View::Batch);
+        assert_eq!(app.messages.last().unwrap().text, "complain: 2 of 2 profiles done");
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('A')));
+        app.handle_key(KeyEvent::from(KeyCode::Char('L')));
+        let batch = app.batch.as_ref().unwrap();
+        let ok: Vec<(&str, bool)> = batch.results.iter().map(|r| (r.profile.as_str(), r.ok)).collect();
+        assert_eq!(ok, [("cupsd", true), ("firefox", false), ("man", false)]);
+        assert_eq!(app.messages.last().unwrap().level, Level::Error);
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert!(app.marked.is_empty());
+    }
+
+    #[test]
+    fn batch_flag_rewrites_are_reviewed_then_loaded_together() {
+        let fresh = |unsupported: Vec<Mode>| {
+            let mut backend = FakeBackend::with_profiles(&[
+                ("cupsd", Mode::Enforce),
+                ("firefox", Mode::Enforce),
+                ("man", Mode::Complain),
+                ("ping", Mode::Disable),
+            ]);
+            backend.files.insert(PathBuf::from("/etc/apparmor.d/cupsd"), "profile cupsd {\n}\n".to_string());
+            backend.files.insert(PathBuf::from("/etc/apparmor.d/multi"), "profile firefox {\n}\nprofile man flags=(complain) {\n}\n".to_string());
+            backend.files.insert(PathBuf::from("/etc/apparmor.d/ping"), "profile ping {\n}\n".to_string());
+            backend.unsupported = unsupported;
+            let mut app = App::new(Box::new(backend));
+            app.load_profiles().unwrap();
+            app.handle_key(KeyEvent::from(KeyCode::Char('A')));
+            app.handle_key(KeyEvent::from(KeyCode::Char('M')));
+            app.handle_key(KeyEvent::from(KeyCode::Char('k')));
+            app
+        };
+        let modes = |app: &App| app.profiles.iter().map(|p| p.mode).collect::<Vec<_>>();
+
+        // One review per file; nothing is loaded before the last one.
+        let mut app = fresh(Vec::new());
+        for path in ["/etc/apparmor.d/cupsd", "/etc/apparmor.d/multi", "/etc/apparmor.d/ping"] {
+            assert_eq!(app.view, View::Diff);
+            assert_eq!(app.writes.front().unwrap().path, PathBuf::from(path));
+            assert_eq!(modes(&app), [Mode::Enforce, Mode::Enforce, Mode::Complain, Mode::Disable]);
+            app.handle_key(KeyEvent::from(KeyCode::Char('w')));
+        }
+        assert_eq!(app.view, View::Batch);
+        assert_eq!(modes(&app), [Mode::Kill; 4]);
+        assert_eq!(
+            app.backend.read_file(Path::new("/etc/apparmor.d/multi")).unwrap(),
+            "profile firefox flags=(kill) {\n}\nprofile man flags=(kill) {\n}\n"
+        );
+        assert_eq!(app.messages.last().unwrap().text, "kill: 4 of 4 profiles done");
+
+        // A file left alone is reported; a refused mode puts the file back
+        // and leaves a profile that wasn't loaded unloaded.
+        let mut app = fresh(vec![Mode::Kill]);
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        app.handle_key(KeyEvent::from(KeyCode::Char('w')));
+        app.handle_key(KeyEvent::from(KeyCode::Char('w')));
+        let batch = app.batch.as_ref().unwrap();
+        let results: Vec<(&str, bool, &str)> = batch.results.iter().map(|r| (r.profile.as_str(), r.ok, &r.message[..20])).collect();
+        assert_eq!(
+            results,
+            [
+                ("cupsd", false, "Left /etc/apparmor.d"),
+                ("firefox", false, "The running kernel d"),
+                ("man", false, "The running kernel d"),
+                ("ping", false, "The running kernel d"),
+            ]
+        );
+        assert_eq!(modes(&app), [Mode::Enforce, Mode::Enforce, Mode::Complain, Mode::Disable]);
+        assert_eq!(app.backend.read_file(Path::new("/etc/apparmor.d/ping")).unwrap(), "profile ping {\n}\n");
+        assert_eq!(app.backend.read_file(Path::new("/etc/apparmor.d/cupsd")).unwrap(), "profile cupsd {\n}\n");
+    }
+
+    #[test]
+    fn processes_link_back_to_profiles() {
+        let mut backend = FakeBackend::with_profiles(&[("cupsd", Mode::Enforce), ("firefox", Mode::Complain)]);
+        let process = |pid, label: &str, mode: &str| ProcessInfo {
+            pid,
+            command: label.to_string(),
+            user: "root".to_string(),
+            label: label.to_string(),
+            mode: mode.to_string(),
+            ..ProcessInfo::default()
+        };
+        backend.processes = vec![process(1, "unconfined", "unconfined"), process(40, "cupsd", "enforce"), process(50, "firefox//browser", "complain")];
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        assert_eq!(app.profiles[1].processes[0].pid, 50);
+
+        app.next();
+        app.handle_key(KeyEvent::from(KeyCode::Char('p')));
+        assert_eq!(app.view, View::Processes);
+        assert_eq!(app.shown_processes().len(), 2);
+        assert_eq!(app.selected_process().unwrap().pid, 50);
+        app.handle_key(KeyEvent::from(KeyCode::Char('u')));
+        assert_eq!(app.selected_process().unwrap().pid, 50);
+
+        app.filter = Filter::parse("fire").unwrap();
+        app.update_visible(None);
+        app.handle_key(KeyEvent::from(KeyCode::Up));
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(app.view, View::Profiles);
+        assert_eq!(app.selected().unwrap().name, "cupsd");
+        assert!(app.filter.is_empty());
+    }
+
+    #[test]
+    fn unconfined_report_uses_profile_attachments() {
+        let mut backend = FakeBackend::with_profiles(&[("cups", Mode::Enforce)]);
+        backend.files.insert(PathBuf::from("/etc/apparmor.d/cups"), "profile cups /usr/sbin/cupsd {\n}\n".to_string());
+        let process = |pid, exe: &str, sockets: Vec<u64>| ProcessInfo {
+            pid,
+            label: "unconfined".to_string(),
+            mode: "unconfined".to_string(),
+            exe: Some(exe.to_string()),
+            unit: Some(format!("{}.service", &exe[10..])),
+            sockets,
+            inspected: true,
+            ..ProcessInfo::default()
+        };
+        backend.processes = vec![process(10, "/usr/sbin/cupsd", vec![]), process(20, "/usr/sbin/sshd", vec![5])];
+        backend.listeners = vec![Listener { protocol: "tcp6", address: "[::]:22".to_string(), inode: 5 }];
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        app.handle_key(KeyEvent::from(KeyCode::Char('U')));
+        assert_eq!(app.view, View::Unconfined);
+        let reasons: Vec<&Reason> = app.findings.iter().map(|f| &f.reason).collect();
+        assert_eq!(reasons, [&Reason::ProfileNotApplied("cups".to_string()), &Reason::NoProfile]);
+        assert_eq!(app.findings[0].restart_hint()[1], "Confinement applies from the next exec: systemctl restart cupsd.service");
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert!(app.show_restart);
+    }
+
+    #[test]
+    fn mode_menu_reaches_every_mode() {
+        let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Enforce)]);
+        backend.files.insert(PathBuf::from("/etc/apparmor.d/firefox"), "profile firefox {\n}\n".to_string());
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        for (key, mode) in MODE_KEYS {
+            let from = app.profiles[0].mode;
+            app.handle_key(KeyEvent::from(KeyCode::Char('M')));
+            assert_eq!(app.view, View::ModeMenu);
+            app.handle_key(KeyEvent::from(KeyCode::Char(key)));
+            // Modes set through the profile flags are reviewed first.
+            if flags::needs_rewrite(Some(from), mode) {
+                assert_eq!(app.view, View::Diff, "{}", mode);
+                app.handle_key(KeyEvent::from(KeyCode::Char('w')));
+            }
+            assert_eq!(app.view, View::Profiles);
+            assert_eq!(app.profiles[0].mode, mode);
+        }
+        assert_eq!(app.messages.last().unwrap().text, "firefox → disable");
+    }
+
+    #[test]
+    fn refused_mode_switch_restores_the_reviewed_file() {
+        let source = "profile firefox {\n}\n";
+        let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Enforce)]);
+        backend.files.insert(PathBuf::from("/etc/apparmor.d/firefox"), source.to_string());
+        backend.unsupported = vec![Mode::Prompt];
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        app.change_mode(Mode::Prompt).unwrap();
+        assert_eq!(app.view, View::Diff);
+        app.handle_key(KeyEvent::from(KeyCode::Char('w')));
+        let last = app.messages.last().unwrap();
+        assert_eq!(last.level, Level::Error);
+        assert!(last.text.contains("doesn't support prompt mode"), "{}", last.text);
+        assert_eq!(app.profiles[0].mode, Mode::Enforce);
+        assert_eq!(app.backend.read_file(Path::new("/etc/apparmor.d/firefox")).unwrap(), source);
+    }
+
+    #[test]
+    fn flags_dialog_rewrites_the_header_and_reloads() {
+        let path = PathBuf::from("/etc/apparmor.d/firefox");
+        let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Enforce)]);
+        backend.files.insert(path.clone(), "profile firefox {\n  /etc/hosts r,\n}\n".to_string());
+        let mut app = App::new(Box::new(backend));
+        app.load_profiles().unwrap();
+        app.handle_key(KeyEvent::from(KeyCode::Char('t')));
+        assert_eq!(app.view, View::Flags);
+        app.handle_key(KeyEvent::from(KeyCode::Down));
+        app.handle_key(KeyEvent::from(KeyCode::Char(' ')));
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(app.view, View::Diff);
+        app.handle_key(KeyEvent::from(KeyCode::Char('w')));
+        assert_eq!(app.profiles[0].mode, Mode::Complain);
+        assert_eq!(app.messages.last().unwrap().text, "Reloaded /etc/apparmor.d/firefox");
+        app.handle_key(KeyEvent::from(KeyCode::Char('t')));
+        let checklist = app.checklist.as_ref().unwrap();
+        assert_eq!(checklist.result(), ["complain"]);
+    }
+
+    #[test]
+    fn runtime_mode_can_be_persisted_or_reverted() {
+        // Loaded in kill mode by hand while the file says complain.
+        let fresh = || {
+            let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Kill)]);
+            backend.files.insert(PathBuf::from("/etc/apparmor.d/firefox"), "profile firefox flags=(complain) {\n}\n".to_string());
+            let mut app = App::new(Box::new(backend));
+            app.load_profiles().unwrap();
+            app
+        };
+        let mut app = fresh();
+        assert_eq!(app.profiles[0].disk_mode, Some(Mode::Complain));
+        assert!(app.profiles[0].mode_differs());
+        app.handle_key(KeyEvent::from(KeyCode::Char('D')));
+        assert_eq!((app.profiles[0].mode, app.profiles[0].disk_mode), (Mode::Complain, Some(Mode::Complain)));
+        assert_eq!(app.messages.last().unwrap().text, "firefox: back to complain from disk");
+        app.handle_key(KeyEvent::from(KeyCode::Char('P')));
+        assert_eq!(app.messages.last().unwrap().level, Level::Error);
+
+        let mut app = fresh();
+        app.handle_key(KeyEvent::from(KeyCode::Char('P')));
+        assert_eq!(app.view, View::Diff);
+        app.handle_key(KeyEvent::from(KeyCode::Char('w')));
+        assert_eq!((app.profiles[0].mode, app.profiles[0].disk_mode), (Mode::Kill, Some(Mode::Kill)));
+        assert_eq!(app.messages.last().unwrap().text, "firefox: kill mode persisted");
+    }
+}
@@ -0,0 +1,172 @@
+//! Parsing AppArmor audit records out of audit.log, kern.log or the journal.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Verdict {
+    Denied,
+    Allowed,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditEvent {
+    pub verdict: Verdict,
+    /// Seconds since the epoch, from the `audit(<time>:<serial>)` stamp.
+    pub timestamp: Option<f64>,
+    pub profile: String,
+    pub operation: String,
+    pub name: Option<String>,
+    pub requested_mask: Option<String>,
+    pub denied_mask: Option<String>,
+    pub pid: Option<u32>,
+    pub comm: Option<String>,
+    /// Every `key=value` pair of the record, unquoted and hex-decoded.
+    pub fields: Vec<(String, String)>,
+}
+
+impl AuditEvent {
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Whether the event belongs to `profile` or one of its hats/children.
+    pub fn matches_profile(&self, profile: &str) -> bool {
+        self.profile == profile
+            || self.profile.strip_prefix(profile).is_some_and(|rest| rest.starts_with("//"))
+    }
+}
+
+/// Fields the audit subsystem hex-encodes when they contain spaces or
+/// other unsafe characters.
+const ENCODED_FIELDS: &[&str] = &["name", "comm", "profile", "peer", "target", "exe", "srcname"];
+
+/// Parses one log line. Returns `None` for anything that isn't an AppArmor
+/// `DENIED` or `ALLOWED` record.
+pub fn parse_line(line: &str) -> Option<AuditEvent> {
+    let start = line.find("apparmor=")?;
+    let fields: Vec<(String, String)> = tokenize(&line[start..])
+        .into_iter()
+        .filter_map(|token| {
+            let (key, value) = token.split_once('=')?;
+            Some((key.to_string(), decode_value(key, value)))
+        })
+        .collect();
+    let get = |key: &str| fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+
+    let verdict = match get("apparmor")?.as_str() {
+        "DENIED" => Verdict::Denied,
+        "ALLOWED" => Verdict::Allowed,
+        _ => return None,
+    };
+
+    Some(AuditEvent {
+        verdict,
+        timestamp: parse_timestamp(line),
+        profile: get("profile")?,
+        operation: get("operation").unwrap_or_default(),
+        name: get("name"),
+        requested_mask: get("requested_mask"),
+        denied_mask: get("denied_mask"),
+        pid: get("pid").and_then(|pid| pid.parse().ok()),
+        comm: get("comm"),
+        fields,
+    })
+}
+
+/// Splits on whitespace, keeping double-quoted values together.
+fn tokenize(input: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut quoted = false;
+    for (i, ch) in input.char_indices() {
+        match ch {
+            '"' => {
+                quoted = !quoted;
+                start.get_or_insert(i);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if let Some(s) = start.take() {
+                    tokens.push(&input[s..i]);
+                }
+            }
+            _ => {
+                start.get_or_insert(i);
+            }
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&input[s..]);
+    }
+    tokens
+}
+
+fn decode_value(key: &str, value: &str) -> String {
+    if let Some(inner) = value.strip_prefix('"') {
+        return inner.strip_suffix('"').unwrap_or(inner).to_string();
+    }
+    if ENCODED_FIELDS.contains(&key)
+        && let Some(decoded) = decode_hex(value)
+    {
+        return decoded;
+    }
+    value.to_string()
+}
+
+fn decode_hex(value: &str) -> Option<String> {
+    if value.is_empty() || !value.len().is_multiple_of(2) || !value.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let bytes: Option<Vec<u8>> = (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).ok())
+        .collect();
+    String::from_utf8(bytes?).ok()
+}
+
+fn parse_timestamp(line: &str) -> Option<f64> {
+    let rest = &line[line.find("audit(")? + "audit(".len()..];
+    rest[..rest.find(':')?].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_audit_log_record() {
+        let line = r#"type=AVC msg=audit(1700000000.123:456): apparmor="DENIED" operation="open" class="file" profile="/usr/sbin/cupsd" name="/etc/shadow" pid=1234 comm="cupsd" requested_mask="r" denied_mask="r" fsuid=0 ouid=0"#;
+        let event = parse_line(line).unwrap();
+        assert_eq!(event.verdict, Verdict::Denied);
+        assert_eq!(event.timestamp, Some(1700000000.123));
+        assert_eq!(event.profile, "/usr/sbin/cupsd");
+        assert_eq!(event.operation, "open");
+        assert_eq!(event.name.as_deref(), Some("/etc/shadow"));
+        assert_eq!(event.pid, Some(1234));
+        assert_eq!(event.comm.as_deref(), Some("cupsd"));
+        assert_eq!(event.requested_mask.as_deref(), Some("r"));
+        assert_eq!(event.denied_mask.as_deref(), Some("r"));
+        assert_eq!(event.field("class"), Some("file"));
+    }
+
+    #[test]
+    fn parses_kernel_log_record_with_hex_fields() {
+        let line = r#"Oct 15 12:00:00 host kernel: [ 12.3] audit: type=1400 audit(1700000001.5:7): apparmor="ALLOWED" operation="capable" profile="firefox" pid=99 comm=57656220436F6E74656E74 capability=12 capname="net_admin""#;
+        let event = parse_line(line).unwrap();
+        assert_eq!(event.verdict, Verdict::Allowed);
+        assert_eq!(event.comm.as_deref(), Some("Web Content"));
+        assert_eq!(event.field("capname"), Some("net_admin"));
+        assert_eq!(event.name, None);
+    }
+
+    #[test]
+    fn ignores_other_records() {
+        assert!(parse_line(r#"audit: type=1400 apparmor="STATUS" operation="profile_load" name="firefox""#).is_none());
+        assert!(parse_line("type=SYSCALL msg=audit(1.0:1): arch=c000003e").is_none());
+    }
+
+    #[test]
+    fn matches_hats_of_the_selected_profile() {
+        let line = r#"apparmor="DENIED" operation="open" profile="/usr/sbin/apache2//HANDLING_UNTRUSTED_INPUT" name="/srv""#;
+        let event = parse_line(line).unwrap();
+        assert!(event.matches_profile("/usr/sbin/apache2"));
+        assert!(!event.matches_profile("/usr/sbin/apache"));
+    }
+}
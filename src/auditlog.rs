@@ -0,0 +1,220 @@
+//! Tailing the audit log in the background and the state of the log pane.
+
+use crate::audit::{self, AuditEvent};
+use anyhow::{bail, Context, Result};
+use ratatui::widgets::ListState;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// How much of an existing log file is read as history when tailing starts.
+const HISTORY_BYTES: u64 = 256 * 1024;
+/// Oldest events are dropped beyond this.
+const MAX_EVENTS: usize = 5000;
+
+const AUDIT_LOG: &str = "/var/log/audit/audit.log";
+const KERN_LOG: &str = "/var/log/kern.log";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LogSource {
+    File(PathBuf),
+    /// `journalctl -k -f`
+    Journal,
+}
+
+impl LogSource {
+    /// `journal`, or a path to a log file.
+    pub fn parse(value: &str) -> LogSource {
+        match value {
+            "journal" => LogSource::Journal,
+            path => LogSource::File(PathBuf::from(path)),
+        }
+    }
+
+    /// The first readable of audit.log and kern.log, else the journal.
+    pub fn detect() -> LogSource {
+        [AUDIT_LOG, KERN_LOG]
+            .iter()
+            .map(Path::new)
+            .find(|path| File::open(path).is_ok())
+            .map_or(LogSource::Journal, |path| LogSource::File(path.to_path_buf()))
+    }
+
+    /// Reads the whole log without following it. The journal is only read
+    /// from `since` (Unix seconds) on; files are read in full.
+    pub fn read(&self, since: Option<f64>) -> Result<Vec<String>> {
+        match self {
+            LogSource::File(path) => {
+                let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+                let mut lines = Vec::new();
+                for line in BufReader::new(file).split(b'\n') {
+                    let line = line.with_context(|| format!("Failed to read {}", path.display()))?;
+                    lines.push(String::from_utf8_lossy(&line).into_owned());
+                }
+                Ok(lines)
+            }
+            LogSource::Journal => {
+                let mut cmd = Command::new("journalctl");
+                cmd.args(["-k", "--no-pager", "-o", "cat"]);
+                if let Some(since) = since {
+                    cmd.arg(format!("--since=@{}", since.floor()));
+                }
+                let output = cmd.stdin(Stdio::null()).output().context("Failed to execute journalctl")?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    bail!("journalctl failed: {}", stderr.trim());
+                }
+                Ok(String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect())
+            }
+        }
+    }
+
+    /// Starts a background thread that sends every log line, beginning
+    /// with some history. The thread stops once the receiver is dropped.
+    pub fn tail(&self) -> Result<Receiver<String>> {
+        let (tx, rx) = mpsc::channel();
+        match self {
+            LogSource::File(path) => {
+                let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+                let path = path.clone();
+                thread::spawn(move || tail_file(path, file, tx));
+            }
+            LogSource::Journal => {
+                let mut child = Command::new("journalctl")
+                    .args(["-k", "-f", "-n", "1000", "-o", "cat"])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .context("Failed to execute journalctl")?;
+                let stdout = child.stdout.take().context("journalctl has no stdout")?;
+                thread::spawn(move || {
+                    for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                        if tx.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    let _ = child.kill();
+                    let _ = child.wait();
+                });
+            }
+        }
+        Ok(rx)
+    }
+}
+
+fn tail_file(path: PathBuf, mut file: File, tx: Sender<String>) {
+    let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let skip_partial = len > HISTORY_BYTES;
+    if file.seek(SeekFrom::Start(len.saturating_sub(HISTORY_BYTES))).is_err() {
+        return;
+    }
+
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    if skip_partial {
+        let _ = reader.read_line(&mut line);
+    }
+
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => {
+                thread::sleep(Duration::from_millis(500));
+                if rotated(&path, reader.get_ref()) {
+                    match File::open(&path) {
+                        Ok(file) => reader = BufReader::new(file),
+                        Err(_) => continue,
+                    }
+                }
+            }
+            Ok(_) if line.ends_with('\n') => {
+                if tx.send(line.trim_end().to_string()).is_err() {
+                    return;
+                }
+            }
+            // A partially written line; rewind and wait for the rest.
+            Ok(n) => {
+                if reader.seek_relative(-(n as i64)).is_err() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(500));
+            }
+            Err(_) => return,
+        }
+    }
+}
+
+/// Whether `path` was replaced or truncated since `file` was opened.
+fn rotated(path: &Path, file: &File) -> bool {
+    let (Ok(current), Ok(open)) = (fs::metadata(path), file.metadata()) else {
+        return false;
+    };
+    let mut file = file;
+    let pos = file.stream_position().unwrap_or(0);
+    current.ino() != open.ino() || current.len() < pos
+}
+
+#[derive(Default)]
+pub struct LogPane {
+    pub visible: bool,
+    pub events: Vec<AuditEvent>,
+    pub state: ListState,
+    /// Show events of every profile instead of only the selected one.
+    pub show_all: bool,
+    /// Sequence numbers of events marked for rule generation.
+    pub marked: HashSet<u64>,
+    /// Sequence number of `events[0]`; stays stable as old events are dropped.
+    first_seq: u64,
+    rx: Option<Receiver<String>>,
+}
+
+impl LogPane {
+    pub fn is_tailing(&self) -> bool {
+        self.rx.is_some()
+    }
+
+    pub fn attach(&mut self, rx: Receiver<String>) {
+        self.rx = Some(rx);
+    }
+
+    /// Moves newly received lines into `events`. Returns how many arrived.
+    pub fn poll(&mut self) -> usize {
+        let Some(rx) = &self.rx else {
+            return 0;
+        };
+        let before = self.events.len();
+        self.events.extend(rx.try_iter().filter_map(|line| audit::parse_line(&line)));
+        let added = self.events.len() - before;
+        if self.events.len() > MAX_EVENTS {
+            let dropped = self.events.len() - MAX_EVENTS;
+            self.events.drain(..dropped);
+            self.first_seq += dropped as u64;
+            let first_seq = self.first_seq;
+            self.marked.retain(|&seq| seq >= first_seq);
+        }
+        added
+    }
+
+    /// Events to show, with their sequence numbers.
+    pub fn filtered<'a>(&'a self, profile: Option<&'a str>) -> Vec<(u64, &'a AuditEvent)> {
+        self.events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| self.show_all || profile.is_some_and(|name| event.matches_profile(name)))
+            .map(|(i, event)| (self.first_seq + i as u64, event))
+            .collect()
+    }
+
+    pub fn toggle_mark(&mut self, seq: u64) {
+        if !self.marked.remove(&seq) {
+            self.marked.insert(seq);
+        }
+    }
+}
@@ -0,0 +1,454 @@
+//! Everything that touches the running system goes through [`PolicyBackend`]
+//! so the TUI state can be driven without root.
+
+use crate::auditlog::LogSource;
+use crate::compile::{self, Report};
+use crate::config::Config;
+use crate::editor::{self, Editor};
+use crate::flags;
+use crate::index::{self, ProfileIndex, ProfileLocation, SNAPD_PROFILES};
+use crate::privileged::{Escalation, Privileged};
+use crate::procs::{self, ProcessInfo};
+use crate::profile::{Mode, Profile};
+use crate::status;
+use crate::unconfined::{self, Listener};
+use anyhow::{anyhow, Context, Result};
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::mpsc::Receiver;
+
+#[cfg(test)]
+mod fake;
+#[cfg(test)]
+pub use fake::FakeBackend;
+
+/// Outcome for one profile of a batch operation.
+#[derive(Clone, Debug)]
+pub struct BatchResult {
+    pub profile: String,
+    pub ok: bool,
+    /// Tool output, or why the profile was skipped.
+    pub message: String,
+}
+
+pub trait PolicyBackend {
+    /// Returns the loaded profiles, followed by the ones in the policy
+    /// directory that aren't loaded (e.g. linked from `disable/`) as
+    /// [`Mode::Disable`]. The kernel has no audit mode, so an enforcing
+    /// profile whose file sets the `audit` flag is listed as [`Mode::Audit`].
+    fn list_profiles(&mut self) -> Result<Vec<Profile>>;
+
+    /// Lists processes with the label they run under. Unconfined ones also
+    /// get their executable, unit and sockets.
+    fn list_processes(&mut self) -> Result<Vec<ProcessInfo>>;
+
+    /// Lists listening TCP and bound UDP sockets.
+    fn list_listeners(&mut self) -> Result<Vec<Listener>>;
+
+    /// Switches a profile to `mode`. A profile that isn't loaded is enabled
+    /// first: its `disable/` link is removed and its file loaded.
+    fn set_mode(&mut self, profile: &str, mode: Mode) -> Result<()>;
+
+    /// Switches several loaded profiles to a mode an `aa-*` tool sets, with
+    /// a single escalation. Profiles that need their flags rewritten or
+    /// their file enabled are reported as failed; those go through
+    /// [`PolicyBackend::write_profiles`].
+    fn set_modes(&mut self, profiles: &[String], mode: Mode) -> Result<Vec<BatchResult>>;
+
+    /// Reloads the whole policy.
+    fn reload(&mut self) -> Result<()>;
+
+    /// Compiles a policy file without loading it (`apparmor_parser -Q -K`).
+    /// Returns `None` when `apparmor_parser` isn't installed.
+    fn check_profile(&mut self, path: &Path) -> Result<Option<Report>>;
+
+    /// Loads or replaces the profiles of a single policy file.
+    fn reload_profile(&mut self, path: &Path) -> Result<Report>;
+
+    /// Like [`PolicyBackend::reload_profile`] for several files with a
+    /// single escalation. Reports are in the order of `paths`.
+    fn reload_profiles(&mut self, paths: &[PathBuf]) -> Result<Vec<Report>>;
+
+    /// Replaces several policy files and loads each of them with a single
+    /// escalation, after backing up the old contents. A file linked from
+    /// `disable/` is enabled. Reports are in the order of `files`.
+    fn write_profiles(&mut self, files: &[(PathBuf, String)]) -> Result<Vec<Report>>;
+
+    /// Finds the file and line a profile, hat or child profile is defined at.
+    fn locate_profile(&mut self, profile: &str) -> Result<ProfileLocation>;
+
+    /// Opens a copy of `path` in an editor at `line`. Returns the edited
+    /// contents, or `None` when nothing changed; the file itself is left
+    /// alone.
+    fn edit_file(&mut self, path: &Path, line: usize) -> Result<Option<String>>;
+
+    /// Names of the files under `abstractions/` in the policy directory.
+    fn list_abstractions(&self) -> Result<Vec<String>>;
+
+    /// Reads a policy file.
+    fn read_file(&self, path: &Path) -> Result<String>;
+
+    /// Replaces a policy file, with privileges if needed, after backing up
+    /// the old contents.
+    fn write_file(&mut self, path: &Path, content: &str) -> Result<()>;
+
+    /// Starts following the kernel audit log, one raw line per message.
+    fn audit_log(&mut self) -> Result<Receiver<String>>;
+
+    /// Reads the kernel audit log up to now without following it. Lines
+    /// before `since` (Unix seconds) may be left out.
+    fn audit_history(&mut self, since: Option<f64>) -> Result<Vec<String>>;
+}
+
+pub struct SystemBackend {
+    pub securityfs_root: PathBuf,
+    pub proc_root: PathBuf,
+    pub policy_dir: PathBuf,
+    pub privileged: Privileged,
+    pub log_source: Option<LogSource>,
+    pub editor: Editor,
+    /// Built on first lookup and dropped whenever profiles are reloaded.
+    index: Option<ProfileIndex>,
+}
+
+impl SystemBackend {
+    pub fn new(config: &Config) -> SystemBackend {
+        SystemBackend {
+            securityfs_root: config
+                .securityfs_root
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(status::SECURITYFS_ROOT)),
+            proc_root: PathBuf::from("/proc"),
+            policy_dir: config.policy_dir.clone().unwrap_or_else(|| PathBuf::from("/etc/apparmor.d")),
+            privileged: Privileged::new(Escalation::detect(config.escalation)),
+            log_source: config.log_source.clone(),
+            editor: Editor::resolve(config.editor.as_deref()),
+            index: None,
+        }
+    }
+}
+
+/// The `aa-*` tool that switches a profile to `mode`. Other modes are set
+/// by rewriting the profile flags.
+fn mode_command(mode: Mode) -> Option<&'static str> {
+    match mode {
+        Mode::Enforce => Some("aa-enforce"),
+        Mode::Complain => Some("aa-complain"),
+        Mode::Audit => Some("aa-audit"),
+        Mode::Disable => Some("aa-disable"),
+        Mode::Kill | Mode::Prompt | Mode::Unconfined | Mode::Unknown => None,
+    }
+}
+
+/// Whether `profile` is missing from the kernel's list, when that list
+/// could be read.
+fn is_unloaded(loaded: Option<&[Profile]>, profile: &str) -> bool {
+    loaded.is_some_and(|loaded| !loaded.iter().any(|p| p.name == profile))
+}
+
+impl SystemBackend {
+    /// Adds the profiles defined in the policy directory that aren't loaded.
+    fn add_unloaded(&mut self, profiles: &mut Vec<Profile>) {
+        let dirs = [self.policy_dir.clone(), PathBuf::from(SNAPD_PROFILES)];
+        let index = self.index.get_or_insert_with(|| ProfileIndex::scan(&dirs));
+        let mut unloaded: Vec<Profile> = index
+            .top_level()
+            .filter(|(name, location)| location.path.starts_with(&self.policy_dir) && !profiles.iter().any(|p| p.name == *name))
+            .map(|(name, _)| Profile::new(name, Mode::Disable))
+            .collect();
+        unloaded.sort_by(|a, b| a.name.cmp(&b.name));
+        profiles.extend(unloaded);
+    }
+
+    /// The mode `profile` gets at boot: disabled when its file is linked
+    /// from `disable/`, complain when from `force-complain/`, otherwise what
+    /// its flags say.
+    fn disk_mode(&self, profile: &str) -> Option<Mode> {
+        let index = self.index.as_ref()?;
+        let file = index.get(profile)?.path.file_name()?;
+        let linked = |dir: &str| self.policy_dir.join(dir).join(file).symlink_metadata().is_ok();
+        if linked("disable") {
+            Some(Mode::Disable)
+        } else if linked("force-complain") {
+            Some(Mode::Complain)
+        } else {
+            index.flags(profile).map(flags::flags_mode)
+        }
+    }
+
+    /// Removes the `disable/` link of `profile`'s file, if any, and loads
+    /// the file.
+    fn enable(&mut self, profile: &str) -> Result<()> {
+        let location = self.locate_profile(profile)?;
+        let name = location.path.file_name().ok_or_else(|| anyhow!("No file name in {}", location.path.display()))?;
+        let link = self.policy_dir.join("disable").join(name);
+        if link.symlink_metadata().is_ok() {
+            self.privileged.run_checked("rm", &["-f".as_ref(), link.as_os_str()])?;
+        }
+        let report = self.reload_profile(&location.path)?;
+        if !report.success {
+            let first = report.diagnostics.iter().find(|d| !d.warning);
+            let reason = first.map_or_else(|| "apparmor_parser failed".to_string(), |d| d.to_string());
+            return Err(anyhow!(reason).context(format!("Failed to load {}", location.path.display())));
+        }
+        Ok(())
+    }
+
+    /// Copies `files` under `staging`, which must not exist yet, then copies
+    /// them into place, drops their `disable/` links and loads them in one
+    /// escalated shell.
+    fn stage_and_load(&self, staging: &Path, files: &[(PathBuf, String)]) -> Result<Vec<(bool, String)>> {
+        // Root copies from here, so nobody else may have made the directory.
+        fs::DirBuilder::new()
+            .mode(0o700)
+            .create(staging)
+            .with_context(|| format!("Failed to create {}", staging.display()))?;
+        for (path, content) in files {
+            if let Ok(old) = fs::read_to_string(path) {
+                editor::backup(path, &old)?;
+            }
+            let staged = staging.join(path.strip_prefix("/").unwrap_or(path));
+            if let Some(dir) = staged.parent() {
+                fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+            }
+            fs::write(&staged, content).with_context(|| format!("Failed to write {}", staged.display()))?;
+        }
+        let paths: Vec<&PathBuf> = files.iter().map(|(path, _)| path).collect();
+        // `cat >` keeps the owner and permissions of the policy file.
+        let script = r#"cat -- "$0$1" > "$1" && rm -f -- "${1%/*}/disable/${1##*/}" && apparmor_parser -r -W "$1""#;
+        self.privileged.run_each("sh", &["-c", script, &staging.to_string_lossy()], &paths)
+    }
+
+    /// The loaded profiles in the modes the tool shows. The kernel reports
+    /// a profile with the `audit` flag as enforce, so that comes from the
+    /// file.
+    fn loaded_profiles(&mut self) -> Result<Vec<Profile>> {
+        let mut profiles = status::load_profiles(&self.securityfs_root)?;
+        let dirs = [self.policy_dir.clone(), PathBuf::from(SNAPD_PROFILES)];
+        let index = self.index.get_or_insert_with(|| ProfileIndex::scan(&dirs));
+        for profile in &mut profiles {
+            profile.mode = flags::shown_mode(profile.mode, index.flags(&profile.name));
+        }
+        Ok(profiles)
+    }
+
+    /// Whether switching `profile` to `mode` must go through its flags.
+    fn needs_rewrite(&self, loaded: &[Profile], profile: &str, mode: Mode) -> bool {
+        let from = loaded.iter().find(|p| p.name == profile).map(|p| p.mode);
+        flags::needs_rewrite(from, mode) || mode_command(mode).is_none()
+    }
+}
+
+impl PolicyBackend for SystemBackend {
+    fn list_profiles(&mut self) -> Result<Vec<Profile>> {
+        self.index = None;
+        let mut profiles = self.loaded_profiles()?;
+        if let Ok(processes) = procs::scan(&self.proc_root) {
+            procs::attach(&mut profiles, &processes);
+        }
+        self.add_unloaded(&mut profiles);
+        for profile in &mut profiles {
+            profile.disk_mode = self.disk_mode(&profile.name);
+        }
+        Ok(profiles)
+    }
+
+    fn list_processes(&mut self) -> Result<Vec<ProcessInfo>> {
+        let mut processes = procs::scan(&self.proc_root)?;
+        procs::inspect_unconfined(&self.proc_root, &mut processes);
+        Ok(processes)
+    }
+
+    fn list_listeners(&mut self) -> Result<Vec<Listener>> {
+        let mut listeners = Vec::new();
+        for protocol in unconfined::PROTOCOLS {
+            let path = self.proc_root.join("net").join(protocol);
+            match fs::read_to_string(&path) {
+                Ok(content) => listeners.extend(unconfined::parse_net(&content, protocol)),
+                // No IPv6 on this machine.
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err).with_context(|| format!("Failed to read {}", path.display())),
+            }
+        }
+        Ok(listeners)
+    }
+
+    fn set_mode(&mut self, profile: &str, mode: Mode) -> Result<()> {
+        if mode == Mode::Unknown {
+            return Err(anyhow!("Can't switch {} to an unknown mode", profile));
+        }
+        let mut loaded = self.loaded_profiles().ok();
+        if mode != Mode::Disable && is_unloaded(loaded.as_deref(), profile) {
+            self.enable(profile)?;
+            loaded = self.loaded_profiles().ok();
+            // It may already come up in the wanted mode.
+            if loaded.iter().flatten().any(|p| p.name == profile && p.mode == mode) {
+                return Ok(());
+            }
+        }
+        let loaded = loaded.unwrap_or_default();
+        match mode_command(mode) {
+            Some(cmd) if !self.needs_rewrite(&loaded, profile, mode) => self.privileged.run_checked(cmd, &[profile]),
+            _ => flags::switch_mode(self, profile, mode),
+        }
+    }
+
+    fn set_modes(&mut self, profiles: &[String], mode: Mode) -> Result<Vec<BatchResult>> {
+        let loaded = self.loaded_profiles().ok();
+        let (single, tool): (Vec<String>, Vec<String>) = profiles.iter().cloned().partition(|profile| {
+            let enable = mode != Mode::Disable && is_unloaded(loaded.as_deref(), profile);
+            enable || self.needs_rewrite(loaded.as_deref().unwrap_or_default(), profile, mode)
+        });
+
+        let message = format!("{} mode has to be written to the policy file", mode);
+        let mut results: Vec<BatchResult> =
+            single.into_iter().map(|profile| BatchResult { profile, ok: false, message: message.clone() }).collect();
+        if let (Some(cmd), false) = (mode_command(mode), tool.is_empty()) {
+            let outcomes = self.privileged.run_each(cmd, &[], &tool)?;
+            for (profile, (ok, message)) in tool.into_iter().zip(outcomes) {
+                results.push(BatchResult { profile, ok, message });
+            }
+        }
+        results.sort_by_key(|result| profiles.iter().position(|p| *p == result.profile));
+        Ok(results)
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        self.privileged.run_checked("systemctl", &["reload", "apparmor"])
+    }
+
+    fn check_profile(&mut self, path: &Path) -> Result<Option<Report>> {
+        // Compiling without loading needs no privileges. The parser lives
+        // in sbin, which often isn't on a normal user's PATH.
+        for program in ["apparmor_parser", "/usr/sbin/apparmor_parser", "/sbin/apparmor_parser"] {
+            let output = match Command::new(program).args(["-Q".as_ref(), "-K".as_ref(), path.as_os_str()]).output() {
+                Ok(output) => output,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err).context("Failed to execute apparmor_parser"),
+            };
+            return Ok(Some(Report {
+                success: output.status.success(),
+                diagnostics: compile::parse_diagnostics(&String::from_utf8_lossy(&output.stderr), path),
+            }));
+        }
+        Ok(None)
+    }
+
+    fn reload_profile(&mut self, path: &Path) -> Result<Report> {
+        let output = self.privileged.output("apparmor_parser", &["-r".as_ref(), "-W".as_ref(), path.as_os_str()])?;
+        Ok(Report {
+            success: output.status.success(),
+            diagnostics: compile::parse_diagnostics(&String::from_utf8_lossy(&output.stderr), path),
+        })
+    }
+
+    fn reload_profiles(&mut self, paths: &[PathBuf]) -> Result<Vec<Report>> {
+        let results = self.privileged.run_each("apparmor_parser", &["-r", "-W"], paths)?;
+        Ok(paths
+            .iter()
+            .zip(results)
+            .map(|(path, (success, output))| Report { success, diagnostics: compile::parse_diagnostics(&output, path) })
+            .collect())
+    }
+
+    fn write_profiles(&mut self, files: &[(PathBuf, String)]) -> Result<Vec<Report>> {
+        let staging = env::temp_dir().join(format!("apparmor-tui-{}", process::id()));
+        let results = self.stage_and_load(&staging, files);
+        let _ = fs::remove_dir_all(&staging);
+        let paths: Vec<&PathBuf> = files.iter().map(|(path, _)| path).collect();
+        Ok(paths
+            .into_iter()
+            .zip(results?)
+            .map(|(path, (success, output))| Report { success, diagnostics: compile::parse_diagnostics(&output, path) })
+            .collect())
+    }
+
+    fn locate_profile(&mut self, profile: &str) -> Result<ProfileLocation> {
+        let dirs = [self.policy_dir.clone(), PathBuf::from(SNAPD_PROFILES)];
+        let index = self.index.get_or_insert_with(|| ProfileIndex::scan(&dirs));
+        if let Some(location) = index.get(profile) {
+            return Ok(location.clone());
+        }
+
+        // Old-style file naming: /usr/bin/foo lives in usr.bin.foo.
+        let top = profile.split("//").next().unwrap_or(profile);
+        let path = self.policy_dir.join(top.trim_start_matches('/').replace('/', "."));
+        if path.is_file() {
+            return Ok(ProfileLocation { path, line: 1 });
+        }
+        Err(anyhow!("No policy file defines profile {}", profile))
+    }
+
+    fn edit_file(&mut self, path: &Path, line: usize) -> Result<Option<String>> {
+        self.editor.edit(path, line)
+    }
+
+    fn list_abstractions(&self) -> Result<Vec<String>> {
+        let dir = self.policy_dir.join("abstractions");
+        let entries = fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| index::is_policy_file(&entry.path()))
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<String> {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+    }
+
+    fn write_file(&mut self, path: &Path, content: &str) -> Result<()> {
+        if let Ok(old) = fs::read_to_string(path) {
+            editor::backup(path, &old)?;
+        }
+        self.privileged.write_file(path, content)
+    }
+
+    fn audit_log(&mut self) -> Result<Receiver<String>> {
+        self.log_source.clone().unwrap_or_else(LogSource::detect).tail()
+    }
+
+    fn audit_history(&mut self, since: Option<f64>) -> Result<Vec<String>> {
+        self.log_source.clone().unwrap_or_else(LogSource::detect).read(since)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn unloaded_policy_files_are_listed_as_disabled() {
+        let root = std::env::temp_dir().join(format!("apparmor-tui-test-{}", process::id()));
+        let policy_dir = root.join("apparmor.d");
+        fs::create_dir_all(root.join("apparmor")).unwrap();
+        fs::create_dir_all(policy_dir.join("disable")).unwrap();
+        fs::write(root.join("apparmor/profiles"), "firefox (enforce)\n").unwrap();
+        fs::write(policy_dir.join("firefox"), "profile firefox /usr/lib/firefox/firefox {\n}\n").unwrap();
+        fs::write(policy_dir.join("usr.sbin.cupsd"), "/usr/sbin/cupsd {\n  ^hat {\n  }\n}\n").unwrap();
+        fs::write(policy_dir.join("disable/usr.sbin.cupsd"), "").unwrap();
+        fs::write(policy_dir.join("usr.bin.man"), "profile man /usr/bin/man {\n}\n").unwrap();
+
+        let config = Config { securityfs_root: Some(root.clone()), policy_dir: Some(policy_dir), ..Config::default() };
+        let profiles = SystemBackend::new(&config).list_profiles();
+        fs::remove_dir_all(&root).unwrap();
+        let profiles = profiles.unwrap();
+        assert!(!profiles.iter().any(Profile::mode_differs), "{:?}", profiles);
+        let profiles: Vec<(String, Mode, Option<Mode>)> = profiles.into_iter().map(|p| (p.name, p.mode, p.disk_mode)).collect();
+        assert_eq!(
+            profiles,
+            [
+                ("firefox".to_string(), Mode::Enforce, Some(Mode::Enforce)),
+                ("/usr/sbin/cupsd".to_string(), Mode::Disable, Some(Mode::Disable)),
+                ("man".to_string(), Mode::Disable, Some(Mode::Enforce)),
+            ]
+        );
+    }
+}
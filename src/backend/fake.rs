@@ -0,0 +1,217 @@
+use super::{BatchResult, PolicyBackend};
+use crate::compile::{Diagnostic, Report};
+use crate::flags;
+use crate::index::{ProfileIndex, ProfileLocation};
+use crate::policy;
+use crate::procs::{self, ProcessInfo};
+use crate::profile::{Mode, Profile};
+use crate::unconfined::Listener;
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+/// In-memory backend for tests. Every call is recorded in `calls`.
+#[derive(Default)]
+pub struct FakeBackend {
+    pub profiles: Vec<Profile>,
+    pub processes: Vec<ProcessInfo>,
+    pub listeners: Vec<Listener>,
+    pub calls: Vec<String>,
+    /// What `apparmor_parser` reports; any non-warning fails the load.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Policy files by path.
+    pub files: BTreeMap<PathBuf, String>,
+    /// What the "user" leaves in the editor; `None` means no change.
+    pub edited: Option<String>,
+    /// Lines handed out by `audit_log` and `audit_history`.
+    pub log_lines: Vec<String>,
+    /// Modes the "kernel" ignores when a file asks for them.
+    pub unsupported: Vec<Mode>,
+    /// When set, every mutating call fails with this message.
+    pub fail_with: Option<String>,
+}
+
+impl FakeBackend {
+    pub fn with_profiles(profiles: &[(&str, Mode)]) -> FakeBackend {
+        FakeBackend {
+            profiles: profiles.iter().map(|(name, mode)| Profile::new(*name, *mode)).collect(),
+            ..FakeBackend::default()
+        }
+    }
+
+    fn index(&self) -> ProfileIndex {
+        let mut index = ProfileIndex::default();
+        for (path, content) in &self.files {
+            index.index_file(path, content);
+        }
+        index
+    }
+
+    /// Loading picks up the mode flags of the profiles in the file, as the
+    /// kernel reports them.
+    fn load(&mut self, path: &Path) -> Report {
+        let success = self.diagnostics.iter().all(|d| d.warning);
+        if success && let Some(Ok(policy)) = self.files.get(path).map(|source| policy::parse(source)) {
+            for (name, found, _) in policy.all_profiles() {
+                let mode = flags::flags_mode(&found.flags);
+                if let Some(profile) = self.profiles.iter_mut().find(|p| p.name == name)
+                    && !self.unsupported.contains(&mode)
+                {
+                    profile.mode = kernel_mode(mode);
+                }
+            }
+        }
+        Report { success, diagnostics: self.diagnostics.clone() }
+    }
+
+    fn check(&self) -> Result<()> {
+        match &self.fail_with {
+            Some(msg) => Err(anyhow!("{}", msg)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The mode the kernel reports for a profile loaded in `mode`. It has no
+/// audit mode: a profile with only the `audit` flag loads as enforce.
+fn kernel_mode(mode: Mode) -> Mode {
+    if mode == Mode::Audit { Mode::Enforce } else { mode }
+}
+
+impl PolicyBackend for FakeBackend {
+    fn list_profiles(&mut self) -> Result<Vec<Profile>> {
+        self.calls.push("list".to_string());
+        let mut profiles = self.profiles.clone();
+        if !self.processes.is_empty() {
+            procs::attach(&mut profiles, &self.processes);
+        }
+        let index = self.index();
+        for profile in &mut profiles {
+            profile.mode = flags::shown_mode(profile.mode, index.flags(&profile.name));
+            profile.disk_mode = index.flags(&profile.name).map(flags::flags_mode);
+        }
+        Ok(profiles)
+    }
+
+    fn list_processes(&mut self) -> Result<Vec<ProcessInfo>> {
+        self.calls.push("list_processes".to_string());
+        Ok(self.processes.clone())
+    }
+
+    fn list_listeners(&mut self) -> Result<Vec<Listener>> {
+        Ok(self.listeners.clone())
+    }
+
+    fn set_mode(&mut self, profile: &str, mode: Mode) -> Result<()> {
+        self.calls.push(format!("set_mode {} {:?}", profile, mode));
+        self.check()?;
+        // Like the aa-* tools, rewrite the mode flag in the file.
+        if mode != Mode::Disable
+            && let Some(location) = self.index().get(profile).cloned()
+            && let Some(source) = self.files.get(&location.path)
+        {
+            let updated = flags::set_flags(source, profile, &flags::with_mode(&flags::profile_flags(source, profile)?, mode))?;
+            self.files.insert(location.path, updated);
+        }
+        let entry = self
+            .profiles
+            .iter_mut()
+            .find(|p| p.name == profile)
+            .ok_or_else(|| anyhow!("no such profile: {}", profile))?;
+        entry.mode = kernel_mode(mode);
+        Ok(())
+    }
+
+    fn set_modes(&mut self, profiles: &[String], mode: Mode) -> Result<Vec<BatchResult>> {
+        self.calls.push(format!("set_modes {} {:?}", profiles.join(","), mode));
+        self.check()?;
+        let mut results = Vec::new();
+        for profile in profiles {
+            let message = match self.set_mode(profile, mode) {
+                Ok(()) => String::new(),
+                Err(err) => err.to_string(),
+            };
+            results.push(BatchResult { profile: profile.clone(), ok: message.is_empty(), message });
+        }
+        Ok(results)
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        self.calls.push("reload".to_string());
+        self.check()
+    }
+
+    fn check_profile(&mut self, path: &Path) -> Result<Option<Report>> {
+        self.calls.push(format!("check_profile {}", path.display()));
+        Ok(Some(Report {
+            success: self.diagnostics.iter().all(|d| d.warning),
+            diagnostics: self.diagnostics.clone(),
+        }))
+    }
+
+    fn reload_profile(&mut self, path: &Path) -> Result<Report> {
+        self.calls.push(format!("reload_profile {}", path.display()));
+        self.check()?;
+        Ok(self.load(path))
+    }
+
+    fn reload_profiles(&mut self, paths: &[PathBuf]) -> Result<Vec<Report>> {
+        self.calls.push(format!("reload_profiles {}", paths.len()));
+        self.check()?;
+        let success = self.diagnostics.iter().all(|d| d.warning);
+        Ok(paths.iter().map(|_| Report { success, diagnostics: self.diagnostics.clone() }).collect())
+    }
+
+    fn write_profiles(&mut self, files: &[(PathBuf, String)]) -> Result<Vec<Report>> {
+        self.calls.push(format!("write_profiles {}", files.len()));
+        self.check()?;
+        Ok(files
+            .iter()
+            .map(|(path, content)| {
+                self.files.insert(path.clone(), content.clone());
+                self.load(path)
+            })
+            .collect())
+    }
+
+    fn locate_profile(&mut self, profile: &str) -> Result<ProfileLocation> {
+        self.index().get(profile).cloned().ok_or_else(|| anyhow!("No policy file defines profile {}", profile))
+    }
+
+    fn edit_file(&mut self, path: &Path, line: usize) -> Result<Option<String>> {
+        self.calls.push(format!("edit {}:{}", path.display(), line));
+        self.check()?;
+        Ok(self.edited.clone())
+    }
+
+    fn list_abstractions(&self) -> Result<Vec<String>> {
+        let names = self.files.keys().filter(|path| path.parent().is_some_and(|dir| dir.ends_with("abstractions")));
+        Ok(names.filter_map(|path| Some(path.file_name()?.to_str()?.to_string())).collect())
+    }
+
+    fn read_file(&self, path: &Path) -> Result<String> {
+        self.files.get(path).cloned().with_context(|| format!("Failed to read {}", path.display()))
+    }
+
+    fn write_file(&mut self, path: &Path, content: &str) -> Result<()> {
+        self.calls.push(format!("write {}", path.display()));
+        self.check()?;
+        self.files.insert(path.to_path_buf(), content.to_string());
+        Ok(())
+    }
+
+    fn audit_log(&mut self) -> Result<Receiver<String>> {
+        self.calls.push("audit_log".to_string());
+        let (tx, rx) = mpsc::channel();
+        for line in &self.log_lines {
+            tx.send(line.clone())?;
+        }
+        Ok(rx)
+    }
+
+    fn audit_history(&mut self, _since: Option<f64>) -> Result<Vec<String>> {
+        self.calls.push("audit_history".to_string());
+        Ok(self.log_lines.clone())
+    }
+}
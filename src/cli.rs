@@ -0,0 +1,388 @@
+//! Subcommands for scripts, run on the same backend as the TUI. Output is
+//! one record per line with tab-separated fields, or JSON for `list --json`.
+
+use crate::audit::{self, Verdict};
+use crate::backend::{PolicyBackend, SystemBackend};
+use crate::compile;
+use crate::config::Config;
+use crate::detail::Detail;
+use crate::json::Value;
+use crate::profile::{Mode, Profile};
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const USAGE: &str = "\
+Usage: apparmor-tui [COMMAND]
+
+Without a command the interactive interface starts.
+
+Commands:
+  list [--mode MODE] [--json]  Profiles: name, loaded mode, mode on disk
+  set-mode PROFILE MODE        Switch a profile to enforce, complain, prompt,
+                               audit, kill, unconfined or disable
+  reload [PROFILE]             Reload the policy, or the file defining PROFILE
+  show PROFILE                 File, attachment, flags, rules and processes
+  denials [--since AGE]        Logged denials: time, profile, operation, name,
+                               denied mask, command, pid. AGE is e.g. 90s,
+                               10m, 2h or 1d
+  check                        Compile every policy file without loading it
+
+Exit status is 0 on success, 1 when the command or a check failed and 2 on
+usage errors.";
+
+/// Exit status for usage errors; 1 is any other failure.
+const EXIT_USAGE: i32 = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    List { mode: Option<Mode>, json: bool },
+    SetMode { profile: String, mode: Mode },
+    Reload { profile: Option<String> },
+    Show { profile: String },
+    /// Only denials logged in the last `since`.
+    Denials { since: Option<Duration> },
+    Check,
+    Help,
+}
+
+impl Command {
+    /// Parses the arguments after the program name.
+    pub fn parse(args: &[String]) -> Result<Command> {
+        let (name, rest) = args.split_first().ok_or_else(|| anyhow!("no command given"))?;
+        let mut rest = rest.iter().map(String::as_str);
+        let mut positional = Vec::new();
+        let mut mode = None;
+        let mut json = false;
+        let mut since = None;
+        while let Some(arg) = rest.next() {
+            match arg {
+                "--mode" if name == "list" => mode = Some(parse_mode(rest.next().unwrap_or_default())?),
+                "--json" if name == "list" => json = true,
+                "--since" if name == "denials" => since = Some(parse_age(rest.next().unwrap_or_default())?),
+                "-h" | "--help" => return Ok(Command::Help),
+                option if option.starts_with('-') => bail!("unknown option {} for {}", option, name),
+                arg => positional.push(arg.to_string()),
+            }
+        }
+
+        let command = match name.as_str() {
+            "list" => Command::List { mode, json },
+            "set-mode" => {
+                let [profile, mode] = <[String; 2]>::try_from(positional).map_err(|_| anyhow!("set-mode takes PROFILE MODE"))?;
+                return Ok(Command::SetMode { mode: parse_mode(&mode)?, profile });
+            }
+            "reload" if positional.len() <= 1 => return Ok(Command::Reload { profile: positional.pop() }),
+            "reload" => bail!("reload takes at most one PROFILE"),
+            "show" => {
+                let [profile] = <[String; 1]>::try_from(positional).map_err(|_| anyhow!("show takes one PROFILE"))?;
+                return Ok(Command::Show { profile });
+            }
+            "denials" => Command::Denials { since },
+            "check" => Command::Check,
+            "help" => Command::Help,
+            other => bail!("unknown command {}", other),
+        };
+        if let Some(arg) = positional.first() {
+            bail!("unexpected argument {} for {}", arg, name);
+        }
+        Ok(command)
+    }
+}
+
+fn parse_mode(name: &str) -> Result<Mode> {
+    Mode::parse(name).ok_or_else(|| {
+        let modes: Vec<&str> = Mode::ALL.iter().filter(|&&mode| mode != Mode::Unknown).map(|mode| mode.as_str()).collect();
+        anyhow!("unknown mode '{}', expected one of {}", name, modes.join(", "))
+    })
+}
+
+/// `90s`, `10m`, `2h`, `1d`, or plain seconds.
+fn parse_age(age: &str) -> Result<Duration> {
+    let (number, unit) = match age.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => age.split_at(i),
+        None => (age, "s"),
+    };
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => 0,
+    };
+    match number.parse::<u64>().ok().and_then(|n| n.checked_mul(seconds)) {
+        Some(secs) if seconds > 0 => Ok(Duration::from_secs(secs)),
+        _ => bail!("invalid age '{}', expected e.g. 90s, 10m, 2h or 1d", age),
+    }
+}
+
+/// Runs the command in `args` against the system and returns the exit
+/// status.
+pub fn main(args: &[String]) -> i32 {
+    let command = match Command::parse(args) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("apparmor-tui: {}\n\n{}", err, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    let result = Config::load().and_then(|config| {
+        let mut backend = SystemBackend::new(&config);
+        run(&mut backend, &command, &mut io::stdout().lock())
+    });
+    match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(err) => {
+            eprintln!("apparmor-tui: {:#}", err);
+            1
+        }
+    }
+}
+
+/// Runs `command`, writing its output to `out`. Returns `false` when it
+/// completed but found problems, such as a policy file that fails to
+/// compile.
+pub fn run(backend: &mut dyn PolicyBackend, command: &Command, out: &mut dyn Write) -> Result<bool> {
+    match command {
+        Command::List { mode, json } => list(backend, *mode, *json, out),
+        Command::SetMode { profile, mode } => {
+            backend.set_mode(profile, *mode)?;
+            writeln!(out, "{}\t{}", profile, mode)?;
+            Ok(true)
+        }
+        Command::Reload { profile: None } => {
+            backend.reload()?;
+            Ok(true)
+        }
+        Command::Reload { profile: Some(profile) } => {
+            let location = backend.locate_profile(profile)?;
+            let report = backend.reload_profile(&location.path)?;
+            for diagnostic in &report.diagnostics {
+                writeln!(out, "{}", diagnostic)?;
+            }
+            Ok(report.success)
+        }
+        Command::Show { profile } => show(backend, profile, out),
+        Command::Denials { since } => denials(backend, *since, out),
+        Command::Check => check(backend, out),
+        Command::Help => {
+            writeln!(out, "{}", USAGE)?;
+            Ok(true)
+        }
+    }
+}
+
+fn list(backend: &mut dyn PolicyBackend, mode: Option<Mode>, json: bool, out: &mut dyn Write) -> Result<bool> {
+    let mut profiles = backend.list_profiles()?;
+    profiles.retain(|p| mode.is_none_or(|mode| p.mode == mode));
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    if json {
+        writeln!(out, "{}", Value::Array(profiles.iter().map(profile_json).collect()))?;
+    } else {
+        for profile in &profiles {
+            writeln!(out, "{}\t{}\t{}", profile.name, profile.mode, profile.disk_mode.map_or("-", Mode::as_str))?;
+        }
+    }
+    Ok(true)
+}
+
+fn profile_json(profile: &Profile) -> Value {
+    let string = |s: &str| Value::String(s.to_string());
+    Value::Object(vec![
+        ("name".to_string(), string(&profile.name)),
+        ("mode".to_string(), string(profile.mode.as_str())),
+        ("disk_mode".to_string(), profile.disk_mode.map_or(Value::Null, |mode| string(mode.as_str()))),
+        (
+            "processes".to_string(),
+            Value::Array(profile.processes.iter().map(|p| Value::Number(p.pid.into())).collect()),
+        ),
+    ])
+}
+
+fn show(backend: &mut dyn PolicyBackend, name: &str, out: &mut dyn Write) -> Result<bool> {
+    let profiles = backend.list_profiles()?;
+    let profile = profiles.iter().find(|p| p.name == name).ok_or_else(|| anyhow!("No profile named {}", name))?;
+    let detail = Detail::load(backend, name);
+    let join = |items: &[String]| items.join(", ");
+    writeln!(out, "profile\t{}", profile.name)?;
+    writeln!(out, "mode\t{}", profile.mode)?;
+    writeln!(out, "disk mode\t{}", profile.disk_mode.map_or("-", Mode::as_str))?;
+    match &detail.location {
+        Some(location) => writeln!(out, "file\t{}:{}", location.path.display(), location.line)?,
+        None => writeln!(out, "file\t-")?,
+    }
+    writeln!(out, "attachment\t{}", detail.attachment.as_deref().unwrap_or("-"))?;
+    writeln!(out, "flags\t{}", join(&detail.flags))?;
+    writeln!(out, "includes\t{}", join(&detail.includes))?;
+    let rules: Vec<String> = detail.rule_counts.iter().map(|(kind, count)| format!("{} {}", kind, count)).collect();
+    writeln!(out, "rules\t{}", join(&rules))?;
+    let pids: Vec<String> = profile.processes.iter().map(|p| p.pid.to_string()).collect();
+    writeln!(out, "processes\t{}", pids.join(" "))?;
+    if let Some(error) = &detail.error {
+        writeln!(out, "error\t{}", error)?;
+    }
+    Ok(detail.error.is_none())
+}
+
+/// Prints the denials in the log history. The log is read up to now, not
+/// followed.
+fn denials(backend: &mut dyn PolicyBackend, since: Option<Duration>, out: &mut dyn Write) -> Result<bool> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+    let cutoff = since.map(|since| now - since.as_secs_f64());
+    for line in backend.audit_history(cutoff)? {
+        let Some(event) = audit::parse_line(&line).filter(|event| event.verdict == Verdict::Denied) else {
+            continue;
+        };
+        if let Some(cutoff) = cutoff
+            && event.timestamp.is_none_or(|time| time < cutoff)
+        {
+            continue;
+        }
+        let field = |value: Option<&str>| value.unwrap_or("-").to_string();
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            event.timestamp.map_or("-".to_string(), |time| format!("{:.3}", time)),
+            event.profile,
+            event.operation,
+            field(event.name.as_deref()),
+            field(event.denied_mask.as_deref()),
+            field(event.comm.as_deref()),
+            event.pid.map_or("-".to_string(), |pid| pid.to_string()),
+        )?;
+    }
+    Ok(true)
+}
+
+/// Compiles each file that defines a listed profile, the way edits are
+/// checked before loading.
+fn check(backend: &mut dyn PolicyBackend, out: &mut dyn Write) -> Result<bool> {
+    let mut paths = BTreeSet::new();
+    for profile in backend.list_profiles()? {
+        // Profiles loaded from elsewhere (e.g. by snapd from a cache) have
+        // no file to check.
+        if let Ok(location) = backend.locate_profile(&profile.name) {
+            paths.insert(location.path);
+        }
+    }
+    let mut passed = true;
+    for path in paths {
+        let validation = compile::validate(backend, &path)?;
+        let status = if validation.passed() { "ok" } else { "failed" };
+        writeln!(out, "{}\t{}", path.display(), status)?;
+        for diagnostic in &validation.diagnostics {
+            writeln!(out, "  {}", diagnostic)?;
+        }
+        passed &= validation.passed();
+    }
+    Ok(passed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use crate::auditlog::LogSource;
+    use crate::compile::Diagnostic;
+    use std::path::PathBuf;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn output(backend: &mut dyn PolicyBackend, line: &str) -> (bool, String) {
+        let mut out = Vec::new();
+        let ok = run(backend, &Command::parse(&args(line)).unwrap(), &mut out).unwrap();
+        (ok, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn parses_arguments() {
+        assert_eq!(
+            Command::parse(&args("list --json --mode complain")).unwrap(),
+            Command::List { mode: Some(Mode::Complain), json: true }
+        );
+        assert_eq!(
+            Command::parse(&args("set-mode firefox kill")).unwrap(),
+            Command::SetMode { profile: "firefox".to_string(), mode: Mode::Kill }
+        );
+        assert_eq!(Command::parse(&args("reload")).unwrap(), Command::Reload { profile: None });
+        assert_eq!(
+            Command::parse(&args("denials --since 10m")).unwrap(),
+            Command::Denials { since: Some(Duration::from_secs(600)) }
+        );
+        assert_eq!(Command::parse(&args("check --help")).unwrap(), Command::Help);
+        for bad in ["frobnicate", "list --mode strict", "set-mode firefox", "denials --since 3w", "denials --since 999999999999999999d", "check extra", "show --json x"] {
+            assert!(Command::parse(&args(bad)).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn lists_and_switches_profiles() {
+        let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Enforce), ("cupsd", Mode::Complain)]);
+        backend.files.insert(PathBuf::from("/etc/apparmor.d/firefox"), "profile firefox /usr/lib/firefox/firefox {\n}\n".to_string());
+        assert_eq!(output(&mut backend, "list"), (true, "cupsd\tcomplain\t-\nfirefox\tenforce\tenforce\n".to_string()));
+        assert_eq!(output(&mut backend, "set-mode firefox complain"), (true, "firefox\tcomplain\n".to_string()));
+        assert_eq!(
+            output(&mut backend, "list --mode complain --json"),
+            (
+                true,
+                r#"[{"name":"cupsd","mode":"complain","disk_mode":null,"processes":[]},{"name":"firefox","mode":"complain","disk_mode":"complain","processes":[]}]"#.to_string() + "\n"
+            )
+        );
+        let (ok, shown) = output(&mut backend, "show firefox");
+        assert!(ok);
+        assert!(shown.contains("file\t/etc/apparmor.d/firefox:1\nattachment\t/usr/lib/firefox/firefox\nflags\tcomplain\n"), "{}", shown);
+    }
+
+    #[test]
+    fn check_and_reload_fail_on_parser_errors() {
+        let mut backend = FakeBackend::with_profiles(&[("firefox", Mode::Enforce)]);
+        backend.files.insert(PathBuf::from("/etc/apparmor.d/firefox"), "profile firefox {\n}\n".to_string());
+        assert_eq!(output(&mut backend, "check"), (true, "/etc/apparmor.d/firefox\tok\n".to_string()));
+        backend.diagnostics = vec![Diagnostic {
+            file: Some(PathBuf::from("/etc/apparmor.d/firefox")),
+            line: Some(2),
+            message: "syntax error".to_string(),
+            warning: false,
+        }];
+        let (ok, checked) = output(&mut backend, "check");
+        assert!(!ok);
+        assert_eq!(checked, "/etc/apparmor.d/firefox\tfailed\n  /etc/apparmor.d/firefox:2: syntax error\n");
+        assert_eq!(output(&mut backend, "reload firefox"), (false, "/etc/apparmor.d/firefox:2: syntax error\n".to_string()));
+    }
+
+    #[test]
+    fn denials_are_filtered_by_age() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let log_lines = vec![
+            format!(r#"audit: type=1400 audit({}.000:1): apparmor="DENIED" operation="open" profile="firefox" name="/etc/shadow" pid=7 comm="firefox" denied_mask="r""#, now - 30),
+            format!(r#"audit: type=1400 audit({}.000:2): apparmor="DENIED" operation="open" profile="cupsd" name="/etc/passwd" pid=9 comm="cupsd" denied_mask="r""#, now - 7200),
+            format!(r#"audit: type=1400 audit({}.000:3): apparmor="ALLOWED" operation="open" profile="cupsd" name="/tmp/x" pid=9"#, now - 10),
+        ];
+        let mut backend = FakeBackend { log_lines, ..FakeBackend::default() };
+        let (_, all) = output(&mut backend, "denials");
+        assert_eq!(all.lines().count(), 2);
+        let (ok, recent) = output(&mut backend, "denials --since 1h");
+        assert!(ok);
+        assert_eq!(recent, format!("{}.000\tfirefox\topen\t/etc/shadow\tr\tfirefox\t7\n", now - 30));
+    }
+
+    #[test]
+    fn denials_read_a_log_file_to_the_end() {
+        let path = std::env::temp_dir().join(format!("apparmor-tui-test-{}-audit.log", std::process::id()));
+        let denial = r#"type=AVC msg=audit(1700000000.000:1): apparmor="DENIED" operation="open" profile="firefox" name="/etc/shadow" pid=7 comm="firefox" denied_mask="r""#;
+        // Older than the history the TUI starts with, and followed by more
+        // than it reads.
+        let filler = "type=SYSCALL msg=audit(1700000001.000:2): arch=c000003e syscall=257 success=yes exit=3\n".repeat(4000);
+        std::fs::write(&path, format!("{}\n{}", denial, filler)).unwrap();
+        let config = Config { log_source: Some(LogSource::File(path.clone())), ..Config::default() };
+        let mut backend = SystemBackend::new(&config);
+        let (ok, shown) = output(&mut backend, "denials");
+        std::fs::remove_file(&path).unwrap();
+        assert!(ok);
+        assert_eq!(shown, "1700000000.000\tfirefox\topen\t/etc/shadow\tr\tfirefox\t7\n");
+    }
+}
@@ -0,0 +1,209 @@
+//! Running `apparmor_parser` on single policy files and making sense of
+//! what it prints.
+
+use crate::backend::PolicyBackend;
+use crate::policy;
+use anyhow::Result;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub message: String,
+    pub warning: bool,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+        }
+        if let Some(line) = self.line {
+            write!(f, "{}:", line)?;
+        }
+        if self.file.is_some() || self.line.is_some() {
+            f.write_str(" ")?;
+        }
+        if self.warning {
+            f.write_str("warning: ")?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Outcome of an `apparmor_parser` run.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub success: bool,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Parses `apparmor_parser` stderr. Messages without a file name are
+/// attributed to `file`.
+///
+/// Recognised forms:
+///
+/// ```text
+/// AppArmor parser error for /etc/apparmor.d/foo in profile /etc/apparmor.d/foo at line 12: syntax error
+/// AppArmor parser error at line 5: Could not open 'abstractions/bar'
+/// Warning from /etc/apparmor.d/foo (/etc/apparmor.d/foo line 10): unknown flag
+/// ```
+pub fn parse_diagnostics(stderr: &str, file: &Path) -> Vec<Diagnostic> {
+    stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| parse_line(line, file))
+        .collect()
+}
+
+fn parse_line(line: &str, file: &Path) -> Diagnostic {
+    if let Some(rest) = line.strip_prefix("AppArmor parser error") {
+        let (location, message) = rest.split_once(": ").unwrap_or((rest, ""));
+        // "in profile X" names the file the error is in, e.g. an abstraction.
+        let in_file = after(location, " in profile ")
+            .or_else(|| after(location, " in "))
+            .or_else(|| after(location, " for "));
+        let line_no = after(location, "at line ").and_then(|n| n.trim().parse().ok());
+        return Diagnostic {
+            file: Some(in_file.map_or_else(|| file.to_path_buf(), PathBuf::from)),
+            line: line_no,
+            message: message.trim().to_string(),
+            warning: false,
+        };
+    }
+
+    if let Some(rest) = line.strip_prefix("Warning from ") {
+        let (location, message) = rest.split_once("): ").unwrap_or((rest, ""));
+        let inner = location.rsplit_once(" (").map_or(location, |(_, inner)| inner);
+        let (path, line_no) = match inner.rsplit_once(" line ") {
+            Some((path, n)) => (path, n.trim().parse().ok()),
+            None => (inner, None),
+        };
+        return Diagnostic {
+            file: Some(PathBuf::from(path)),
+            line: line_no,
+            message: message.trim().to_string(),
+            warning: true,
+        };
+    }
+
+    Diagnostic {
+        file: Some(file.to_path_buf()),
+        line: None,
+        message: line.to_string(),
+        warning: line.starts_with("Warning") || line.starts_with("Cache read/write disabled"),
+    }
+}
+
+/// The text after `marker`, up to the next " at " or " in ".
+fn after<'a>(text: &'a str, marker: &str) -> Option<&'a str> {
+    let rest = &text[text.find(marker)? + marker.len()..];
+    let end = [" at ", " in "].iter().filter_map(|m| rest.find(m)).min().unwrap_or(rest.len());
+    Some(rest[..end].trim())
+}
+
+/// Checks `source` with the built-in parser. It knows less syntax than
+/// `apparmor_parser`, so its complaints are advisory.
+pub fn builtin_check(path: &Path, source: &str) -> Vec<Diagnostic> {
+    match policy::parse(source) {
+        Ok(_) => Vec::new(),
+        Err(err) => vec![Diagnostic {
+            file: Some(path.to_path_buf()),
+            line: Some(err.span.line(source)),
+            message: err.message,
+            warning: false,
+        }],
+    }
+}
+
+/// Result of validating an edited file before loading it.
+#[derive(Clone, Debug, Default)]
+pub struct Validation {
+    pub diagnostics: Vec<Diagnostic>,
+    /// `apparmor_parser` itself rejected the file, so it must not be loaded.
+    pub parser_rejected: bool,
+}
+
+impl Validation {
+    pub fn passed(&self) -> bool {
+        !self.parser_rejected && self.diagnostics.iter().all(|d| d.warning)
+    }
+}
+
+/// Checks a policy file with `apparmor_parser`, or with the built-in
+/// parser when `apparmor_parser` isn't installed.
+pub fn validate(backend: &mut dyn PolicyBackend, path: &Path) -> Result<Validation> {
+    let source = backend.read_file(path)?;
+    let mut validation = Validation { diagnostics: builtin_check(path, &source), parser_rejected: false };
+    if let Some(report) = backend.check_profile(path)? {
+        if !report.success || !report.diagnostics.is_empty() {
+            // apparmor_parser is authoritative; drop the built-in
+            // parser's opinion once it has spoken.
+            validation.diagnostics = report.diagnostics;
+        } else {
+            validation.diagnostics.clear();
+        }
+        validation.parser_rejected = !report.success;
+    }
+    Ok(validation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = "/etc/apparmor.d/usr.sbin.cupsd";
+
+    fn parse(line: &str) -> Diagnostic {
+        let mut diagnostics = parse_diagnostics(line, Path::new(FILE));
+        assert_eq!(diagnostics.len(), 1);
+        diagnostics.remove(0)
+    }
+
+    fn diagnostic(file: &str, line: Option<usize>, message: &str, warning: bool) -> Diagnostic {
+        Diagnostic { file: Some(PathBuf::from(file)), line, message: message.to_string(), warning }
+    }
+
+    #[test]
+    fn parses_error_in_an_included_file() {
+        let found = parse(
+            "AppArmor parser error for /etc/apparmor.d/usr.sbin.cupsd in profile /etc/apparmor.d/abstractions/cups-client at line 12: syntax error, unexpected TOK_ID",
+        );
+        assert_eq!(
+            found,
+            diagnostic("/etc/apparmor.d/abstractions/cups-client", Some(12), "syntax error, unexpected TOK_ID", false)
+        );
+        assert_eq!(found.to_string(), "/etc/apparmor.d/abstractions/cups-client:12: syntax error, unexpected TOK_ID");
+    }
+
+    #[test]
+    fn parses_error_without_a_file_name() {
+        let found = parse("AppArmor parser error at line 5: Could not open 'abstractions/bar'");
+        assert_eq!(found, diagnostic(FILE, Some(5), "Could not open 'abstractions/bar'", false));
+    }
+
+    #[test]
+    fn parses_warning_with_file_and_line() {
+        let found = parse("Warning from /etc/apparmor.d/usr.sbin.cupsd (/etc/apparmor.d/usr.sbin.cupsd line 10): unknown flag");
+        assert_eq!(found, diagnostic(FILE, Some(10), "unknown flag", true));
+        assert_eq!(found.to_string(), "/etc/apparmor.d/usr.sbin.cupsd:10: warning: unknown flag");
+    }
+
+    #[test]
+    fn keeps_unrecognised_lines_whole() {
+        let found = parse_diagnostics(
+            "\n  Cache read/write disabled: interface file missing.\nProfile doesn't conform to protocol\n",
+            Path::new(FILE),
+        );
+        assert_eq!(
+            found,
+            [
+                diagnostic(FILE, None, "Cache read/write disabled: interface file missing.", true),
+                diagnostic(FILE, None, "Profile doesn't conform to protocol", false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! User configuration from `$XDG_CONFIG_HOME/apparmor-tui/config`.
+//!
+//! The file holds `key = value` lines; `#` starts a comment:
+//!
+//! ```text
+//! escalation = pkexec   # sudo, pkexec, doas, none or auto
+//! securityfs = /sys/kernel/security
+//! policy_dir = /etc/apparmor.d
+//! log_source = journal  # auto, journal or a log file path
+//! editor = nvim         # overrides $VISUAL and $EDITOR
+//! ```
+
+use crate::auditlog::LogSource;
+use crate::privileged::Escalation;
+use anyhow::{anyhow, bail, Context, Result};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Default, Debug)]
+pub struct Config {
+    /// `None` picks automatically based on the effective UID.
+    pub escalation: Option<Escalation>,
+    pub securityfs_root: Option<PathBuf>,
+    pub policy_dir: Option<PathBuf>,
+    /// `None` picks the first readable log.
+    pub log_source: Option<LogSource>,
+    /// Editor command line; `None` uses `$VISUAL` or `$EDITOR`.
+    pub editor: Option<String>,
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        let base = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("apparmor-tui").join("config"))
+    }
+
+    /// Loads the config file, or the defaults when there is none.
+    pub fn load() -> Result<Config> {
+        let Some(path) = Config::path().filter(|path| path.exists()) else {
+            return Ok(Config::default());
+        };
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Config::parse(&content).with_context(|| format!("Invalid config {}", path.display()))
+    }
+
+    pub fn parse(input: &str) -> Result<Config> {
+        let mut config = Config::default();
+        for (n, line) in input.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("line {}: expected key = value", n + 1))?;
+            let value = value.trim();
+            match key.trim() {
+                "escalation" if value == "auto" => config.escalation = None,
+                "escalation" => {
+                    config.escalation = Some(
+                        Escalation::parse(value)
+                            .ok_or_else(|| anyhow!("line {}: unknown escalation '{}'", n + 1, value))?,
+                    )
+                }
+                "securityfs" => config.securityfs_root = Some(PathBuf::from(value)),
+                "policy_dir" => config.policy_dir = Some(PathBuf::from(value)),
+                "log_source" if value == "auto" => config.log_source = None,
+                "log_source" => config.log_source = Some(LogSource::parse(value)),
+                "editor" => config.editor = Some(value.to_string()),
+                other => bail!("line {}: unknown key '{}'", n + 1, other),
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keys_and_comments() {
+        let config = Config::parse(
+            "# apparmor-tui\n\
+             escalation = doas   # no sudo here\n\
+             \n\
+             securityfs = /tmp/securityfs\n\
+             policy_dir=/tmp/apparmor.d\n\
+             log_source = /var/log/audit/audit.log\n\
+             editor = code --wait\n",
+        )
+        .unwrap();
+        assert_eq!(config.escalation, Some(Escalation::Doas));
+        assert_eq!(config.securityfs_root, Some(PathBuf::from("/tmp/securityfs")));
+        assert_eq!(config.policy_dir, Some(PathBuf::from("/tmp/apparmor.d")));
+        assert!(config.log_source.is_some());
+        assert_eq!(config.editor.as_deref(), Some("code --wait"));
+    }
+
+    #[test]
+    fn auto_resets_to_the_default() {
+        let config = Config::parse("escalation = none\nescalation = auto\nlog_source = journal\nlog_source = auto\n").unwrap();
+        assert_eq!(config.escalation, None);
+        assert!(config.log_source.is_none());
+        assert!(Config::parse("").unwrap().editor.is_none());
+    }
+
+    #[test]
+    fn reports_the_offending_line() {
+        let error = |input| Config::parse(input).unwrap_err().to_string();
+        assert_eq!(error("editor = vi\nescalation = su\n"), "line 2: unknown escalation 'su'");
+        assert_eq!(error("colour = red\n"), "line 1: unknown key 'colour'");
+        assert_eq!(error("# ok\nescalation\n"), "line 2: expected key = value");
+    }
+}
@@ -0,0 +1,135 @@
+//! Information shown in the detail pane for the selected profile.
+
+use crate::backend::PolicyBackend;
+use crate::index::ProfileLocation;
+use crate::policy;
+
+#[derive(Default)]
+pub struct Detail {
+    pub profile: String,
+    pub location: Option<ProfileLocation>,
+    /// Content of the whole policy file.
+    pub source: String,
+    pub attachment: Option<String>,
+    pub flags: Vec<String>,
+    pub includes: Vec<String>,
+    /// Number of rules per rule type, sorted by type.
+    pub rule_counts: Vec<(String, usize)>,
+    /// Why the file couldn't be found or parsed.
+    pub error: Option<String>,
+}
+
+impl Detail {
+    pub fn load(backend: &mut dyn PolicyBackend, profile: &str) -> Detail {
+        let mut detail = Detail { profile: profile.to_string(), ..Detail::default() };
+
+        let location = match backend.locate_profile(profile) {
+            Ok(location) => location,
+            Err(err) => {
+                detail.error = Some(format!("{:#}", err));
+                return detail;
+            }
+        };
+        match backend.read_file(&location.path) {
+            Ok(source) => detail.source = source,
+            Err(err) => detail.error = Some(format!("{:#}", err)),
+        }
+        detail.location = Some(location);
+
+        let parsed = match policy::parse(&detail.source) {
+            Ok(parsed) => parsed,
+            Err(err) if detail.error.is_none() => {
+                detail.error = Some(format!("line {}: {}", err.span.line(&detail.source), err.message));
+                return detail;
+            }
+            Err(_) => return detail,
+        };
+
+        let all = parsed.all_profiles();
+        let found = all.iter().find(|(name, _, _)| name.replace('"', "") == profile).or_else(|| {
+            let line = detail.location.as_ref()?.line;
+            all.iter().find(|(_, _, span)| span.line(&detail.source) == line)
+        });
+        let Some((_, found, _)) = found else {
+            return detail;
+        };
+
+        detail.attachment = found.attachment.clone();
+        detail.flags = found.flags.clone();
+        detail.includes = found.includes().map(|include| include.path().to_string()).collect();
+        for rule in found.rules() {
+            let kind = rule.kind.type_name();
+            match detail.rule_counts.iter_mut().find(|(name, _)| name == kind) {
+                Some((_, count)) => *count += 1,
+                None => detail.rule_counts.push((kind.to_string(), 1)),
+            }
+        }
+        detail.rule_counts.sort();
+        detail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use crate::profile::Mode;
+    use std::path::PathBuf;
+
+    const CUPSD: &str = "profile cupsd /usr/sbin/cupsd flags=(complain, attach_disconnected) {
+  #include <abstractions/base>
+  include <abstractions/nameservice>
+  /var/log/cups/* rw,
+  network inet stream,
+  capability net_bind_service,
+  /etc/cups/** r,
+
+  ^hat flags=(complain) {
+    /tmp/** rw,
+  }
+}
+";
+
+    fn backend() -> FakeBackend {
+        let mut backend = FakeBackend::with_profiles(&[("cupsd", Mode::Complain)]);
+        backend.files.insert(PathBuf::from("/etc/apparmor.d/usr.sbin.cupsd"), CUPSD.to_string());
+        backend.files.insert(PathBuf::from("/etc/apparmor.d/broken"), "profile broken {\n  /etc/foo zz,\n}\n".to_string());
+        backend
+    }
+
+    #[test]
+    fn summarises_the_profile() {
+        let detail = Detail::load(&mut backend(), "cupsd");
+        assert_eq!(detail.error, None);
+        assert_eq!(detail.location, Some(ProfileLocation { path: PathBuf::from("/etc/apparmor.d/usr.sbin.cupsd"), line: 1 }));
+        assert_eq!(detail.source, CUPSD);
+        assert_eq!(detail.attachment.as_deref(), Some("/usr/sbin/cupsd"));
+        assert_eq!(detail.flags, ["complain", "attach_disconnected"]);
+        assert_eq!(detail.includes, ["abstractions/base", "abstractions/nameservice"]);
+        let counts: Vec<(&str, usize)> = detail.rule_counts.iter().map(|(kind, n)| (kind.as_str(), *n)).collect();
+        assert_eq!(counts, [("capability", 1), ("file", 2), ("network", 1)]);
+    }
+
+    #[test]
+    fn finds_hats_by_their_full_name() {
+        let detail = Detail::load(&mut backend(), "cupsd//hat");
+        assert_eq!(detail.error, None);
+        assert_eq!(detail.location.unwrap().line, 9);
+        assert_eq!(detail.attachment, None);
+        assert_eq!(detail.flags, ["complain"]);
+        assert!(detail.includes.is_empty());
+        assert_eq!(detail.rule_counts, [("file".to_string(), 1)]);
+    }
+
+    #[test]
+    fn explains_missing_and_unparsable_files() {
+        let detail = Detail::load(&mut backend(), "missing");
+        assert_eq!(detail.error.as_deref(), Some("No policy file defines profile missing"));
+        assert!(detail.location.is_none());
+
+        let detail = Detail::load(&mut backend(), "broken");
+        assert_eq!(detail.error.as_deref(), Some("line 2: invalid file permissions 'zz'"));
+        assert_eq!(detail.location.unwrap().path, PathBuf::from("/etc/apparmor.d/broken"));
+        assert!(detail.flags.is_empty() && detail.rule_counts.is_empty());
+    }
+}
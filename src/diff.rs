@@ -0,0 +1,189 @@
+//! Line diffs between a file on disk and a proposed replacement, with
+//! per-hunk accept/reject.
+
+use std::ops::Range;
+
+/// Unchanged lines shown around each hunk.
+pub const CONTEXT: usize = 3;
+
+/// A run of changed lines: `old` is replaced by `new`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RowKind {
+    /// `@@ -a,b +c,d @@` line starting a hunk.
+    Header,
+    Context,
+    Change,
+}
+
+/// One display row. Change rows pair a removed line with an added one for
+/// side-by-side display; either side may be missing.
+#[derive(Clone, Debug)]
+pub struct Row<'a> {
+    pub hunk: usize,
+    pub kind: RowKind,
+    /// 0-based line number and text (without newline) on each side.
+    pub old: Option<(usize, &'a str)>,
+    pub new: Option<(usize, &'a str)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diff {
+    /// Lines including their newline, so a missing final newline survives.
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl Diff {
+    pub fn new(old: &str, new: &str) -> Diff {
+        let old: Vec<String> = old.split_inclusive('\n').map(str::to_string).collect();
+        let new: Vec<String> = new.split_inclusive('\n').map(str::to_string).collect();
+        let hunks = hunks(&old, &new);
+        Diff { old, new, hunks }
+    }
+
+    /// Rebuilds the file taking the new side of accepted hunks and the old
+    /// side of the rest.
+    pub fn apply(&self, accepted: &[bool]) -> String {
+        let mut out = String::new();
+        let mut at = 0;
+        for (hunk, &accept) in self.hunks.iter().zip(accepted) {
+            out.extend(self.old[at..hunk.old.start].iter().map(String::as_str));
+            let lines = if accept { &self.new[hunk.new.clone()] } else { &self.old[hunk.old.clone()] };
+            out.extend(lines.iter().map(String::as_str));
+            at = hunk.old.end;
+        }
+        out.extend(self.old[at..].iter().map(String::as_str));
+        out
+    }
+
+    /// Header, context and change rows for every hunk.
+    pub fn rows(&self) -> Vec<Row<'_>> {
+        let line = numbered;
+        let mut rows = Vec::new();
+        for (i, hunk) in self.hunks.iter().enumerate() {
+            // Context already shown after the previous hunk isn't repeated.
+            let shown = i.checked_sub(1).map_or(0, |p| (self.hunks[p].old.end + CONTEXT).min(hunk.old.start));
+            let before = hunk.old.start.saturating_sub(CONTEXT).max(shown);
+            let next = self.hunks.get(i + 1).map_or(self.old.len(), |h| h.old.start);
+            let after = (hunk.old.end + CONTEXT).min(next);
+            let offset = hunk.new.start as isize - hunk.old.start as isize;
+            let new_line = |n: usize| (n as isize + offset) as usize;
+            rows.push(Row { hunk: i, kind: RowKind::Header, old: None, new: None });
+            for n in before..hunk.old.start {
+                rows.push(Row { hunk: i, kind: RowKind::Context, old: Some(line(&self.old, n)), new: Some(line(&self.new, new_line(n))) });
+            }
+            let removed = hunk.old.len();
+            let added = hunk.new.len();
+            for k in 0..removed.max(added) {
+                rows.push(Row {
+                    hunk: i,
+                    kind: RowKind::Change,
+                    old: (k < removed).then(|| line(&self.old, hunk.old.start + k)),
+                    new: (k < added).then(|| line(&self.new, hunk.new.start + k)),
+                });
+            }
+            let offset = hunk.new.end as isize - hunk.old.end as isize;
+            for n in hunk.old.end..after {
+                let m = (n as isize + offset) as usize;
+                rows.push(Row { hunk: i, kind: RowKind::Context, old: Some(line(&self.old, n)), new: Some(line(&self.new, m)) });
+            }
+        }
+        rows
+    }
+
+    /// The `@@` header of hunk `i`. Like `diff -u`, numbers are 1-based and
+    /// an empty side names the line before it.
+    pub fn header(&self, i: usize) -> String {
+        let hunk = &self.hunks[i];
+        let start = |range: &Range<usize>| if range.is_empty() { range.start } else { range.start + 1 };
+        format!("@@ -{},{} +{},{} @@", start(&hunk.old), hunk.old.len(), start(&hunk.new), hunk.new.len())
+    }
+}
+
+fn numbered(lines: &[String], n: usize) -> (usize, &str) {
+    (n, lines[n].strip_suffix('\n').unwrap_or(&lines[n]))
+}
+
+/// Longest-common-subsequence diff. The common prefix and suffix are
+/// trimmed first, so typical edits only compare a few lines.
+fn hunks(old: &[String], new: &[String]) -> Vec<Hunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j] is the LCS length of a[i..] and b[j..].
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut start: Option<(usize, usize)> = None;
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            if let Some((si, sj)) = start.take() {
+                hunks.push(Hunk { old: prefix + si..prefix + i, new: prefix + sj..prefix + j });
+            }
+            i += 1;
+            j += 1;
+            continue;
+        }
+        start.get_or_insert((i, j));
+        if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            j += 1;
+        } else {
+            i += 1;
+        }
+    }
+    if let Some((si, sj)) = start {
+        hunks.push(Hunk { old: prefix + si..prefix + i, new: prefix + sj..prefix + j });
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "profile test {\n  /etc/passwd r,\n  /etc/group r,\n\n\n\n\n\n  network inet,\n}\n";
+    const NEW: &str = "profile test {\n  /etc/passwd r,\n  /etc/shadow r,\n\n\n\n\n\n  network inet,\n  capability kill,\n}\n";
+
+    #[test]
+    fn finds_separate_hunks() {
+        let diff = Diff::new(OLD, NEW);
+        assert_eq!(diff.hunks, [Hunk { old: 2..3, new: 2..3 }, Hunk { old: 9..9, new: 9..10 }]);
+        assert_eq!(diff.header(1), "@@ -9,0 +10,1 @@");
+        let changes: Vec<_> = diff.rows().into_iter().filter(|row| row.kind == RowKind::Change).collect();
+        assert_eq!(changes[0].old, Some((2, "  /etc/group r,")));
+        assert_eq!(changes[0].new, Some((2, "  /etc/shadow r,")));
+        assert_eq!(changes[1].old, None);
+    }
+
+    #[test]
+    fn applies_only_accepted_hunks() {
+        let diff = Diff::new(OLD, NEW);
+        assert_eq!(diff.apply(&[true, true]), NEW);
+        assert_eq!(diff.apply(&[false, false]), OLD);
+        let mixed = diff.apply(&[false, true]);
+        assert!(mixed.contains("/etc/group r,") && mixed.contains("capability kill,"));
+    }
+
+    #[test]
+    fn keeps_missing_final_newline() {
+        let diff = Diff::new("a\nb", "a\nb\n");
+        assert_eq!(diff.hunks.len(), 1);
+        assert_eq!(diff.apply(&[false]), "a\nb");
+        assert_eq!(diff.apply(&[true]), "a\nb\n");
+    }
+}
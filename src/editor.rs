@@ -0,0 +1,203 @@
+//! Editing policy files the way `sudoedit` does: the editor runs as the
+//! invoking user on a temporary copy, and only a changed copy is handed
+//! back to be written with privileges.
+
+use crate::tui;
+use anyhow::{anyhow, bail, Context, Result};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Editors known to accept `+LINE` before the file name.
+const LINE_ARG_EDITORS: &[&str] = &["vi", "vim", "nvim", "view", "nano", "emacs", "emacsclient", "micro", "kak", "mg", "joe", "ne"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Editor {
+    /// Program followed by its arguments, e.g. `["code", "--wait"]`.
+    pub command: Vec<String>,
+}
+
+impl Editor {
+    /// Uses the configured editor, then `$VISUAL`, then `$EDITOR`, then `vi`.
+    pub fn resolve(configured: Option<&str>) -> Editor {
+        Editor::choose(configured, env::var("VISUAL").ok(), env::var("EDITOR").ok())
+    }
+
+    fn choose(configured: Option<&str>, visual: Option<String>, editor: Option<String>) -> Editor {
+        let set = |value: &String| !value.trim().is_empty();
+        let line = configured.map(str::to_string).or(visual.filter(set)).or(editor.filter(set));
+        Editor::parse(line.as_deref().unwrap_or("vi"))
+    }
+
+    /// Splits a command line the way `sh` would, minus expansions.
+    pub fn parse(line: &str) -> Editor {
+        let command = split_words(line);
+        if command.is_empty() {
+            return Editor { command: vec!["vi".to_string()] };
+        }
+        Editor { command }
+    }
+
+    fn accepts_line_arg(&self) -> bool {
+        let program = Path::new(&self.command[0]);
+        let name = program.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        LINE_ARG_EDITORS.contains(&name)
+    }
+
+    /// Edits a temporary copy of `path`. Returns the new contents, or
+    /// `None` when nothing changed.
+    pub fn edit(&self, path: &Path, line: usize) -> Result<Option<String>> {
+        let original = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let copy = temp_copy(path, &original)?;
+        let edited = self.run(&copy, line).and_then(|()| {
+            fs::read_to_string(&copy).with_context(|| format!("Failed to read {}", copy.display()))
+        });
+        if let Some(dir) = copy.parent() {
+            let _ = fs::remove_dir_all(dir);
+        }
+        let edited = edited?;
+        Ok((edited != original).then_some(edited))
+    }
+
+    fn run(&self, file: &Path, line: usize) -> Result<()> {
+        let mut cmd = Command::new(&self.command[0]);
+        cmd.args(&self.command[1..]);
+        if self.accepts_line_arg() {
+            cmd.arg(format!("+{}", line));
+        }
+        cmd.arg(file);
+        let status = tui::suspended(|| cmd.status())?
+            .with_context(|| format!("Failed to execute {}", self.command[0]))?;
+        if !status.success() {
+            bail!("{} exited with {}; changes were not saved", self.command[0], status);
+        }
+        Ok(())
+    }
+}
+
+/// Splits on unquoted whitespace. Single quotes keep everything literally;
+/// in double quotes and outside quotes a backslash escapes the next
+/// character (in double quotes only `"`, `\\`, `$` and `` ` ``).
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                word.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some(next @ ('"' | '\\' | '$' | '`')) => word.push(next),
+                            Some(next) => {
+                                word.push('\\');
+                                word.push(next);
+                            }
+                            None => word.push('\\'),
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// Creates a private copy of `path` that keeps its file name, so editors
+/// still pick the right syntax.
+fn temp_copy(path: &Path, content: &str) -> Result<PathBuf> {
+    let name = path.file_name().ok_or_else(|| anyhow!("Not a file: {}", path.display()))?;
+    let dir = env::temp_dir().join(format!("apparmor-tui-{}-{}", process::id(), unix_time()));
+    fs::create_dir(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let copy = dir.join(name);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&copy)
+        .with_context(|| format!("Failed to create {}", copy.display()))?;
+    file.write_all(content.as_bytes()).with_context(|| format!("Failed to write {}", copy.display()))?;
+    Ok(copy)
+}
+
+/// Saves `content` under `$XDG_STATE_HOME/apparmor-tui/backups`, outside
+/// the policy directory so the backups are never loaded as profiles.
+pub fn backup(path: &Path, content: &str) -> Result<()> {
+    let dir = backup_dir().ok_or_else(|| anyhow!("Cannot find a directory for backups; set $HOME"))?;
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let name = path.file_name().ok_or_else(|| anyhow!("Not a file: {}", path.display()))?;
+    let target = dir.join(format!("{}.{}", name.to_string_lossy(), unix_time()));
+    fs::write(&target, content).with_context(|| format!("Failed to write backup {}", target.display()))
+}
+
+fn backup_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local").join("state"),
+    };
+    Some(base.join("apparmor-tui").join("backups"))
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        Editor::parse(line).command
+    }
+
+    #[test]
+    fn parses_quoted_editor_commands() {
+        assert_eq!(words("code --wait"), ["code", "--wait"]);
+        assert_eq!(words("'/opt/My Editor/bin/edit' -n"), ["/opt/My Editor/bin/edit", "-n"]);
+        assert_eq!(words(r#"emacsclient -a "" -c"#), ["emacsclient", "-a", "", "-c"]);
+        assert_eq!(words(r#"vim -c "set ft=apparmor \"x\"" a\ b"#), ["vim", "-c", "set ft=apparmor \"x\"", "a b"]);
+        assert_eq!(words("   "), ["vi"]);
+    }
+
+    #[test]
+    fn prefers_config_then_visual_then_editor() {
+        let var = |value: &str| Some(value.to_string());
+        assert_eq!(Editor::choose(Some("nano"), var("code --wait"), var("vim")), Editor::parse("nano"));
+        assert_eq!(Editor::choose(None, var("code --wait"), var("vim")), Editor::parse("code --wait"));
+        assert_eq!(Editor::choose(None, var(" "), var("vim")), Editor::parse("vim"));
+        assert_eq!(Editor::choose(None, None, None), Editor::parse("vi"));
+    }
+
+    #[test]
+    fn unchanged_copy_is_not_handed_back() {
+        let dir = env::temp_dir().join(format!("apparmor-tui-editor-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("usr.bin.test");
+        fs::write(&path, "profile test {\n}\n").unwrap();
+        let unchanged = Editor::parse("true").edit(&path, 1);
+        let changed = Editor::parse(r#"sh -c 'echo "  /tmp/x r," >> "$1"' sh"#).edit(&path, 1);
+        let failed = Editor::parse("false").edit(&path, 1);
+        let original = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(unchanged.unwrap(), None);
+        assert_eq!(changed.unwrap().as_deref(), Some("profile test {\n}\n  /tmp/x r,\n"));
+        assert!(failed.unwrap_err().to_string().contains("changes were not saved"));
+        // Only the copy is edited.
+        assert_eq!(original, "profile test {\n}\n");
+    }
+}
@@ -0,0 +1,305 @@
+//! Narrowing and searching the profile list: a persistent filter of
+//! substrings, `/regex/` and `mode:` terms, plus fuzzy matching for `/`.
+
+use crate::profile::{Mode, Profile};
+use anyhow::{anyhow, bail, Result};
+
+/// A filter is a space-separated list of terms that must all match.
+#[derive(Default)]
+pub struct Filter {
+    pub text: String,
+    terms: Vec<Term>,
+}
+
+enum Term {
+    /// Case-insensitive name substring.
+    Substring(String),
+    /// `/pattern/` on the name.
+    Regex(Regex),
+    /// `mode:complain`
+    Mode(Mode),
+}
+
+impl Filter {
+    pub fn parse(text: &str) -> Result<Filter> {
+        let mut terms = Vec::new();
+        for word in text.split_whitespace() {
+            let term = if let Some(mode) = word.strip_prefix("mode:") {
+                Term::Mode(Mode::parse(mode).ok_or_else(|| anyhow!("unknown mode '{}'", mode))?)
+            } else if word.len() >= 2 && word.starts_with('/') && word.ends_with('/') {
+                Term::Regex(Regex::new(&word[1..word.len() - 1])?)
+            } else {
+                Term::Substring(word.to_lowercase())
+            };
+            terms.push(term);
+        }
+        Ok(Filter { text: text.trim().to_string(), terms })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn matches(&self, profile: &Profile) -> bool {
+        self.terms.iter().all(|term| match term {
+            Term::Substring(text) => profile.name.to_lowercase().contains(text),
+            Term::Regex(regex) => regex.is_match(&profile.name),
+            Term::Mode(mode) => profile.mode == *mode,
+        })
+    }
+}
+
+/// Matches `pattern` as a case-insensitive subsequence of `text`. Returns
+/// a score (higher is better) and the matched char positions.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<(i32, Vec<usize>)> {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    let text: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = text.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let first = *pattern.first()?;
+
+    // Try every place the first char occurs and keep the best greedy run.
+    let mut best: Option<(i32, Vec<usize>)> = None;
+    for start in (0..lower.len()).filter(|&i| lower[i] == first) {
+        let mut positions = vec![start];
+        let mut at = start + 1;
+        for &c in &pattern[1..] {
+            let Some(offset) = lower[at..].iter().position(|&t| t == c) else { break };
+            positions.push(at + offset);
+            at += offset + 1;
+        }
+        if positions.len() < pattern.len() {
+            break;
+        }
+        let mut score = 0;
+        for (k, &p) in positions.iter().enumerate() {
+            score += 16;
+            if p == 0 || matches!(text[p - 1], '/' | '.' | '-' | '_' | ' ') {
+                score += 8;
+            }
+            if k > 0 {
+                let gap = p - positions[k - 1] - 1;
+                score += if gap == 0 { 8 } else { -(gap as i32).min(8) };
+            }
+        }
+        if best.as_ref().is_none_or(|(s, _)| score > *s) {
+            best = Some((score, positions));
+        }
+    }
+    best
+}
+
+/// A small backtracking regular expression: literals, `.`, `[...]`
+/// classes, `\d \w \s`, `* + ?`, `^ $`, groups and `|`.
+pub struct Regex {
+    alternatives: Vec<Vec<Piece>>,
+}
+
+struct Piece {
+    node: Node,
+    min: usize,
+    max: Option<usize>,
+}
+
+enum Node {
+    Char(char),
+    Any,
+    Class { ranges: Vec<(char, char)>, negated: bool },
+    Group(Vec<Vec<Piece>>),
+    Start,
+    End,
+}
+
+type Cont<'a> = &'a mut dyn FnMut(usize) -> bool;
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex> {
+        let mut parser = RegexParser { chars: pattern.chars().collect(), at: 0 };
+        let alternatives = parser.alternatives()?;
+        if parser.at < parser.chars.len() {
+            bail!("unmatched ')' in /{}/", pattern);
+        }
+        Ok(Regex { alternatives })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        (0..=text.len()).any(|start| match_alternatives(&self.alternatives, &text, start, &mut |_| true))
+    }
+}
+
+fn match_alternatives(alternatives: &[Vec<Piece>], text: &[char], at: usize, k: Cont) -> bool {
+    alternatives.iter().any(|sequence| match_sequence(sequence, text, at, k))
+}
+
+fn match_sequence(sequence: &[Piece], text: &[char], at: usize, k: Cont) -> bool {
+    let Some((first, rest)) = sequence.split_first() else { return k(at) };
+    match_piece(first, 0, text, at, &mut |next| match_sequence(rest, text, next, k))
+}
+
+/// Greedy repetition: try one more occurrence before giving up on it.
+fn match_piece(piece: &Piece, count: usize, text: &[char], at: usize, k: Cont) -> bool {
+    if piece.max.is_none_or(|max| count < max) {
+        // An empty occurrence only helps while the minimum isn't reached.
+        let more = &mut |next| (next != at || count < piece.min) && match_piece(piece, count + 1, text, next, k);
+        if match_node(&piece.node, text, at, more) {
+            return true;
+        }
+    }
+    count >= piece.min && k(at)
+}
+
+fn match_node(node: &Node, text: &[char], at: usize, k: Cont) -> bool {
+    match node {
+        Node::Char(c) => text.get(at) == Some(c) && k(at + 1),
+        Node::Any => at < text.len() && k(at + 1),
+        Node::Class { ranges, negated } => {
+            let Some(&c) = text.get(at) else { return false };
+            ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated && k(at + 1)
+        }
+        Node::Group(alternatives) => match_alternatives(alternatives, text, at, k),
+        Node::Start => at == 0 && k(at),
+        Node::End => at == text.len() && k(at),
+    }
+}
+
+struct RegexParser {
+    chars: Vec<char>,
+    at: usize,
+}
+
+impl RegexParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.at += 1;
+        c
+    }
+
+    fn alternatives(&mut self) -> Result<Vec<Vec<Piece>>> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.at += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Piece>> {
+        let mut pieces = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let node = self.atom()?;
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                _ => {
+                    pieces.push(Piece { node, min: 1, max: Some(1) });
+                    continue;
+                }
+            };
+            self.at += 1;
+            pieces.push(Piece { node, min, max });
+        }
+        Ok(pieces)
+    }
+
+    fn atom(&mut self) -> Result<Node> {
+        Ok(match self.bump() {
+            Some('(') => {
+                let alternatives = self.alternatives()?;
+                if self.bump() != Some(')') {
+                    bail!("unclosed '(' in regex");
+                }
+                Node::Group(alternatives)
+            }
+            Some('.') => Node::Any,
+            Some('^') => Node::Start,
+            Some('$') => Node::End,
+            Some('[') => self.class()?,
+            Some('\\') => self.escape()?,
+            Some(c @ ('*' | '+' | '?')) => bail!("nothing to repeat before '{}'", c),
+            Some(c) => Node::Char(c),
+            None => bail!("unexpected end of regex"),
+        })
+    }
+
+    fn escape(&mut self) -> Result<Node> {
+        let class = |ranges: &[(char, char)]| Node::Class { ranges: ranges.to_vec(), negated: false };
+        Ok(match self.bump() {
+            Some('d') => class(&[('0', '9')]),
+            Some('w') => class(&[('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')]),
+            Some('s') => class(&[(' ', ' '), ('\t', '\t'), ('\n', '\n'), ('\r', '\r')]),
+            Some(c) => Node::Char(c),
+            None => bail!("trailing '\\' in regex"),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.at += 1;
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.bump() {
+                None => bail!("unclosed '[' in regex"),
+                Some(']') if !first => break,
+                Some('\\') => self.bump().ok_or_else(|| anyhow!("trailing '\\' in regex"))?,
+                Some(c) => c,
+            };
+            first = false;
+            if self.peek() == Some('-') && self.chars.get(self.at + 1).is_some_and(|&c| c != ']') {
+                self.at += 1;
+                let hi = self.bump().unwrap_or(c);
+                ranges.push((c, hi));
+            } else {
+                ranges.push((c, c));
+            }
+        }
+        Ok(Node::Class { ranges, negated })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regex_basics() {
+        let regex = |p: &str| Regex::new(p).unwrap();
+        assert!(regex("^snap\\.").is_match("snap.firefox.firefox"));
+        assert!(!regex("^snap\\.").is_match("/snap/bin/foo"));
+        assert!(regex("fire(fox|bird)$").is_match("firebird"));
+        assert!(!regex("^/usr/s?bin/[a-c]+d$").is_match("/usr/sbin/cupsd"));
+        assert!(regex("^/usr/s?bin/[a-z]+d$").is_match("/usr/sbin/cupsd"));
+        assert!(regex("lib.*\\d").is_match("/usr/lib/x86_64"));
+        assert!(Regex::new("(a").is_err());
+        assert!(Regex::new("*a").is_err());
+    }
+
+    #[test]
+    fn filter_terms_combine() {
+        let filter = Filter::parse("mode:complain FIRE").unwrap();
+        assert!(filter.matches(&Profile::new("firefox", Mode::Complain)));
+        assert!(!filter.matches(&Profile::new("firefox", Mode::Enforce)));
+        assert!(!filter.matches(&Profile::new("cupsd", Mode::Complain)));
+        let filter = Filter::parse("/^snap\\./").unwrap();
+        assert!(filter.matches(&Profile::new("snap.lxd.daemon", Mode::Enforce)));
+        assert!(Filter::parse("mode:sleepy").is_err());
+    }
+
+    #[test]
+    fn fuzzy_prefers_word_starts() {
+        let (_, positions) = fuzzy_match("ff", "snap.firefox.firefox").unwrap();
+        assert_eq!(positions, [5, 9]);
+        assert!(fuzzy_match("cupsd", "/usr/sbin/cupsd").unwrap().0 > fuzzy_match("cupsd", "cups-browsed").unwrap().0);
+        assert!(fuzzy_match("xyz", "firefox").is_none());
+    }
+}
@@ -0,0 +1,319 @@
+//! Profile flags in policy files: reading and rewriting the `flags=(...)`
+//! of a profile header, and switching to modes that have no `aa-*` helper.
+
+use crate::backend::PolicyBackend;
+use crate::policy::{self, Policy};
+use crate::profile::Mode;
+use anyhow::{anyhow, bail, Result};
+use std::path::{Path, PathBuf};
+
+/// Flags that select the profile mode. A profile has at most one; none
+/// means enforce.
+pub const MODE_FLAGS: [&str; 5] = ["enforce", "complain", "kill", "unconfined", "prompt"];
+
+/// Flags offered in the checklist, mode flags first.
+pub const KNOWN_FLAGS: [&str; 14] = [
+    "enforce",
+    "complain",
+    "kill",
+    "unconfined",
+    "prompt",
+    "audit",
+    "debug",
+    "attach_disconnected",
+    "mediate_deleted",
+    "delegate_deleted",
+    "chroot_relative",
+    "namespace_relative",
+    "chroot_attach",
+    "chroot_no_attach",
+];
+
+/// Flags of which a profile can only have one.
+const EXCLUSIVE: [&[&str]; 4] = [
+    &MODE_FLAGS,
+    &["mediate_deleted", "delegate_deleted"],
+    &["chroot_relative", "namespace_relative"],
+    &["chroot_attach", "chroot_no_attach"],
+];
+
+/// The flag selecting `mode`, for modes that are set through flags.
+pub fn mode_flag(mode: Mode) -> Option<&'static str> {
+    match mode {
+        Mode::Complain => Some("complain"),
+        Mode::Kill => Some("kill"),
+        Mode::Unconfined => Some("unconfined"),
+        Mode::Prompt => Some("prompt"),
+        Mode::Audit => Some("audit"),
+        Mode::Enforce | Mode::Disable | Mode::Unknown => None,
+    }
+}
+
+/// The mode a set of header flags gives a profile. An `audit` flag without
+/// a mode flag counts as audit mode.
+pub fn flags_mode(flags: &[String]) -> Mode {
+    let flag = flags.iter().find(|flag| MODE_FLAGS.contains(&flag.as_str()));
+    match flag.and_then(|flag| Mode::parse(flag)) {
+        Some(mode) => mode,
+        None if flags.iter().any(|flag| flag == "audit") => Mode::Audit,
+        None => Mode::Enforce,
+    }
+}
+
+/// The mode to show for a profile the kernel reports in `loaded`, given the
+/// header flags of its file: audit when an enforcing profile has the
+/// `audit` flag.
+pub fn shown_mode(loaded: Mode, flags: Option<&[String]>) -> Mode {
+    if loaded == Mode::Enforce && flags.is_some_and(|flags| flags_mode(flags) == Mode::Audit) {
+        Mode::Audit
+    } else {
+        loaded
+    }
+}
+
+/// `flags` with the mode flag replaced by the one for `mode`. Audit mode
+/// is the `audit` flag, so every other mode drops it.
+pub fn with_mode(flags: &[String], mode: Mode) -> Vec<String> {
+    let others = flags.iter().filter(|flag| !MODE_FLAGS.contains(&flag.as_str()) && *flag != "audit").cloned();
+    mode_flag(mode).map(str::to_string).into_iter().chain(others).collect()
+}
+
+/// Whether going from `from` to `to` has to rewrite the flags. The `aa-*`
+/// tools only know enforce, complain and audit and each add or drop one
+/// flag: `aa-enforce` leaves a kill, prompt, unconfined or audit flag in
+/// place, and `aa-audit` only gives audit mode to an enforcing profile.
+pub fn needs_rewrite(from: Option<Mode>, to: Mode) -> bool {
+    let flagged = |mode: Mode| matches!(mode, Mode::Kill | Mode::Prompt | Mode::Unconfined | Mode::Audit);
+    match to {
+        Mode::Kill | Mode::Prompt | Mode::Unconfined => true,
+        Mode::Audit => from.is_some_and(|from| !matches!(from, Mode::Enforce | Mode::Audit)),
+        Mode::Enforce | Mode::Complain => from.is_some_and(flagged),
+        Mode::Disable | Mode::Unknown => false,
+    }
+}
+
+/// The profile (or hat, as `parent//child`) called `name` in `source`.
+fn find<'a>(policy: &'a Policy, name: &str) -> Result<&'a policy::Profile> {
+    policy
+        .all_profiles()
+        .into_iter()
+        .find(|(found, _, _)| found.replace('"', "") == name)
+        .map(|(_, profile, _)| profile)
+        .ok_or_else(|| anyhow!("profile {} not found in file", name))
+}
+
+fn parse(source: &str) -> Result<Policy> {
+    policy::parse(source).map_err(|e| anyhow!("line {}: {}", e.span.line(source), e.message))
+}
+
+/// The header flags of `profile`.
+pub fn profile_flags(source: &str, profile: &str) -> Result<Vec<String>> {
+    Ok(find(&parse(source)?, profile)?.flags.clone())
+}
+
+/// Returns `source` with the header flags of `profile` replaced by `flags`,
+/// leaving the rest of the file as written. No flags drops `flags=(...)`.
+pub fn set_flags(source: &str, profile: &str, flags: &[String]) -> Result<String> {
+    let policy = parse(source)?;
+    let found = find(&policy, profile)?;
+    let text = format!("flags=({})", flags.join(", "));
+    let mut out = String::with_capacity(source.len() + text.len());
+    match found.flags_span {
+        Some(span) if flags.is_empty() => {
+            out.push_str(source[..span.start].trim_end());
+            out.push_str(&source[span.end..]);
+        }
+        Some(span) => {
+            out.push_str(&source[..span.start]);
+            out.push_str(&text);
+            out.push_str(&source[span.end..]);
+        }
+        None if flags.is_empty() => out.push_str(source),
+        None => {
+            let brace = found.header.end - 1;
+            out.push_str(source[..brace].trim_end());
+            out.push(' ');
+            out.push_str(&text);
+            out.push(' ');
+            out.push_str(&source[brace..]);
+        }
+    }
+    Ok(out)
+}
+
+/// The flags of one profile as a list of toggles.
+pub struct Checklist {
+    pub profile: String,
+    pub path: PathBuf,
+    source: String,
+    /// What the header says now.
+    original: Vec<String>,
+    /// Known flags, then any others the profile has (e.g.
+    /// `attach_disconnected.path=/run`), with whether each is set.
+    pub flags: Vec<(String, bool)>,
+    pub selected: usize,
+}
+
+impl Checklist {
+    pub fn new(profile: &str, path: PathBuf, source: String) -> Result<Checklist> {
+        let original = profile_flags(&source, profile)?;
+        let mut flags: Vec<(String, bool)> =
+            KNOWN_FLAGS.iter().map(|&flag| (flag.to_string(), original.iter().any(|f| f == flag))).collect();
+        for flag in &original {
+            if !KNOWN_FLAGS.contains(&flag.as_str()) {
+                flags.push((flag.clone(), true));
+            }
+        }
+        Ok(Checklist { profile: profile.to_string(), path, source, original, flags, selected: 0 })
+    }
+
+    /// Flips the selected flag. Setting a flag clears the ones it excludes,
+    /// such as the other mode flags.
+    pub fn toggle(&mut self) {
+        let Some((name, on)) = self.flags.get(self.selected).cloned() else { return };
+        if !on && let Some(group) = EXCLUSIVE.iter().find(|group| group.contains(&name.as_str())) {
+            for (flag, set) in &mut self.flags {
+                if group.contains(&flag.as_str()) {
+                    *set = false;
+                }
+            }
+        }
+        self.flags[self.selected].1 = !on;
+    }
+
+    /// Flags to write: the ones kept in their original order, then the
+    /// newly set ones.
+    pub fn result(&self) -> Vec<String> {
+        let set = |name: &str| self.flags.iter().any(|(flag, on)| flag == name && *on);
+        let mut out: Vec<String> = self.original.iter().filter(|flag| set(flag)).cloned().collect();
+        for (flag, on) in &self.flags {
+            if *on && !out.contains(flag) {
+                out.push(flag.clone());
+            }
+        }
+        out
+    }
+
+    /// The policy file with the new header flags.
+    pub fn apply(&self) -> Result<String> {
+        set_flags(&self.source, &self.profile, &self.result())
+    }
+}
+
+/// `source` with the mode flag of `profile` replaced by the one for `mode`.
+pub fn rewrite_mode(source: &str, profile: &str, mode: Mode) -> Result<String> {
+    set_flags(source, profile, &with_mode(&profile_flags(source, profile)?, mode))
+}
+
+/// Switches `profile` to `mode` by rewriting the mode flag in its policy
+/// file and reloading that file. The old file is put back when the parser
+/// or the kernel refuses the mode.
+pub fn switch_mode(backend: &mut dyn PolicyBackend, profile: &str, mode: Mode) -> Result<()> {
+    let location = backend.locate_profile(profile)?;
+    let source = backend.read_file(&location.path)?;
+    backend.write_file(&location.path, &rewrite_mode(&source, profile, mode)?)?;
+    load_mode(backend, &location.path, profile, mode, &source)
+}
+
+/// Loads `path` after its flags were rewritten and checks that `profile`
+/// came up in `mode`. Otherwise `original` is written back and loaded.
+pub fn load_mode(backend: &mut dyn PolicyBackend, path: &Path, profile: &str, mode: Mode, original: &str) -> Result<()> {
+    let report = backend.reload_profile(path)?;
+    if !report.success {
+        restore(backend, path, original)?;
+        let first = report.diagnostics.iter().find(|d| !d.warning);
+        let reason = first.map_or("apparmor_parser failed", |d| d.message.as_str());
+        bail!("apparmor_parser refused {} mode for {}: {}", mode, profile, reason);
+    }
+    let loaded = backend.list_profiles()?.into_iter().find(|p| p.name == profile).map(|p| p.mode);
+    if loaded != Some(mode) {
+        restore(backend, path, original)?;
+        let loaded = loaded.map_or("not loaded", Mode::as_str);
+        bail!("The running kernel doesn't support {} mode ({} came back as {})", mode, profile, loaded);
+    }
+    Ok(())
+}
+
+fn restore(backend: &mut dyn PolicyBackend, path: &Path, source: &str) -> Result<()> {
+    backend.write_file(path, source)?;
+    backend.reload_profile(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use std::path::PathBuf;
+
+    const SOURCE: &str = "/usr/sbin/cupsd flags=(complain, attach_disconnected) {\n  ^hat {\n  }\n}\nprofile ping /usr/bin/ping {\n}\n";
+
+    #[test]
+    fn rewrites_only_header_flags() {
+        let flags = |list: &[&str]| list.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        let out = set_flags(SOURCE, "/usr/sbin/cupsd", &flags(&["kill", "attach_disconnected"])).unwrap();
+        assert!(out.starts_with("/usr/sbin/cupsd flags=(kill, attach_disconnected) {\n"));
+        let out = set_flags(&out, "/usr/sbin/cupsd", &[]).unwrap();
+        assert!(out.starts_with("/usr/sbin/cupsd {\n"));
+        let out = set_flags(&out, "/usr/sbin/cupsd//hat", &flags(&["complain"])).unwrap();
+        assert!(out.contains("  ^hat flags=(complain) {\n"));
+        let out = set_flags(&out, "ping", &flags(&["prompt"])).unwrap();
+        assert!(out.ends_with("profile ping /usr/bin/ping flags=(prompt) {\n}\n"));
+        assert_eq!(profile_flags(&out, "ping").unwrap(), ["prompt"]);
+        assert!(set_flags(SOURCE, "missing", &[]).is_err());
+    }
+
+    #[test]
+    fn checklist_keeps_exclusive_flags_apart() {
+        let path = PathBuf::from("/etc/apparmor.d/usr.sbin.cupsd");
+        let mut list = Checklist::new("/usr/sbin/cupsd", path, SOURCE.to_string()).unwrap();
+        let select = |list: &mut Checklist, name: &str| list.selected = list.flags.iter().position(|(f, _)| f == name).unwrap();
+        select(&mut list, "kill");
+        list.toggle();
+        select(&mut list, "mediate_deleted");
+        list.toggle();
+        assert_eq!(list.result(), ["attach_disconnected", "kill", "mediate_deleted"]);
+        select(&mut list, "delegate_deleted");
+        list.toggle();
+        select(&mut list, "attach_disconnected");
+        list.toggle();
+        assert_eq!(list.result(), ["kill", "delegate_deleted"]);
+        assert!(list.apply().unwrap().starts_with("/usr/sbin/cupsd flags=(kill, delegate_deleted) {\n  ^hat {"));
+    }
+
+    #[test]
+    fn audit_mode_is_read_from_the_flags() {
+        let path = PathBuf::from("/etc/apparmor.d/ping");
+        let mut backend = FakeBackend::with_profiles(&[("ping", Mode::Enforce)]);
+        backend.files.insert(path.clone(), "profile ping /usr/bin/ping {\n}\n".to_string());
+        backend.set_mode("ping", Mode::Audit).unwrap();
+        assert!(backend.files[&path].starts_with("profile ping /usr/bin/ping flags=(audit) {"));
+        // The kernel still says enforce; the listing has it from the file.
+        assert_eq!(backend.profiles[0].mode, Mode::Enforce);
+        let listed = backend.list_profiles().unwrap();
+        assert_eq!((listed[0].mode, listed[0].disk_mode), (Mode::Audit, Some(Mode::Audit)));
+
+        assert!(needs_rewrite(Some(Mode::Audit), Mode::Enforce));
+        assert!(needs_rewrite(Some(Mode::Complain), Mode::Audit));
+        assert!(!needs_rewrite(Some(Mode::Enforce), Mode::Audit));
+        switch_mode(&mut backend, "ping", Mode::Complain).unwrap();
+        assert!(backend.files[&path].starts_with("profile ping /usr/bin/ping flags=(complain) {"));
+        assert_eq!(backend.list_profiles().unwrap()[0].mode, Mode::Complain);
+    }
+
+    #[test]
+    fn switching_restores_the_file_when_the_kernel_refuses() {
+        let path = PathBuf::from("/etc/apparmor.d/usr.sbin.cupsd");
+        let mut backend = FakeBackend::with_profiles(&[("/usr/sbin/cupsd", Mode::Complain)]);
+        backend.files.insert(path.clone(), SOURCE.to_string());
+        switch_mode(&mut backend, "/usr/sbin/cupsd", Mode::Kill).unwrap();
+        assert_eq!(backend.profiles[0].mode, Mode::Kill);
+        assert!(backend.files[&path].starts_with("/usr/sbin/cupsd flags=(kill, attach_disconnected) {"));
+
+        backend.unsupported = vec![Mode::Prompt];
+        let err = switch_mode(&mut backend, "/usr/sbin/cupsd", Mode::Prompt).unwrap_err();
+        assert!(err.to_string().contains("doesn't support prompt mode"), "{}", err);
+        assert!(backend.files[&path].starts_with("/usr/sbin/cupsd flags=(kill, attach_disconnected) {"));
+        assert_eq!(backend.profiles[0].mode, Mode::Kill);
+    }
+}
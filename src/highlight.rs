@@ -0,0 +1,107 @@
+//! Line-based syntax highlighting for AppArmor policy source.
+
+use crate::policy;
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+pub const RULE_KEYWORDS: &[&str] = &[
+    "profile", "hat", "capability", "network", "signal", "ptrace", "mount", "remount", "umount", "pivot_root",
+    "dbus", "unix", "file", "link", "set", "rlimit", "change_profile", "userns", "io_uring", "mqueue", "abi",
+    "alias", "include", "#include",
+];
+
+const QUALIFIERS: &[&str] = &["audit", "allow", "owner", "other"];
+
+fn word_style(word: &str, first: bool) -> Style {
+    let base = Style::default();
+    match word {
+        "deny" => base.fg(Color::Red).add_modifier(Modifier::BOLD),
+        w if QUALIFIERS.contains(&w) => base.fg(Color::Yellow),
+        w if RULE_KEYWORDS.contains(&w) => base.fg(Color::Blue).add_modifier(Modifier::BOLD),
+        w if w.starts_with('<') => base.fg(Color::Magenta),
+        w if w.starts_with('^') => base.fg(Color::Blue).add_modifier(Modifier::BOLD),
+        w if w.starts_with("@{") && !w.contains('/') => base.fg(Color::Cyan),
+        w if w.starts_with(['/', '@', '"', '{']) => base.fg(Color::Green),
+        w if w.contains('=') => base.fg(Color::Cyan),
+        _ if first => base.fg(Color::Blue),
+        _ => base.fg(Color::Magenta),
+    }
+}
+
+/// Highlights one line of policy. Comments, includes, keywords, qualifiers,
+/// paths, variables and permissions each get their own colour.
+pub fn highlight_line(line: &str) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut rest = line;
+    let mut first = true;
+
+    while !rest.is_empty() {
+        let ws = rest.len() - rest.trim_start().len();
+        if ws > 0 {
+            spans.push(Span::raw(rest[..ws].to_string()));
+            rest = &rest[ws..];
+            continue;
+        }
+        if rest.starts_with('#') && !policy::is_hash_include(rest) {
+            spans.push(Span::styled(rest.to_string(), Style::default().fg(Color::DarkGray)));
+            break;
+        }
+
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (word, punct) = split_trailing(&rest[..end]);
+        spans.push(Span::styled(word.to_string(), word_style(word, first)));
+        if !punct.is_empty() {
+            spans.push(Span::styled(punct.to_string(), Style::default().fg(Color::DarkGray)));
+        }
+        first = false;
+        rest = &rest[end..];
+    }
+    Line::from(spans)
+}
+
+/// Splits a trailing `,` or `{`/`}` off a word.
+fn split_trailing(word: &str) -> (&str, &str) {
+    let cut = word.trim_end_matches([',', '{', '}']).len();
+    if cut == 0 { (word, "") } else { word.split_at(cut) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(line: &str) -> Vec<(String, Style)> {
+        highlight_line(line).spans.into_iter().map(|span| (span.content.into_owned(), span.style)).collect()
+    }
+
+    #[test]
+    fn colours_comments_and_includes() {
+        let comment = Style::default().fg(Color::DarkGray);
+        assert_eq!(spans("  # allow, deny"), [("  ".to_string(), Style::default()), ("# allow, deny".to_string(), comment)]);
+        assert_eq!(spans("#includes are comments"), [("#includes are comments".to_string(), comment)]);
+        assert_eq!(
+            spans("#include <abstractions/base>"),
+            [
+                ("#include".to_string(), Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)),
+                (" ".to_string(), Style::default()),
+                ("<abstractions/base>".to_string(), Style::default().fg(Color::Magenta)),
+            ]
+        );
+    }
+
+    #[test]
+    fn colours_rules_and_splits_off_the_comma() {
+        assert_eq!(
+            spans("deny /etc/shadow r,"),
+            [
+                ("deny".to_string(), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+                (" ".to_string(), Style::default()),
+                ("/etc/shadow".to_string(), Style::default().fg(Color::Green)),
+                (" ".to_string(), Style::default()),
+                ("r".to_string(), Style::default().fg(Color::Magenta)),
+                (",".to_string(), Style::default().fg(Color::DarkGray)),
+            ]
+        );
+    }
+}
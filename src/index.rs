@@ -0,0 +1,251 @@
+//! Maps loaded profile names to the policy file and line defining them.
+
+use crate::policy::{self, ItemKind};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where snapd writes the generated profiles of installed snaps.
+pub const SNAPD_PROFILES: &str = "/var/lib/snapd/apparmor/profiles";
+
+/// Subdirectories of the policy directory that hold no profiles.
+const SKIP_DIRS: &[&str] = &["abstractions", "tunables", "local", "disable", "cache", "force-complain", "abi"];
+
+/// Packaging leftovers that are never loaded.
+const SKIP_SUFFIXES: &[&str] = &[".dpkg-new", ".dpkg-old", ".dpkg-dist", ".dpkg-bak", ".rpmnew", ".rpmsave", ".orig", ".rej", "~"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileLocation {
+    pub path: PathBuf,
+    /// 1-based line of the profile header.
+    pub line: usize,
+}
+
+#[derive(Default, Debug)]
+pub struct ProfileIndex {
+    entries: HashMap<String, ProfileLocation>,
+    /// Header flags, for files the parser understood.
+    flags: HashMap<String, Vec<String>>,
+}
+
+impl ProfileIndex {
+    /// Indexes every policy file directly inside `dirs`.
+    pub fn scan(dirs: &[PathBuf]) -> ProfileIndex {
+        let mut index = ProfileIndex::default();
+        for dir in dirs {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+            paths.sort();
+            for path in paths.iter().filter(|path| is_policy_file(path)) {
+                if let Ok(content) = fs::read_to_string(path) {
+                    index.index_file(path, &content);
+                }
+            }
+        }
+        index
+    }
+
+    /// Adds the profiles defined in one file. Files the parser can't handle
+    /// are still indexed by a line-based scan of their top-level headers.
+    pub fn index_file(&mut self, path: &Path, content: &str) {
+        match policy::parse(content) {
+            Ok(parsed) => {
+                let vars = file_variables(&parsed);
+                for (name, profile, span) in parsed.all_profiles() {
+                    let location = ProfileLocation { path: path.to_path_buf(), line: span.line(content) };
+                    let name = expand(&unquote(&name), &vars);
+                    self.flags.entry(name.clone()).or_insert_with(|| profile.flags.clone());
+                    self.entries.entry(name).or_insert(location);
+                }
+            }
+            Err(_) => {
+                for (name, line) in scan_headers(content) {
+                    let location = ProfileLocation { path: path.to_path_buf(), line };
+                    self.entries.entry(name).or_insert(location);
+                }
+            }
+        }
+    }
+
+    /// Top-level profiles and where they are defined. Names still holding
+    /// variables from tunables are left out.
+    pub fn top_level(&self) -> impl Iterator<Item = (&str, &ProfileLocation)> {
+        self.entries
+            .iter()
+            .filter(|(name, _)| !name.contains("//") && !name.contains("@{"))
+            .map(|(name, location)| (name.as_str(), location))
+    }
+
+    /// The header flags of exactly this profile, hat or child.
+    pub fn flags(&self, name: &str) -> Option<&[String]> {
+        self.flags.get(name).map(Vec::as_slice)
+    }
+
+    /// Looks up a profile. Unknown hats and child profiles resolve to the
+    /// closest known parent.
+    pub fn get(&self, name: &str) -> Option<&ProfileLocation> {
+        let mut name = name;
+        loop {
+            if let Some(location) = self.entries.get(name) {
+                return Some(location);
+            }
+            name = &name[..name.rfind("//")?];
+        }
+    }
+}
+
+pub fn is_policy_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    path.is_file()
+        && !name.starts_with('.')
+        && !SKIP_DIRS.contains(&name)
+        && name != "README"
+        && !SKIP_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+fn unquote(name: &str) -> String {
+    name.replace('"', "")
+}
+
+/// Single-valued variables defined in the file, for profiles named after
+/// e.g. `@{exec_path}`.
+fn file_variables(parsed: &policy::Policy) -> HashMap<String, String> {
+    let mut vars = HashMap::new();
+    for item in &parsed.items {
+        if let ItemKind::Variable(var) = &item.kind {
+            if var.append || var.values.len() != 1 {
+                vars.remove(&var.name);
+            } else {
+                vars.insert(var.name.clone(), var.values[0].clone());
+            }
+        }
+    }
+    vars
+}
+
+fn expand(name: &str, vars: &HashMap<String, String>) -> String {
+    let mut name = name.to_string();
+    // Bounded so self-referencing variables can't loop forever.
+    for _ in 0..8 {
+        let Some((var, value)) = vars.iter().find(|(var, _)| name.contains(var.as_str())) else {
+            break;
+        };
+        name = name.replace(var.as_str(), value);
+    }
+    name
+}
+
+/// Finds `profile NAME ...{`, `/path ...{` and hat headers at the start of
+/// lines. Braces are counted to name hats and child profiles after their
+/// parent.
+fn scan_headers(content: &str) -> Vec<(String, usize)> {
+    let mut headers = Vec::new();
+    // Open profiles and the brace depth outside each.
+    let mut open: Vec<(String, usize)> = Vec::new();
+    let mut depth = 0usize;
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        if line.ends_with('{') {
+            let mut words = line.split_whitespace();
+            let name = match words.next() {
+                Some("profile" | "hat") => words.next(),
+                Some(hat) if hat.starts_with('^') => Some(&hat[1..]),
+                Some(path) if path.starts_with('/') => Some(path),
+                _ => None,
+            };
+            if let Some(name) = name.map(|name| unquote(name.trim_end_matches('{'))) {
+                let name = match open.last() {
+                    Some((parent, _)) => format!("{}//{}", parent, name),
+                    None => name,
+                };
+                headers.push((name.clone(), n + 1));
+                open.push((name, depth));
+            }
+        }
+        depth = (depth + line.matches('{').count()).saturating_sub(line.matches('}').count());
+        while open.last().is_some_and(|&(_, outside)| depth <= outside) {
+            open.pop();
+        }
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> ProfileIndex {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/index");
+        ProfileIndex::scan(&[root.join("apparmor.d"), root.join("snapd")])
+    }
+
+    fn at(index: &ProfileIndex, name: &str) -> Option<(String, usize)> {
+        let location = index.get(name)?;
+        Some((location.path.file_name()?.to_str()?.to_string(), location.line))
+    }
+
+    fn some(file: &str, line: usize) -> Option<(String, usize)> {
+        Some((file.to_string(), line))
+    }
+
+    #[test]
+    fn indexes_hats_and_child_profiles() {
+        let index = index();
+        assert_eq!(at(&index, "/usr/sbin/cupsd"), some("usr.sbin.cupsd", 3));
+        assert_eq!(at(&index, "/usr/sbin/cupsd//third_party"), some("usr.sbin.cupsd", 6));
+        assert_eq!(at(&index, "/usr/sbin/cupsd//dbus"), some("usr.sbin.cupsd", 10));
+        assert_eq!(at(&index, "/usr/sbin/cupsd//lpstat"), some("usr.sbin.cupsd", 14));
+        // Hats the kernel made up, e.g. for `px` transitions, go to the parent.
+        assert_eq!(at(&index, "/usr/sbin/cupsd//null-/usr/bin/true"), some("usr.sbin.cupsd", 3));
+        assert_eq!(index.flags("/usr/sbin/cupsd").unwrap(), ["attach_disconnected"]);
+        assert_eq!(index.flags("/usr/sbin/cupsd//third_party").unwrap(), [] as [String; 0]);
+    }
+
+    #[test]
+    fn indexes_every_profile_of_a_file() {
+        let index = index();
+        assert_eq!(at(&index, "/usr/bin/man"), some("usr.bin.man", 4));
+        assert_eq!(at(&index, "man_filter"), some("usr.bin.man", 8));
+        assert_eq!(at(&index, "man_groff"), some("usr.bin.man", 12));
+        assert_eq!(index.flags("man_groff").unwrap(), ["complain"]);
+    }
+
+    #[test]
+    fn expands_variables_and_quotes_in_names() {
+        let index = index();
+        assert_eq!(at(&index, "ping"), some("ping", 7));
+        assert_eq!(index.flags("ping").unwrap(), ["kill"]);
+        assert_eq!(at(&index, "ping helper"), some("ping", 11));
+        assert_eq!(at(&index, "snap.lxd.daemon"), some("snap.lxd.daemon", 10));
+        assert_eq!(index.flags("snap.lxd.daemon").unwrap(), ["attach_disconnected", "mediate_deleted"]);
+    }
+
+    #[test]
+    fn scans_headers_of_files_the_parser_rejects() {
+        let index = index();
+        assert_eq!(at(&index, "broken"), some("broken", 2));
+        assert_eq!(at(&index, "broken//inner"), some("broken", 4));
+        assert_eq!(at(&index, "broken//child"), some("broken", 6));
+        assert_eq!(at(&index, "/usr/bin/other"), some("broken", 10));
+        assert_eq!(index.flags("broken"), None);
+    }
+
+    #[test]
+    fn skips_leftovers_and_support_files() {
+        let index = index();
+        let mut top: Vec<&str> = index.top_level().map(|(name, _)| name).collect();
+        top.sort();
+        assert_eq!(
+            top,
+            ["/usr/bin/man", "/usr/bin/other", "/usr/sbin/cupsd", "broken", "man_filter", "man_groff", "ping", "ping helper", "snap.lxd.daemon"]
+        );
+        assert_eq!(at(&index, "/usr/sbin/cupsd"), some("usr.sbin.cupsd", 3));
+    }
+}
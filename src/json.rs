@@ -0,0 +1,327 @@
+//! Minimal JSON reader, just enough for the output of `aa-status --json`,
+//! and the writer for `--json` output.
+
+use anyhow::{anyhow, bail, Result};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Object(fields) => Some(fields),
+            _ => None,
+        }
+    }
+}
+
+/// Writes compact JSON. Numbers without a fraction are written as integers.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) if n.is_finite() => write!(f, "{}", n),
+            Value::Number(_) => f.write_str("null"),
+            Value::String(s) => write_string(f, s),
+            Value::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            Value::Object(fields) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for ch in s.chars() {
+        match ch {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+pub fn parse(input: &str) -> Result<Value> {
+    let mut parser = Parser { bytes: input.as_bytes(), pos: 0 };
+    let value = parser.value()?;
+    parser.skip_ws();
+    if parser.pos != parser.bytes.len() {
+        bail!("trailing characters at offset {}", parser.pos);
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_ws(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_ws();
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(anyhow!("expected '{}' at offset {}", byte as char, self.pos))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value> {
+        if self.bytes[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(anyhow!("invalid literal at offset {}", self.pos))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(anyhow!("unexpected character at offset {}", self.pos)),
+            None => Err(anyhow!("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value> {
+        self.expect(b'{')?;
+        let mut fields = Vec::new();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Value::Object(fields));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                bail!("expected object key at offset {}", self.pos);
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            fields.push((key, self.value()?));
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(fields));
+                }
+                _ => bail!("expected ',' or '}}' at offset {}", self.pos),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(items));
+                }
+                _ => bail!("expected ',' or ']' at offset {}", self.pos),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let start = self.pos;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.pos])?;
+        text.parse()
+            .map(Value::Number)
+            .map_err(|_| anyhow!("invalid number '{}' at offset {}", text, start))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect(b'"')?;
+        let mut out = Vec::new();
+        loop {
+            let Some(&byte) = self.bytes.get(self.pos) else {
+                bail!("unterminated string");
+            };
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&esc) = self.bytes.get(self.pos) else {
+                        bail!("unterminated escape");
+                    };
+                    self.pos += 1;
+                    match esc {
+                        b'"' | b'\\' | b'/' => out.push(esc),
+                        b'b' => out.push(0x08),
+                        b'f' => out.push(0x0c),
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'u' => {
+                            let ch = self.unicode_escape()?;
+                            let mut buf = [0; 4];
+                            out.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                        }
+                        _ => bail!("invalid escape at offset {}", self.pos - 1),
+                    }
+                }
+                _ => out.push(byte),
+            }
+        }
+        Ok(String::from_utf8(out)?)
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let digits = self
+            .bytes
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| anyhow!("truncated \\u escape"))?;
+        self.pos += 4;
+        u32::from_str_radix(std::str::from_utf8(digits)?, 16)
+            .map_err(|_| anyhow!("invalid \\u escape at offset {}", self.pos - 4))
+    }
+
+    fn unicode_escape(&mut self) -> Result<char> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) && self.bytes[self.pos..].starts_with(b"\\u") {
+            self.pos += 2;
+            let low = self.hex4()?;
+            0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+        } else {
+            high
+        };
+        Ok(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn parses_nested_values() {
+        let value = parse(r#" {"a": [1, -2.5, 3e2, true, false, null], "b": {}, "c": []} "#).unwrap();
+        assert_eq!(
+            value.get("a").and_then(Value::as_array).unwrap(),
+            [Value::Number(1.0), Value::Number(-2.5), Value::Number(300.0), Value::Bool(true), Value::Bool(false), Value::Null]
+        );
+        assert_eq!(value.get("b").and_then(Value::as_object), Some(&[][..]));
+        assert_eq!(value.get("c").and_then(Value::as_array), Some(&[][..]));
+        assert_eq!(value.get("d"), None);
+    }
+
+    #[test]
+    fn decodes_escapes() {
+        assert_eq!(parse(r#""a\"b\\c\/d\n\t\r\b\f""#).unwrap(), string("a\"b\\c/d\n\t\r\u{8}\u{c}"));
+        assert_eq!(parse(r#""\u00e9\u4E2D""#).unwrap(), string("é中"));
+        assert_eq!(parse(r#""\ud83d\ude00""#).unwrap(), string("😀"));
+        assert_eq!(parse(r#""\ud83d""#).unwrap(), string("\u{fffd}"));
+        assert_eq!(parse("\"ünïcode\"").unwrap(), string("ünïcode"));
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        let error = |input: &str| parse(input).unwrap_err().to_string();
+        assert_eq!(error("[1, 2"), "expected ',' or ']' at offset 5");
+        assert_eq!(error(r#"{"a" 1}"#), "expected ':' at offset 5");
+        assert_eq!(error("{1: 2}"), "expected object key at offset 1");
+        assert_eq!(error("[1] x"), "trailing characters at offset 4");
+        assert_eq!(error("tru"), "invalid literal at offset 0");
+        assert_eq!(error("[1.2.3]"), "invalid number '1.2.3' at offset 1");
+        assert_eq!(error(r#""\x""#), "invalid escape at offset 2");
+        assert_eq!(error(r#""abc"#), "unterminated string");
+        assert_eq!(error(""), "unexpected end of input");
+        assert_eq!(error("@"), "unexpected character at offset 0");
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        let value = Value::Object(vec![
+            ("name".to_string(), string("a \"b\"\\\n\u{1}")),
+            ("pids".to_string(), Value::Array(vec![Value::Number(12.0), Value::Number(0.5)])),
+            ("none".to_string(), Value::Null),
+            ("ok".to_string(), Value::Bool(true)),
+        ]);
+        let text = value.to_string();
+        assert_eq!(text, r#"{"name":"a \"b\"\\\n\u0001","pids":[12,0.5],"none":null,"ok":true}"#);
+        assert_eq!(parse(&text).unwrap(), value);
+    }
+}
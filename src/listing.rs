@@ -0,0 +1,131 @@
+//! Ordering and grouping the rows of the profile list.
+
+use crate::profile::{Mode, Profile};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Mode,
+    /// Most confined processes first.
+    Processes,
+    /// Most denials in the audit log first.
+    Denials,
+}
+
+impl SortKey {
+    pub fn next(self) -> SortKey {
+        match self {
+            SortKey::Name => SortKey::Mode,
+            SortKey::Mode => SortKey::Processes,
+            SortKey::Processes => SortKey::Denials,
+            SortKey::Denials => SortKey::Name,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Mode => "mode",
+            SortKey::Processes => "processes",
+            SortKey::Denials => "denials",
+        }
+    }
+}
+
+/// One row of the profile list.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ListRow {
+    /// Heading of a mode group with the number of profiles in it.
+    Group { mode: Mode, count: usize, collapsed: bool },
+    /// Index into the profile list.
+    Profile(usize),
+}
+
+/// How the list is laid out.
+#[derive(Default)]
+pub struct Layout {
+    pub sort: SortKey,
+    pub group_by_mode: bool,
+    pub collapsed: HashSet<Mode>,
+}
+
+impl Layout {
+    /// Sorts `visible` (indices into `profiles`) and splits it into mode
+    /// groups when grouping is on. `denials` counts denials per profile.
+    pub fn rows(&self, profiles: &[Profile], visible: &[usize], denials: &HashMap<String, usize>) -> Vec<ListRow> {
+        let mode_rank = |mode: Mode| Mode::ALL.iter().position(|&m| m == mode).unwrap_or(usize::MAX);
+        let denied = |profile: &Profile| denials.get(&profile.name).copied().unwrap_or(0);
+        let mut sorted = visible.to_vec();
+        sorted.sort_by(|&a, &b| {
+            let (a, b) = (&profiles[a], &profiles[b]);
+            let primary = match self.sort {
+                SortKey::Name => std::cmp::Ordering::Equal,
+                SortKey::Mode => mode_rank(a.mode).cmp(&mode_rank(b.mode)),
+                SortKey::Processes => b.processes.len().cmp(&a.processes.len()),
+                SortKey::Denials => denied(b).cmp(&denied(a)),
+            };
+            primary.then_with(|| a.name.cmp(&b.name))
+        });
+
+        if !self.group_by_mode {
+            return sorted.into_iter().map(ListRow::Profile).collect();
+        }
+        let mut rows = Vec::new();
+        for mode in Mode::ALL {
+            let members: Vec<usize> = sorted.iter().copied().filter(|&i| profiles[i].mode == mode).collect();
+            if members.is_empty() {
+                continue;
+            }
+            let collapsed = self.collapsed.contains(&mode);
+            rows.push(ListRow::Group { mode, count: members.len(), collapsed });
+            if !collapsed {
+                rows.extend(members.into_iter().map(ListRow::Profile));
+            }
+        }
+        rows
+    }
+}
+
+/// Number of profiles in each mode, in `Mode::ALL` order. Unknown modes
+/// are only counted when there are any.
+pub fn mode_counts(profiles: &[Profile]) -> Vec<(Mode, usize)> {
+    let counts = Mode::ALL.iter().map(|&mode| (mode, profiles.iter().filter(|p| p.mode == mode).count()));
+    counts.filter(|&(mode, count)| mode != Mode::Unknown || count > 0).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_follow_mode_order_and_collapse() {
+        let profiles = vec![
+            Profile::new("b", Mode::Complain),
+            Profile::new("c", Mode::Enforce),
+            Profile::new("a", Mode::Enforce),
+        ];
+        let mut layout = Layout { group_by_mode: true, ..Layout::default() };
+        let rows = layout.rows(&profiles, &[0, 1, 2], &HashMap::new());
+        assert_eq!(
+            rows,
+            [
+                ListRow::Group { mode: Mode::Enforce, count: 2, collapsed: false },
+                ListRow::Profile(2),
+                ListRow::Profile(1),
+                ListRow::Group { mode: Mode::Complain, count: 1, collapsed: false },
+                ListRow::Profile(0),
+            ]
+        );
+        layout.collapsed.insert(Mode::Enforce);
+        layout.sort = SortKey::Denials;
+        let denials = HashMap::from([("c".to_string(), 4)]);
+        let rows = layout.rows(&profiles, &[0, 1, 2], &denials);
+        assert_eq!(rows[0], ListRow::Group { mode: Mode::Enforce, count: 2, collapsed: true });
+        assert_eq!(rows.len(), 3);
+
+        layout.group_by_mode = false;
+        assert_eq!(layout.rows(&profiles, &[0, 1, 2], &denials)[0], ListRow::Profile(1));
+    }
+}
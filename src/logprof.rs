@@ -0,0 +1,376 @@
+//! Turning denials into proposed profile rules, like `aa-logprof` does.
+
+use crate::audit::{AuditEvent, Verdict};
+use crate::policy;
+use anyhow::{anyhow, Result};
+use ratatui::widgets::ListState;
+
+/// One alternative way to allow a denial.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuleOption {
+    pub label: &'static str,
+    pub rule: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub profile: String,
+    /// Ordered from most to least specific; `choice` indexes into it.
+    pub options: Vec<RuleOption>,
+    pub choice: usize,
+    pub accepted: bool,
+    /// How many events this suggestion covers.
+    pub events: usize,
+}
+
+impl Suggestion {
+    pub fn rule(&self) -> &str {
+        &self.options[self.choice].rule
+    }
+
+    pub fn cycle(&mut self, forward: bool) {
+        let n = self.options.len();
+        self.choice = if forward { (self.choice + 1) % n } else { (self.choice + n - 1) % n };
+    }
+}
+
+/// Well-known paths that an abstraction already grants.
+const ABSTRACTIONS: &[(&str, &str)] = &[
+    ("/etc/fonts/", "fonts"),
+    ("/usr/share/fonts/", "fonts"),
+    ("/var/cache/fontconfig/", "fonts"),
+    ("/etc/passwd", "nameservice"),
+    ("/etc/group", "nameservice"),
+    ("/etc/nsswitch.conf", "nameservice"),
+    ("/etc/hosts", "nameservice"),
+    ("/etc/resolv.conf", "nameservice"),
+    ("/run/systemd/resolve/", "nameservice"),
+    ("/etc/ssl/", "ssl_certs"),
+    ("/usr/share/ca-certificates/", "ssl_certs"),
+    ("/etc/ca-certificates/", "ssl_certs"),
+    ("/usr/share/locale/", "base"),
+    ("/usr/lib/locale/", "base"),
+    ("/etc/ld.so.cache", "base"),
+    ("/dev/snd/", "audio"),
+    ("/etc/asound.conf", "audio"),
+    ("/usr/share/icons/", "freedesktop.org"),
+    ("/usr/share/mime/", "freedesktop.org"),
+    ("/usr/lib/python3", "python"),
+    ("/usr/share/perl", "perl"),
+    ("/usr/share/X11/", "X"),
+    ("/tmp/.X11-unix/", "X"),
+    ("/usr/share/zoneinfo/", "base"),
+];
+
+/// Builds suggestions for the denied events, merging events that map to
+/// the same rule and file permissions requested on the same path.
+pub fn suggest(events: &[&AuditEvent]) -> Vec<Suggestion> {
+    // (profile, path, mask, event count)
+    let mut file_masks: Vec<(String, String, String, usize)> = Vec::new();
+    let mut suggestions: Vec<Suggestion> = Vec::new();
+
+    for event in events.iter().filter(|e| e.verdict == Verdict::Denied) {
+        let profile = event.profile.clone();
+        if is_file_event(event) {
+            let (Some(path), Some(mask)) = (event.name.clone(), event.denied_mask.as_ref().or(event.requested_mask.as_ref()))
+            else {
+                continue;
+            };
+            match file_masks.iter_mut().find(|(p, n, _, _)| *p == profile && *n == path) {
+                Some((_, _, existing, count)) => {
+                    existing.push_str(mask);
+                    *count += 1;
+                }
+                None => file_masks.push((profile, path, mask.clone(), 1)),
+            }
+            continue;
+        }
+
+        let Some(rule) = simple_rule(event) else {
+            continue;
+        };
+        match suggestions.iter_mut().find(|s| s.profile == profile && s.options[0].rule == rule) {
+            Some(existing) => existing.events += 1,
+            None => suggestions.push(Suggestion {
+                profile,
+                options: vec![RuleOption { label: "exact", rule }],
+                choice: 0,
+                accepted: true,
+                events: 1,
+            }),
+        }
+    }
+
+    let files = file_masks.into_iter().map(|(profile, path, mask, events)| Suggestion {
+        profile,
+        options: file_options(&path, &file_permissions(&mask)),
+        choice: 0,
+        accepted: true,
+        events,
+    });
+    files.chain(suggestions).collect()
+}
+
+fn is_file_event(event: &AuditEvent) -> bool {
+    event.field("class") == Some("file")
+        || (event.field("class").is_none()
+            && event.name.as_deref().is_some_and(|name| name.starts_with('/'))
+            && event.requested_mask.is_some()
+            && !matches!(event.operation.as_str(), "signal" | "ptrace" | "mount" | "umount" | "pivotroot"))
+}
+
+/// Converts an audit mask such as `rc` or `wd` into rule permissions.
+pub fn file_permissions(mask: &str) -> String {
+    let has = |chars: &str| mask.chars().any(|c| chars.contains(c));
+    let mut perms = String::new();
+    if has("m") {
+        perms.push('m');
+    }
+    if has("r") {
+        perms.push('r');
+    }
+    if has("wcdD") {
+        perms.push('w');
+    } else if has("a") {
+        perms.push('a');
+    }
+    if has("l") {
+        perms.push('l');
+    }
+    if has("k") {
+        perms.push('k');
+    }
+    if has("x") {
+        perms.push_str("ix");
+    }
+    perms
+}
+
+fn file_options(path: &str, perms: &str) -> Vec<RuleOption> {
+    let mut options = vec![RuleOption { label: "exact", rule: format!("{} {},", path, perms) }];
+
+    let generic = generalize(path);
+    if let Some((dir, _)) = generic.rsplit_once('/').filter(|(dir, _)| !dir.is_empty()) {
+        options.push(RuleOption { label: "glob", rule: format!("{}/* {},", dir, perms) });
+        options.push(RuleOption { label: "recursive glob", rule: format!("{}/** {},", dir, perms) });
+    }
+
+    if let Some((_, abstraction)) = ABSTRACTIONS.iter().find(|(prefix, _)| path.starts_with(prefix)) {
+        options.push(RuleOption {
+            label: "abstraction",
+            rule: format!("#include <abstractions/{}>", abstraction),
+        });
+    }
+    options
+}
+
+/// Replaces per-user and per-process path components with the usual
+/// tunables, e.g. `/home/ann/.cache/x` becomes `owner @{HOME}/.cache/x`.
+fn generalize(path: &str) -> String {
+    let parts: Vec<&str> = path.splitn(4, '/').collect();
+    match parts.as_slice() {
+        ["", "home", _, rest] => format!("owner @{{HOME}}/{}", rest),
+        ["", "proc", pid, rest] if pid.chars().all(|c| c.is_ascii_digit()) => format!("@{{PROC}}/@{{pid}}/{}", rest),
+        _ => path.to_string(),
+    }
+}
+
+fn simple_rule(event: &AuditEvent) -> Option<String> {
+    match event.operation.as_str() {
+        "capable" => Some(format!("capability {},", event.field("capname")?)),
+        "signal" => {
+            let mut rule = format!("signal ({})", event.requested_mask.as_deref().unwrap_or("send"));
+            if let Some(signal) = event.field("signal") {
+                rule.push_str(&format!(" set=({})", signal));
+            }
+            if let Some(peer) = event.field("peer") {
+                rule.push_str(&format!(" peer={}", peer));
+            }
+            Some(rule + ",")
+        }
+        "ptrace" => {
+            let mut rule = format!("ptrace ({})", event.requested_mask.as_deref().unwrap_or("trace"));
+            if let Some(peer) = event.field("peer") {
+                rule.push_str(&format!(" peer={}", peer));
+            }
+            Some(rule + ",")
+        }
+        op if op.starts_with("dbus_") => {
+            let mut rule = format!("dbus ({})", event.field("mask").unwrap_or("send"));
+            for key in ["bus", "path", "interface", "member"] {
+                if let Some(value) = event.field(key) {
+                    rule.push_str(&format!(" {}={}", key, value));
+                }
+            }
+            if let Some(name) = event.field("name") {
+                rule.push_str(&format!(" peer=(name={})", name));
+            }
+            Some(rule + ",")
+        }
+        _ => {
+            let family = event.field("family")?;
+            match event.field("sock_type") {
+                Some(sock_type) => Some(format!("network {} {},", family, sock_type)),
+                None => Some(format!("network {},", family)),
+            }
+        }
+    }
+}
+
+/// Inserts `rules` before the closing brace of `profile`'s block, which
+/// may be a hat or child profile (`parent//child`). Rules and includes the
+/// profile already has are skipped.
+pub fn insert_rules(source: &str, profile: &str, rules: &[&str]) -> Result<String> {
+    let policy = policy::parse(source).map_err(|e| anyhow!("line {}: {}", e.span.line(source), e.message))?;
+    let (_, found, span) = policy
+        .all_profiles()
+        .into_iter()
+        .find(|(name, _, _)| name == profile)
+        .ok_or_else(|| anyhow!("profile {} not found in file", profile))?;
+    let mut existing: Vec<String> = found.rules().map(|rule| rule.to_string()).collect();
+    existing.extend(found.includes().map(|include| format!("#include <{}>", include.path())));
+    let mut new: Vec<&str> = Vec::new();
+    for &rule in rules {
+        let key = include_key(rule);
+        if !existing.contains(&key) && !new.iter().any(|r| include_key(r) == key) {
+            new.push(rule);
+        }
+    }
+    let rules = new;
+    let end = span.end - 1; // the closing '}'
+    let line_start = source[..end].rfind('\n').map_or(0, |i| i + 1);
+    let before_brace = &source[line_start..end];
+
+    let mut out = String::with_capacity(source.len() + rules.len() * 32);
+    if before_brace.trim().is_empty() {
+        // The brace is on a line of its own: put the rules on the lines above.
+        out.push_str(&source[..line_start]);
+        for rule in rules {
+            out.push_str(&format!("{}  {}\n", before_brace, rule));
+        }
+        out.push_str(&source[line_start..]);
+    } else {
+        out.push_str(source[..end].trim_end());
+        out.push('\n');
+        for rule in rules {
+            out.push_str(&format!("  {}\n", rule));
+        }
+        out.push_str(&source[end..]);
+    }
+    Ok(out)
+}
+
+/// `include <x>`, `#include "x"` and `#include <x>` all compare as the
+/// last; other rules as written.
+fn include_key(rule: &str) -> String {
+    let target = rule.strip_prefix('#').unwrap_or(rule).strip_prefix("include").map(str::trim);
+    match target {
+        Some(target) if target.starts_with(['<', '"']) => {
+            format!("#include <{}>", target.trim_start_matches(['<', '"']).trim_end_matches(['>', '"']))
+        }
+        _ => rule.to_string(),
+    }
+}
+
+/// Appends `rules` to a `local/` override file.
+pub fn append_rules(source: &str, rules: &[&str]) -> String {
+    let mut out = source.to_string();
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    for rule in rules {
+        out.push_str(rule);
+        out.push('\n');
+    }
+    out
+}
+
+/// State of the suggestion review view.
+#[derive(Default)]
+pub struct Review {
+    pub suggestions: Vec<Suggestion>,
+    pub state: ListState,
+    /// Write to the profile's `local/` override instead of the profile.
+    pub to_local: bool,
+}
+
+/// Whether `profile` in `source` includes its `local/<file_name>` override.
+pub fn includes_local(source: &str, profile: &str, file_name: &str) -> bool {
+    let Ok(policy) = policy::parse(source) else {
+        return false;
+    };
+    let local = format!("local/{}", file_name);
+    policy
+        .all_profiles()
+        .into_iter()
+        .any(|(name, found, _)| name == profile && found.includes().any(|include| include.path() == local))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit;
+
+    const SOURCE: &str = "#include <tunables/global>\n\nprofile firefox /usr/lib/firefox/firefox {\n  #include <abstractions/base>\n  /etc/shadow r,\n\n  ^browser {\n    capability sys_admin,\n  }\n}\n";
+
+    fn events(lines: &[&str]) -> Vec<AuditEvent> {
+        lines.iter().map(|line| audit::parse_line(line).unwrap()).collect()
+    }
+
+    #[test]
+    fn merges_denials_into_rule_options() {
+        let events = events(&[
+            r#"apparmor="DENIED" operation="open" class="file" profile="firefox" name="/home/ann/.cache/x" requested_mask="r" denied_mask="r""#,
+            r#"apparmor="DENIED" operation="mknod" class="file" profile="firefox" name="/home/ann/.cache/x" requested_mask="c" denied_mask="c""#,
+            r#"apparmor="DENIED" operation="open" class="file" profile="firefox" name="/etc/fonts/fonts.conf" requested_mask="r" denied_mask="r""#,
+            r#"apparmor="DENIED" operation="capable" profile="firefox" capname="sys_ptrace""#,
+            r#"apparmor="DENIED" operation="capable" profile="firefox" capname="sys_ptrace""#,
+            r#"apparmor="ALLOWED" operation="capable" profile="firefox" capname="chown""#,
+            r#"apparmor="DENIED" operation="create" profile="firefox" family="inet" sock_type="stream""#,
+        ]);
+        let suggestions = suggest(&events.iter().collect::<Vec<_>>());
+        let rules: Vec<Vec<&str>> = suggestions.iter().map(|s| s.options.iter().map(|o| o.rule.as_str()).collect()).collect();
+        assert_eq!(
+            rules,
+            [
+                vec!["/home/ann/.cache/x rw,", "owner @{HOME}/.cache/* rw,", "owner @{HOME}/.cache/** rw,"],
+                vec!["/etc/fonts/fonts.conf r,", "/etc/fonts/* r,", "/etc/fonts/** r,", "#include <abstractions/fonts>"],
+                vec!["capability sys_ptrace,"],
+                vec!["network inet stream,"],
+            ]
+        );
+        assert_eq!(suggestions.iter().map(|s| s.events).collect::<Vec<_>>(), [2, 1, 2, 1]);
+    }
+
+    #[test]
+    fn maps_masks_and_tunables() {
+        assert_eq!(file_permissions("rc"), "rw");
+        assert_eq!(file_permissions("ra"), "ra");
+        assert_eq!(file_permissions("mrx"), "mrix");
+        assert_eq!(file_permissions("lk"), "lk");
+        assert_eq!(generalize("/proc/1234/status"), "@{PROC}/@{pid}/status");
+        assert_eq!(generalize("/proc/self/status"), "/proc/self/status");
+        assert_eq!(generalize("/usr/bin/ls"), "/usr/bin/ls");
+    }
+
+    #[test]
+    fn inserts_into_the_right_block_without_duplicates() {
+        let out = insert_rules(SOURCE, "firefox", &["/etc/shadow r,", "include <abstractions/base>", "/tmp/x rw,", "/tmp/x rw,"]).unwrap();
+        assert_eq!(out, SOURCE.replace("  }\n}\n", "  }\n  /tmp/x rw,\n}\n"));
+        let out = insert_rules(SOURCE, "firefox//browser", &["capability sys_admin,", "#include <abstractions/fonts>"]).unwrap();
+        assert_eq!(out, SOURCE.replace("    capability sys_admin,\n", "    capability sys_admin,\n    #include <abstractions/fonts>\n"));
+        let out = insert_rules("profile x { }\n", "x", &["/tmp/y r,"]).unwrap();
+        assert_eq!(out, "profile x {\n  /tmp/y r,\n}\n");
+        assert!(insert_rules(SOURCE, "firefox//missing", &["/tmp/x r,"]).is_err());
+    }
+
+    #[test]
+    fn local_overrides_must_be_included() {
+        assert_eq!(append_rules("/tmp/a r,", &["/tmp/b r,"]), "/tmp/a r,\n/tmp/b r,\n");
+        assert_eq!(append_rules("", &["/tmp/b r,"]), "/tmp/b r,\n");
+        let source = SOURCE.replace("  /etc/shadow r,\n", "  /etc/shadow r,\n  include if exists <local/firefox>\n");
+        assert!(includes_local(&source, "firefox", "firefox"));
+        assert!(!includes_local(&source, "firefox//browser", "firefox"));
+        assert!(!includes_local(SOURCE, "firefox", "firefox"));
+    }
+}
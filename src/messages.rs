@@ -0,0 +1,69 @@
+//! Status messages shown in the bottom bar and the message history view.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Level {
+    Info,
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub time: SystemTime,
+    pub level: Level,
+    pub text: String,
+}
+
+impl Message {
+    pub fn timestamp(&self) -> String {
+        clock(self.time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0))
+    }
+}
+
+/// Time of day as `HH:MM:SS UTC` for seconds since the epoch.
+pub fn clock(secs: u64) -> String {
+    format!("{:02}:{:02}:{:02} UTC", secs / 3600 % 24, secs / 60 % 60, secs % 60)
+}
+
+#[derive(Default)]
+pub struct Messages {
+    pub history: Vec<Message>,
+    /// Offset from the newest entry in the history view.
+    pub scroll: usize,
+}
+
+impl Messages {
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(Level::Info, text.into());
+    }
+
+    /// Records an error with its whole context chain, e.g.
+    /// "Failed to execute aa-enforce: No such file or directory".
+    pub fn error(&mut self, err: &anyhow::Error) {
+        self.push(Level::Error, format!("{:#}", err));
+    }
+
+    pub fn last(&self) -> Option<&Message> {
+        self.history.last()
+    }
+
+    pub fn scroll_up(&mut self) {
+        if self.scroll + 1 < self.history.len() {
+            self.scroll += 1;
+        }
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    fn push(&mut self, level: Level, text: String) {
+        self.history.push(Message {
+            time: SystemTime::now(),
+            level,
+            text,
+        });
+        self.scroll = 0;
+    }
+}
@@ -0,0 +1,85 @@
+//! Parser for the AppArmor policy language.
+
+mod ast;
+mod lexer;
+mod parser;
+
+pub use ast::*;
+pub use lexer::is_hash_include;
+pub use parser::parse;
+
+use std::fmt;
+
+/// Byte range into the parsed source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// 1-based line of the start of the span.
+    pub fn line(self, src: &str) -> usize {
+        line_of(src, self.start)
+    }
+}
+
+/// 1-based line number of a byte offset.
+pub fn line_of(src: &str, offset: usize) -> usize {
+    src.as_bytes()[..offset.min(src.len())].iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl ParseError {
+    pub fn new(message: impl Into<String>, span: Span) -> ParseError {
+        ParseError { message: message.into(), span }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (at byte {})", self.message, self.span.start)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Policy {
+    /// Top-level profiles.
+    pub fn profiles(&self) -> impl Iterator<Item = (&Profile, Span)> {
+        self.items.iter().filter_map(|item| match &item.kind {
+            ItemKind::Profile(profile) => Some((profile, item.span)),
+            _ => None,
+        })
+    }
+
+    /// Every profile, hat and child profile with its full `parent//child`
+    /// name, in source order.
+    pub fn all_profiles(&self) -> Vec<(String, &Profile, Span)> {
+        fn walk<'a>(prefix: &str, profile: &'a Profile, span: Span, out: &mut Vec<(String, &'a Profile, Span)>) {
+            let name = match prefix {
+                "" => profile.display_name().to_string(),
+                parent => format!("{}//{}", parent, profile.display_name()),
+            };
+            out.push((name.clone(), profile, span));
+            for (child, child_span) in profile.children() {
+                walk(&name, child, child_span, out);
+            }
+        }
+
+        let mut out = Vec::new();
+        for (profile, span) in self.profiles() {
+            walk("", profile, span, &mut out);
+        }
+        out
+    }
+}
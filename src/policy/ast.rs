@@ -0,0 +1,338 @@
+//! Syntax tree of an AppArmor policy file. Words are kept as written
+//! (quotes included); `Display` prints each node in canonical form.
+
+use super::Span;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Policy {
+    pub items: Vec<Item>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    pub kind: ItemKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ItemKind {
+    Include(Include),
+    Variable(Variable),
+    /// `abi <abi/3.0>,`
+    Abi(String),
+    /// `alias /usr/ -> /mnt/usr/,`
+    Alias { from: String, to: String },
+    Profile(Profile),
+    Rule(Rule),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Include {
+    /// `<abstractions/base>` or `"/path"`, as written.
+    pub target: String,
+    /// `#include` rather than `include`.
+    pub hash: bool,
+    pub if_exists: bool,
+}
+
+impl Include {
+    /// The included path without the surrounding `<>` or quotes.
+    pub fn path(&self) -> &str {
+        self.target.trim_start_matches(['<', '"']).trim_end_matches(['>', '"'])
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variable {
+    /// Name including `@{}`.
+    pub name: String,
+    pub append: bool,
+    pub values: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    /// Empty for an unnamed profile whose name is its attachment path.
+    pub name: String,
+    pub attachment: Option<String>,
+    /// Declared with `profile`, as opposed to a bare path.
+    pub keyword: bool,
+    /// A hat, declared as `^name` or with `hat`.
+    pub hat: bool,
+    pub flags: Vec<String>,
+    /// Other header conditionals such as `xattrs=(...)`, as written.
+    pub conditionals: Vec<(String, String)>,
+    pub body: Vec<Item>,
+    /// From the first header token through the opening `{`.
+    pub header: Span,
+    /// The `flags=(...)` text, if present.
+    pub flags_span: Option<Span>,
+}
+
+impl Profile {
+    /// The name the kernel knows the profile by.
+    pub fn display_name(&self) -> &str {
+        match &self.attachment {
+            Some(attachment) if self.name.is_empty() => attachment,
+            _ => &self.name,
+        }
+    }
+
+    /// Rules, includes and other items of the body, not descending into
+    /// child profiles.
+    pub fn rules(&self) -> impl Iterator<Item = &Rule> {
+        self.body.iter().filter_map(|item| match &item.kind {
+            ItemKind::Rule(rule) => Some(rule),
+            _ => None,
+        })
+    }
+
+    pub fn includes(&self) -> impl Iterator<Item = &Include> {
+        self.body.iter().filter_map(|item| match &item.kind {
+            ItemKind::Include(include) => Some(include),
+            _ => None,
+        })
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = (&Profile, Span)> {
+        self.body.iter().filter_map(|item| match &item.kind {
+            ItemKind::Profile(profile) => Some((profile, item.span)),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Qualifiers {
+    pub audit: bool,
+    pub deny: bool,
+    pub allow: bool,
+    pub owner: bool,
+    pub other: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub qualifiers: Qualifiers,
+    pub kind: RuleKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuleKind {
+    File(FileRule),
+    /// `link [subset] /from -> /to,`
+    Link { subset: bool, from: String, to: String },
+    /// Capability names; empty for all capabilities.
+    Capability(Vec<String>),
+    /// Domain, type and protocol words; empty for all networking.
+    Network(Vec<String>),
+    Signal(Conditional),
+    Ptrace(Conditional),
+    /// `mount`, `remount`, `umount` and `pivot_root`.
+    Mount { keyword: String, rule: Conditional },
+    Dbus(Conditional),
+    Unix(Conditional),
+    /// `set rlimit nofile <= 1024,`
+    Rlimit { resource: String, value: String },
+    /// Rules this parser has no dedicated node for, e.g. `change_profile`
+    /// or `userns`.
+    Other { keyword: String, rule: Conditional },
+}
+
+impl RuleKind {
+    /// Rule type name used for grouping, e.g. "file" or "capability".
+    pub fn type_name(&self) -> &str {
+        match self {
+            RuleKind::File(_) => "file",
+            RuleKind::Link { .. } => "link",
+            RuleKind::Capability(_) => "capability",
+            RuleKind::Network(_) => "network",
+            RuleKind::Signal(_) => "signal",
+            RuleKind::Ptrace(_) => "ptrace",
+            RuleKind::Mount { .. } => "mount",
+            RuleKind::Dbus(_) => "dbus",
+            RuleKind::Unix(_) => "unix",
+            RuleKind::Rlimit { .. } => "rlimit",
+            RuleKind::Other { keyword, .. } => keyword,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileRule {
+    /// `None` for a bare `file,` rule.
+    pub path: Option<String>,
+    pub perms: String,
+    /// Exec transition target after `->`.
+    pub target: Option<String>,
+    /// Written with the `file` keyword.
+    pub keyword: bool,
+}
+
+/// The shared shape of signal, ptrace, dbus, unix and mount rules:
+/// `keyword (access) key=value ... rest`.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Conditional {
+    pub access: Vec<String>,
+    /// Values as written, e.g. `("peer", "(name=org.foo label=bar)")`. A
+    /// `key in (...)` list keeps the `in` with the key: `("options in", "(ro)")`.
+    pub conditionals: Vec<(String, String)>,
+    /// Remaining words, e.g. mount sources and `->` targets.
+    pub rest: Vec<String>,
+}
+
+impl fmt::Display for Qualifiers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (set, word) in [
+            (self.audit, "audit "),
+            (self.allow, "allow "),
+            (self.deny, "deny "),
+            (self.owner, "owner "),
+            (self.other, "other "),
+        ] {
+            if set {
+                f.write_str(word)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Conditional {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.access.as_slice() {
+            [] => {}
+            [one] => write!(f, " {}", one)?,
+            many => write!(f, " ({})", many.join(", "))?,
+        }
+        for (key, value) in &self.conditionals {
+            match key.strip_suffix(" in") {
+                Some(key) => write!(f, " {} in {}", key, value)?,
+                None => write!(f, " {}={}", key, value)?,
+            }
+        }
+        for word in &self.rest {
+            write!(f, " {}", word)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_words(f: &mut fmt::Formatter, words: &[String]) -> fmt::Result {
+    for word in words {
+        write!(f, " {}", word)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.qualifiers)?;
+        match &self.kind {
+            RuleKind::File(rule) => {
+                if rule.keyword {
+                    f.write_str("file")?;
+                }
+                if let Some(path) = &rule.path {
+                    if rule.keyword {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{} {}", path, rule.perms)?;
+                }
+                if let Some(target) = &rule.target {
+                    write!(f, " -> {}", target)?;
+                }
+            }
+            RuleKind::Link { subset, from, to } => {
+                f.write_str("link")?;
+                if *subset {
+                    f.write_str(" subset")?;
+                }
+                write!(f, " {} -> {}", from, to)?;
+            }
+            RuleKind::Capability(names) => {
+                f.write_str("capability")?;
+                write_words(f, names)?;
+            }
+            RuleKind::Network(words) => {
+                f.write_str("network")?;
+                write_words(f, words)?;
+            }
+            RuleKind::Signal(rule) => write!(f, "signal{}", rule)?,
+            RuleKind::Ptrace(rule) => write!(f, "ptrace{}", rule)?,
+            RuleKind::Mount { keyword, rule } => write!(f, "{}{}", keyword, rule)?,
+            RuleKind::Dbus(rule) => write!(f, "dbus{}", rule)?,
+            RuleKind::Unix(rule) => write!(f, "unix{}", rule)?,
+            RuleKind::Rlimit { resource, value } => write!(f, "set rlimit {} <= {}", resource, value)?,
+            RuleKind::Other { keyword, rule } => write!(f, "{}{}", keyword, rule)?,
+        }
+        f.write_str(",")
+    }
+}
+
+impl fmt::Display for Include {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(if self.hash { "#include" } else { "include" })?;
+        if self.if_exists {
+            f.write_str(" if exists")?;
+        }
+        write!(f, " {}", self.target)
+    }
+}
+
+impl Profile {
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        f.write_str(&indent)?;
+        if self.hat {
+            write!(f, "^{}", self.name)?;
+        } else if self.keyword {
+            write!(f, "profile {}", self.name)?;
+            if let Some(attachment) = &self.attachment {
+                write!(f, " {}", attachment)?;
+            }
+        } else if let Some(attachment) = &self.attachment {
+            f.write_str(attachment)?;
+        }
+        for (key, value) in &self.conditionals {
+            write!(f, " {}={}", key, value)?;
+        }
+        if !self.flags.is_empty() {
+            write!(f, " flags=({})", self.flags.join(","))?;
+        }
+        f.write_str(" {\n")?;
+        fmt_items(f, &self.body, depth + 1)?;
+        writeln!(f, "{}}}", indent)
+    }
+}
+
+fn fmt_items(f: &mut fmt::Formatter, items: &[Item], depth: usize) -> fmt::Result {
+    let indent = "  ".repeat(depth);
+    for item in items {
+        match &item.kind {
+            ItemKind::Include(include) => writeln!(f, "{}{}", indent, include)?,
+            ItemKind::Variable(var) => {
+                let op = if var.append { "+=" } else { "=" };
+                writeln!(f, "{}{} {} {}", indent, var.name, op, var.values.join(" "))?
+            }
+            ItemKind::Abi(target) => writeln!(f, "{}abi {},", indent, target)?,
+            ItemKind::Alias { from, to } => writeln!(f, "{}alias {} -> {},", indent, from, to)?,
+            ItemKind::Profile(profile) => profile.fmt_indented(f, depth)?,
+            ItemKind::Rule(rule) => writeln!(f, "{}{}", indent, rule)?,
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_items(f, &self.items, 0)
+    }
+}
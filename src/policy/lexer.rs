@@ -0,0 +1,149 @@
+//! Splits AppArmor policy source into tokens. Comments are dropped, except
+//! `#include`, which is a directive.
+
+use super::{ParseError, Span};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenKind {
+    /// Keywords, names, paths, permissions, numbers. Quotes are kept.
+    Word,
+    /// `<abstractions/base>`
+    Angle,
+    OpenBrace,
+    CloseBrace,
+    OpenParen,
+    CloseParen,
+    Comma,
+    Equals,
+    PlusEquals,
+    Arrow,
+    LessEquals,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub span: Span,
+    /// Whether a line break separates this token from the previous one.
+    /// Variable assignments end at the end of the line.
+    pub newline_before: bool,
+}
+
+pub fn tokenize(src: &str) -> Result<Vec<Token<'_>>, ParseError> {
+    let bytes = src.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    let mut newline_before = true;
+
+    while pos < bytes.len() {
+        let c = bytes[pos];
+        if c == b'\n' {
+            newline_before = true;
+            pos += 1;
+            continue;
+        }
+        if c.is_ascii_whitespace() {
+            pos += 1;
+            continue;
+        }
+        if c == b'#' && !is_hash_include(&src[pos..]) {
+            pos = src[pos..].find('\n').map_or(bytes.len(), |n| pos + n);
+            continue;
+        }
+
+        let start = pos;
+        let after_equals = tokens.last().is_some_and(|t: &Token| t.kind == TokenKind::Equals);
+        let kind = match c {
+            // `{a,b}` alternations may start a word, e.g. `member={Get,Set}`
+            // or `{,/usr}/bin/foo`.
+            b'{' if after_equals || matches!(bytes.get(pos + 1), Some(b'/' | b',')) => {
+                pos = word_end(src, pos)?;
+                TokenKind::Word
+            }
+            b'{' => single(&mut pos, TokenKind::OpenBrace),
+            b'}' => single(&mut pos, TokenKind::CloseBrace),
+            b'(' => single(&mut pos, TokenKind::OpenParen),
+            b')' => single(&mut pos, TokenKind::CloseParen),
+            b',' => single(&mut pos, TokenKind::Comma),
+            b'=' => single(&mut pos, TokenKind::Equals),
+            b'+' if bytes.get(pos + 1) == Some(&b'=') => {
+                pos += 2;
+                TokenKind::PlusEquals
+            }
+            b'-' if bytes.get(pos + 1) == Some(&b'>') => {
+                pos += 2;
+                TokenKind::Arrow
+            }
+            b'<' if bytes.get(pos + 1) == Some(&b'=') => {
+                pos += 2;
+                TokenKind::LessEquals
+            }
+            b'<' => {
+                let close = src[pos..]
+                    .find(['>', '\n'])
+                    .filter(|&n| bytes[pos + n] == b'>')
+                    .ok_or_else(|| ParseError::new("unterminated '<'", Span::new(pos, pos + 1)))?;
+                pos += close + 1;
+                TokenKind::Angle
+            }
+            _ => {
+                pos = word_end(src, pos)?;
+                TokenKind::Word
+            }
+        };
+
+        tokens.push(Token {
+            kind,
+            text: &src[start..pos],
+            span: Span::new(start, pos),
+            newline_before,
+        });
+        newline_before = false;
+    }
+    Ok(tokens)
+}
+
+/// `#include` followed by a space, `<` or `"`. Any other `#include...`,
+/// such as `#includes`, is a comment.
+pub fn is_hash_include(rest: &str) -> bool {
+    rest.strip_prefix("#include")
+        .is_some_and(|after| after.starts_with(|c: char| c.is_ascii_whitespace() || c == '<' || c == '"'))
+}
+
+fn single(pos: &mut usize, kind: TokenKind) -> TokenKind {
+    *pos += 1;
+    kind
+}
+
+/// Finds the end of a word starting at `start`. Paths may contain `{a,b}`
+/// alternations and `[...]` classes, so separators only end a word outside
+/// of those.
+fn word_end(src: &str, start: usize) -> Result<usize, ParseError> {
+    let bytes = src.as_bytes();
+    let mut pos = start;
+    let mut depth = 0usize;
+
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'"' => {
+                pos += 1;
+                while pos < bytes.len() && bytes[pos] != b'"' {
+                    pos += if bytes[pos] == b'\\' { 2 } else { 1 };
+                }
+                if pos >= bytes.len() {
+                    return Err(ParseError::new("unterminated string", Span::new(start, bytes.len())));
+                }
+            }
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' if depth > 0 => depth -= 1,
+            c if c.is_ascii_whitespace() => break,
+            b',' | b'(' | b')' | b'=' | b'}' if depth == 0 => break,
+            b'+' | b'<' if depth == 0 && bytes.get(pos + 1) == Some(&b'=') => break,
+            b'-' if depth == 0 && pos > start && bytes.get(pos + 1) == Some(&b'>') => break,
+            _ => {}
+        }
+        pos += 1;
+    }
+    Ok(pos)
+}
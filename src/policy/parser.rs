@@ -0,0 +1,614 @@
+use super::ast::*;
+use super::lexer::{tokenize, Token, TokenKind};
+use super::{ParseError, Span};
+
+const QUALIFIERS: &[&str] = &["audit", "deny", "allow", "owner", "other"];
+
+/// Characters of file permissions, including exec modes like `Px` or `cix`.
+const PERM_CHARS: &str = "rwaxmlkdiupcCUPIb";
+
+pub fn parse(src: &str) -> Result<Policy, ParseError> {
+    let tokens = tokenize(src)?;
+    let mut parser = Parser { src, tokens, pos: 0 };
+    let items = parser.items(false)?;
+    Ok(Policy { items })
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+
+fn is_path(word: &str) -> bool {
+    word.starts_with(['/', '@', '"', '{'])
+}
+
+fn is_perms(word: &str) -> bool {
+    !word.is_empty() && word.chars().all(|c| PERM_CHARS.contains(c))
+}
+
+/// Splits `(a, b c)` into its words.
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split([',', ' ', '\t', '\n'])
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn peek_word(&self) -> Option<&'a str> {
+        self.peek().filter(|t| t.kind == TokenKind::Word).map(|t| t.text)
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, ParseError> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| self.eof())?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eof(&self) -> ParseError {
+        ParseError::new("unexpected end of file", Span::new(self.src.len(), self.src.len()))
+    }
+
+    fn expect(&mut self, kind: TokenKind, what: &str) -> Result<Token<'a>, ParseError> {
+        let token = self.next()?;
+        if token.kind != kind {
+            return Err(ParseError::new(format!("expected {}, found '{}'", what, token.text), token.span));
+        }
+        Ok(token)
+    }
+
+    fn word(&mut self, what: &str) -> Result<&'a str, ParseError> {
+        Ok(self.expect(TokenKind::Word, what)?.text)
+    }
+
+    fn last_end(&self) -> usize {
+        self.tokens[self.pos - 1].span.end
+    }
+
+    fn items(&mut self, in_block: bool) -> Result<Vec<Item>, ParseError> {
+        let mut items = Vec::new();
+        loop {
+            match self.peek() {
+                None if in_block => return Err(ParseError::new("missing '}'", Span::new(self.src.len(), self.src.len()))),
+                None => return Ok(items),
+                Some(t) if t.kind == TokenKind::CloseBrace => {
+                    if in_block {
+                        return Ok(items);
+                    }
+                    return Err(ParseError::new("unexpected '}'", t.span));
+                }
+                Some(t) => {
+                    let start = t.span.start;
+                    let kind = self.item()?;
+                    items.push(Item { kind, span: Span::new(start, self.last_end()) });
+                }
+            }
+        }
+    }
+
+    fn item(&mut self) -> Result<ItemKind, ParseError> {
+        let token = self.peek().cloned().ok_or_else(|| self.eof())?;
+        if token.kind != TokenKind::Word {
+            return Err(ParseError::new(format!("unexpected '{}'", token.text), token.span));
+        }
+        let next_kind = self.peek_at(1).map(|t| t.kind);
+
+        match token.text {
+            "#include" | "include" => self.include(),
+            "abi" => {
+                self.pos += 1;
+                let target = self.next()?;
+                self.expect(TokenKind::Comma, "','")?;
+                Ok(ItemKind::Abi(target.text.to_string()))
+            }
+            "alias" => {
+                self.pos += 1;
+                let from = self.word("alias source")?.to_string();
+                self.expect(TokenKind::Arrow, "'->'")?;
+                let to = self.word("alias target")?.to_string();
+                self.expect(TokenKind::Comma, "','")?;
+                Ok(ItemKind::Alias { from, to })
+            }
+            var if var.starts_with("@{") && matches!(next_kind, Some(TokenKind::Equals | TokenKind::PlusEquals)) => {
+                self.variable()
+            }
+            "profile" | "hat" => self.profile(),
+            hat if hat.starts_with('^') => self.profile(),
+            path if is_path(path) && self.header_ahead() => self.profile(),
+            _ => Ok(ItemKind::Rule(self.rule()?)),
+        }
+    }
+
+    /// Whether the tokens ahead open a block before a rule would end.
+    fn header_ahead(&self) -> bool {
+        let mut depth = 0;
+        for token in &self.tokens[self.pos..] {
+            match token.kind {
+                TokenKind::OpenParen => depth += 1,
+                TokenKind::CloseParen => depth -= 1,
+                TokenKind::Comma if depth == 0 => return false,
+                TokenKind::OpenBrace => return true,
+                TokenKind::CloseBrace => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    fn include(&mut self) -> Result<ItemKind, ParseError> {
+        let hash = self.next()?.text == "#include";
+        let mut if_exists = false;
+        if self.peek_word() == Some("if") && self.peek_at(1).is_some_and(|t| t.text == "exists") {
+            self.pos += 2;
+            if_exists = true;
+        }
+        let target = self.next()?;
+        if !matches!(target.kind, TokenKind::Angle | TokenKind::Word) {
+            return Err(ParseError::new("expected include path", target.span));
+        }
+        Ok(ItemKind::Include(Include { target: target.text.to_string(), hash, if_exists }))
+    }
+
+    fn variable(&mut self) -> Result<ItemKind, ParseError> {
+        let name = self.next()?.text.to_string();
+        let append = self.next()?.kind == TokenKind::PlusEquals;
+        let mut values = Vec::new();
+        while let Some(t) = self.peek().filter(|t| !t.newline_before && t.kind == TokenKind::Word) {
+            values.push(t.text.to_string());
+            self.pos += 1;
+        }
+        Ok(ItemKind::Variable(Variable { name, append, values }))
+    }
+
+    fn profile(&mut self) -> Result<ItemKind, ParseError> {
+        let start = self.peek().map(|t| t.span.start).unwrap_or_default();
+        let first = self.word("profile")?;
+        let mut profile = Profile {
+            name: String::new(),
+            attachment: None,
+            keyword: false,
+            hat: false,
+            flags: Vec::new(),
+            conditionals: Vec::new(),
+            body: Vec::new(),
+            header: Span::default(),
+            flags_span: None,
+        };
+
+        match first {
+            "profile" | "hat" => {
+                profile.keyword = first == "profile";
+                profile.hat = first == "hat";
+                profile.name = self.word("profile name")?.to_string();
+                if profile.keyword
+                    && let Some(attachment) = self.peek_word().filter(|w| is_path(w))
+                    && self.peek_at(1).is_none_or(|t| t.kind != TokenKind::Equals)
+                {
+                    profile.attachment = Some(attachment.to_string());
+                    self.pos += 1;
+                }
+            }
+            hat if hat.starts_with('^') => {
+                profile.hat = true;
+                profile.name = hat[1..].to_string();
+            }
+            path => profile.attachment = Some(path.to_string()),
+        }
+
+        // Old-style `/path (complain) {` flags without `flags=`.
+        if self.peek().is_some_and(|t| t.kind == TokenKind::OpenParen) {
+            let start = self.peek().map(|t| t.span.start).unwrap_or_default();
+            let value = self.conditional_value()?;
+            profile.flags = split_list(&value);
+            profile.flags_span = Some(Span::new(start, self.last_end()));
+        }
+
+        while let Some(key) = self.peek_word() {
+            let key_span = self.peek().map(|t| t.span).unwrap_or_default();
+            self.pos += 1;
+            self.expect(TokenKind::Equals, "'='")?;
+            let value = self.conditional_value()?;
+            if key == "flags" {
+                profile.flags = split_list(&value);
+                profile.flags_span = Some(Span::new(key_span.start, self.last_end()));
+            } else {
+                profile.conditionals.push((key.to_string(), value));
+            }
+        }
+
+        self.expect(TokenKind::OpenBrace, "'{'")?;
+        profile.header = Span::new(start, self.last_end());
+        profile.body = self.items(true)?;
+        self.expect(TokenKind::CloseBrace, "'}'")?;
+        Ok(ItemKind::Profile(profile))
+    }
+
+    /// A conditional value: one word, or a parenthesised list kept as written.
+    fn conditional_value(&mut self) -> Result<String, ParseError> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Word | TokenKind::Angle => Ok(token.text.to_string()),
+            TokenKind::OpenParen => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match self.next()?.kind {
+                        TokenKind::OpenParen => depth += 1,
+                        TokenKind::CloseParen => depth -= 1,
+                        _ => {}
+                    }
+                }
+                Ok(self.src[token.span.start..self.last_end()].to_string())
+            }
+            _ => Err(ParseError::new(format!("unexpected '{}'", token.text), token.span)),
+        }
+    }
+
+    fn rule(&mut self) -> Result<Rule, ParseError> {
+        let mut qualifiers = Qualifiers::default();
+        while let Some(word) = self.peek_word().filter(|w| QUALIFIERS.contains(w)) {
+            // `owner` etc. may also start a path-less `file` rule, so only
+            // treat them as qualifiers when something follows.
+            if self.peek_at(1).is_none_or(|t| t.kind == TokenKind::Comma) {
+                break;
+            }
+            match word {
+                "audit" => qualifiers.audit = true,
+                "deny" => qualifiers.deny = true,
+                "allow" => qualifiers.allow = true,
+                "owner" => qualifiers.owner = true,
+                _ => qualifiers.other = true,
+            }
+            self.pos += 1;
+        }
+
+        let first = self.next()?;
+        if first.kind != TokenKind::Word {
+            return Err(ParseError::new(format!("unexpected '{}'", first.text), first.span));
+        }
+
+        let kind = match first.text {
+            "capability" => RuleKind::Capability(self.words_until_comma()?),
+            "network" => RuleKind::Network(self.words_until_comma()?),
+            "signal" => RuleKind::Signal(self.conditional()?),
+            "ptrace" => RuleKind::Ptrace(self.conditional()?),
+            "dbus" => RuleKind::Dbus(self.conditional()?),
+            "unix" => RuleKind::Unix(self.conditional()?),
+            "mount" | "remount" | "umount" | "pivot_root" => RuleKind::Mount {
+                keyword: first.text.to_string(),
+                rule: self.conditional()?,
+            },
+            "set" => {
+                if self.word("rlimit")? != "rlimit" {
+                    return Err(ParseError::new("expected 'rlimit' after 'set'", first.span));
+                }
+                let resource = self.word("rlimit resource")?.to_string();
+                self.expect(TokenKind::LessEquals, "'<='")?;
+                let value = self.word("rlimit value")?.to_string();
+                self.expect(TokenKind::Comma, "','")?;
+                RuleKind::Rlimit { resource, value }
+            }
+            "link" => {
+                let subset = self.peek_word() == Some("subset");
+                if subset {
+                    self.pos += 1;
+                }
+                let from = self.word("link source")?.to_string();
+                self.expect(TokenKind::Arrow, "'->'")?;
+                let to = self.word("link target")?.to_string();
+                self.expect(TokenKind::Comma, "','")?;
+                RuleKind::Link { subset, from, to }
+            }
+            "file" => {
+                if self.peek().is_some_and(|t| t.kind == TokenKind::Comma) {
+                    self.pos += 1;
+                    RuleKind::File(FileRule { path: None, perms: String::new(), target: None, keyword: true })
+                } else {
+                    let word = self.word("path")?;
+                    let mut rule = self.file_rule(word)?;
+                    rule.keyword = true;
+                    RuleKind::File(rule)
+                }
+            }
+            word if is_path(word) || is_perms(word) => RuleKind::File(self.file_rule(word)?),
+            keyword => RuleKind::Other {
+                keyword: keyword.to_string(),
+                rule: self.conditional()?,
+            },
+        };
+        Ok(Rule { qualifiers, kind })
+    }
+
+    /// `path perms [-> target],` or `perms path [-> target],`; `first` has
+    /// already been consumed.
+    fn file_rule(&mut self, first: &'a str) -> Result<FileRule, ParseError> {
+        let second = self.word("file permissions")?;
+        let (path, perms) = if is_path(first) { (first, second) } else { (second, first) };
+        if !is_perms(perms) {
+            let span = self.tokens[self.pos - 1].span;
+            return Err(ParseError::new(format!("invalid file permissions '{}'", perms), span));
+        }
+        let mut target = None;
+        if self.peek().is_some_and(|t| t.kind == TokenKind::Arrow) {
+            self.pos += 1;
+            target = Some(self.word("exec target")?.to_string());
+        }
+        self.expect(TokenKind::Comma, "','")?;
+        Ok(FileRule { path: Some(path.to_string()), perms: perms.to_string(), target, keyword: false })
+    }
+
+    fn words_until_comma(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut words = Vec::new();
+        loop {
+            let token = self.next()?;
+            match token.kind {
+                TokenKind::Comma => return Ok(words),
+                TokenKind::Word => words.push(token.text.to_string()),
+                _ => return Err(ParseError::new(format!("unexpected '{}'", token.text), token.span)),
+            }
+        }
+    }
+
+    /// `[access | (access, ...)] [key=value ...] [words ...],`
+    fn conditional(&mut self) -> Result<Conditional, ParseError> {
+        let mut rule = Conditional::default();
+        if self.peek().is_some_and(|t| t.kind == TokenKind::OpenParen) {
+            rule.access = split_list(&self.conditional_value()?);
+        }
+
+        loop {
+            let token = self.next()?;
+            match token.kind {
+                TokenKind::Comma => return Ok(rule),
+                TokenKind::Word if self.peek().is_some_and(|t| t.kind == TokenKind::Equals) => {
+                    self.pos += 1;
+                    let value = self.conditional_value()?;
+                    rule.conditionals.push((token.text.to_string(), value));
+                }
+                // `options in (ro, remount)`
+                TokenKind::Word
+                    if self.peek().is_some_and(|t| t.text == "in")
+                        && self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::OpenParen) =>
+                {
+                    self.pos += 1;
+                    let value = self.conditional_value()?;
+                    rule.conditionals.push((format!("{} in", token.text), value));
+                }
+                TokenKind::Word if rule.access.is_empty() && rule.conditionals.is_empty() && rule.rest.is_empty()
+                    && !is_path(token.text) =>
+                {
+                    rule.access.push(token.text.to_string())
+                }
+                TokenKind::Word | TokenKind::Arrow => rule.rest.push(token.text.to_string()),
+                _ => return Err(ParseError::new(format!("unexpected '{}'", token.text), token.span)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &[(&str, &str)] = &[
+        ("usr.sbin.cups-browsed", include_str!("../../testdata/profiles/usr.sbin.cups-browsed")),
+        ("firefox", include_str!("../../testdata/profiles/firefox")),
+        ("usr.bin.man", include_str!("../../testdata/profiles/usr.bin.man")),
+    ];
+
+    fn slice(src: &str, span: Span) -> &str {
+        &src[span.start..span.end]
+    }
+
+    /// Rule types the shipped fixtures don't use, in a profile with hats.
+    const RULES: &str = r#"
+@{HOME}=/home/*/
+@{HOME}+=/root/
+@{exec_path} = /opt/firefox/firefox{,.sh,-bin}
+
+profile snap.firefox.firefox {
+  include <abstractions/base>
+  #include "/etc/apparmor.d/abstractions/fonts"
+  #includes are not comments unless followed by a space
+
+  audit deny @{HOME}/.ssh/** mrwkl,
+  owner @{HOME}/.mozilla/** rwk,
+  r /etc/machine-id,
+  file,
+  deny owner /etc/passwd w,
+  link subset /tmp/foo -> /tmp/bar,
+  capability,
+  network unix stream,
+  ptrace (read, trace) peer=@{profile_name},
+  signal send set=(kill) peer=snap.firefox.*,
+  unix (send, receive) type=stream addr=@/tmp/.X11-unix/X[0-9]*,
+  mount fstype=tmpfs options=(rw, nosuid) tmpfs -> /tmp/,
+  mount options in (ro, remount) -> /mnt/,
+  mount fstype in (ext4, xfs),
+  umount /tmp/,
+  change_profile -> snap.firefox.hook,
+  set rlimit nofile <= 4096,
+  "/opt/firefox dir/firefox" ix,
+  /usr/bin/xdg-open Px -> xdg_open,
+
+  ^prefetch {
+    /var/cache/firefox/** rw,
+  }
+
+  hat cleanup flags=(complain) {
+    capability dac_override,
+  }
+}
+"#;
+
+    fn render(items: &[Item]) -> String {
+        Policy { items: items.to_vec() }.to_string()
+    }
+
+    /// `items` with every span zeroed, for comparing trees parsed from
+    /// different text.
+    fn without_spans(items: &[Item]) -> Vec<Item> {
+        items
+            .iter()
+            .map(|item| {
+                let kind = match &item.kind {
+                    ItemKind::Profile(profile) => ItemKind::Profile(Profile {
+                        body: without_spans(&profile.body),
+                        header: Span::default(),
+                        flags_span: profile.flags_span.map(|_| Span::default()),
+                        ..profile.clone()
+                    }),
+                    kind => kind.clone(),
+                };
+                Item { kind, span: Span::default() }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn printed_policy_reparses_identically() {
+        for (name, src) in FIXTURES.iter().chain([&("rules", RULES)]) {
+            let policy = parse(src).unwrap_or_else(|e| panic!("{}: {} at line {}", name, e, e.span.line(src)));
+            let printed = policy.to_string();
+            let reparsed = parse(&printed).unwrap_or_else(|e| panic!("{}: reprint: {}\n{}", name, e, printed));
+            assert_eq!(without_spans(&reparsed.items), without_spans(&policy.items), "{}:\n{}", name, printed);
+        }
+    }
+
+    #[test]
+    fn spans_cover_their_nodes() {
+        fn check(src: &str, items: &[Item]) {
+            for item in items {
+                let text = slice(src, item.span);
+                let alone = parse(text).unwrap_or_else(|e| panic!("{:?}: {}", text, e));
+                assert_eq!(render(&alone.items), render(std::slice::from_ref(item)), "{:?}", text);
+                if let ItemKind::Profile(profile) = &item.kind {
+                    assert!(slice(src, profile.header).ends_with('{'));
+                    check(src, &profile.body);
+                }
+            }
+        }
+        for (_, src) in FIXTURES {
+            check(src, &parse(src).unwrap().items);
+        }
+    }
+
+    #[test]
+    fn parses_profile_headers_and_children() {
+        let src = FIXTURES[0].1;
+        let policy = parse(src).unwrap();
+        let names: Vec<String> = policy.all_profiles().into_iter().map(|(name, _, _)| name).collect();
+        assert_eq!(names, ["/usr/sbin/cups-browsed", "/usr/sbin/cups-browsed//backend"]);
+
+        let (profile, span) = policy.profiles().next().unwrap();
+        assert_eq!(profile.attachment.as_deref(), Some("/usr/sbin/cups-browsed"));
+        assert_eq!(profile.flags, ["attach_disconnected"]);
+        assert_eq!(slice(src, profile.flags_span.unwrap()), "flags=(attach_disconnected)");
+        assert_eq!(span.line(src), 8);
+        let includes: Vec<&str> = profile.includes().map(Include::path).collect();
+        assert!(includes.contains(&"abstractions/nameservice"));
+        assert!(includes.contains(&"local/usr.sbin.cups-browsed"));
+
+        let (backend, _) = profile.children().next().unwrap();
+        assert_eq!(backend.flags, ["complain"]);
+        assert!(backend.keyword && !backend.hat);
+    }
+
+    #[test]
+    fn parses_shipped_profiles() {
+        let policy = parse(FIXTURES[1].1).unwrap();
+        let Some(ItemKind::Variable(var)) = policy.items.iter().map(|item| &item.kind).find(|kind| matches!(kind, ItemKind::Variable(_))) else {
+            panic!("no variable");
+        };
+        assert_eq!(var.name, "@{firefox_exec}");
+        let (firefox, _) = policy.profiles().next().unwrap();
+        assert_eq!(firefox.attachment.as_deref(), Some("@{firefox_exec}"));
+        assert_eq!(firefox.flags, ["unconfined"]);
+        assert!(firefox.includes().next().unwrap().if_exists);
+        assert_eq!(firefox.rules().map(Rule::to_string).collect::<Vec<_>>(), ["userns,"]);
+
+        let policy = parse(FIXTURES[2].1).unwrap();
+        let names: Vec<String> = policy.all_profiles().into_iter().map(|(name, _, _)| name).collect();
+        assert_eq!(names, ["man", "man_groff", "man_filter"]);
+        let (man, _) = policy.profiles().next().unwrap();
+        let rules: Vec<String> = man.rules().map(Rule::to_string).collect();
+        assert!(rules.contains(&"/usr/bin/eqn rmCx -> &man_groff,".to_string()));
+        assert!(rules.contains(&"deny capability dac_override,".to_string()));
+        assert!(rules.contains(&"signal peer=@{profile_name},".to_string()));
+        assert!(rules.contains(&"unix,".to_string()));
+    }
+
+    #[test]
+    fn parses_rule_types() {
+        let policy = parse(RULES).unwrap();
+        let vars: Vec<&Variable> = policy
+            .items
+            .iter()
+            .filter_map(|item| match &item.kind {
+                ItemKind::Variable(var) => Some(var),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vars.len(), 3);
+        assert!(vars[1].append);
+        assert_eq!(vars[2].values, ["/opt/firefox/firefox{,.sh,-bin}"]);
+
+        let all = policy.all_profiles();
+        let names: Vec<&str> = all.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, ["snap.firefox.firefox", "snap.firefox.firefox//prefetch", "snap.firefox.firefox//cleanup"]);
+        assert!(all[1].1.hat && all[2].1.hat);
+
+        let snap = all[0].1;
+        let includes: Vec<(&str, bool)> = snap.includes().map(|include| (include.path(), include.hash)).collect();
+        assert_eq!(includes, [("abstractions/base", false), ("/etc/apparmor.d/abstractions/fonts", true)]);
+
+        let types: Vec<&str> = snap.rules().map(|rule| rule.kind.type_name()).collect();
+        for expected in ["file", "link", "capability", "network", "ptrace", "signal", "unix", "mount", "rlimit", "change_profile"] {
+            assert!(types.contains(&expected), "missing {}", expected);
+        }
+
+        let rules: Vec<String> = snap.rules().map(Rule::to_string).collect();
+        assert!(rules.contains(&"audit deny @{HOME}/.ssh/** mrwkl,".to_string()));
+        assert!(rules.contains(&"/etc/machine-id r,".to_string()));
+        assert!(rules.contains(&"deny owner /etc/passwd w,".to_string()));
+        assert!(rules.contains(&"ptrace (read, trace) peer=@{profile_name},".to_string()));
+        assert!(rules.contains(&"set rlimit nofile <= 4096,".to_string()));
+        assert!(rules.contains(&"mount options in (ro, remount) -> /mnt/,".to_string()));
+        assert!(rules.contains(&"mount fstype in (ext4, xfs),".to_string()));
+        assert!(rules.contains(&"/usr/bin/xdg-open Px -> xdg_open,".to_string()));
+        assert!(rules.contains(&"\"/opt/firefox dir/firefox\" ix,".to_string()));
+    }
+
+    #[test]
+    fn old_style_flags_and_multiple_rules_per_line() {
+        let policy = parse("/usr/bin/man (complain) {\n  capability setuid, capability setgid,\n}\n").unwrap();
+        let (profile, _) = policy.profiles().next().unwrap();
+        assert_eq!(profile.flags, ["complain"]);
+        assert_eq!(profile.rules().filter(|r| r.kind.type_name() == "capability").count(), 2);
+    }
+
+    #[test]
+    fn reports_errors_with_position() {
+        let src = "/usr/bin/foo {\n  /etc/foo rw\n}\n";
+        let err = parse(src).unwrap_err();
+        assert_eq!(err.span.line(src), 3);
+
+        let err = parse("profile foo {\n  capability,\n").unwrap_err();
+        assert!(err.message.contains("missing '}'"));
+
+        let err = parse("/usr/bin/foo {\n  /etc/foo zz,\n}").unwrap_err();
+        assert!(err.message.contains("invalid file permissions"));
+    }
+}
@@ -0,0 +1,242 @@
+//! Running commands as root through sudo, pkexec or doas.
+
+use crate::tui;
+use anyhow::{anyhow, Context, Result};
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Output, Stdio};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Escalation {
+    Sudo,
+    Pkexec,
+    Doas,
+    /// Already root, run commands directly.
+    None,
+}
+
+impl Escalation {
+    pub fn parse(name: &str) -> Option<Escalation> {
+        match name {
+            "sudo" => Some(Escalation::Sudo),
+            "pkexec" => Some(Escalation::Pkexec),
+            "doas" => Some(Escalation::Doas),
+            "none" => Some(Escalation::None),
+            _ => None,
+        }
+    }
+
+    /// Uses the configured method, or skips escalation when running as root.
+    pub fn detect(configured: Option<Escalation>) -> Escalation {
+        match configured {
+            Some(escalation) => escalation,
+            None if effective_uid() == Some(0) => Escalation::None,
+            None => Escalation::Sudo,
+        }
+    }
+
+    fn command<S: AsRef<OsStr>>(self, program: &str, args: &[S]) -> Command {
+        let mut cmd = match self {
+            Escalation::Sudo => Command::new("sudo"),
+            Escalation::Pkexec => Command::new("pkexec"),
+            Escalation::Doas => Command::new("doas"),
+            Escalation::None => return command_with_args(program, args),
+        };
+        cmd.arg(program).args(args);
+        cmd
+    }
+}
+
+fn command_with_args<S: AsRef<OsStr>>(program: &str, args: &[S]) -> Command {
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    cmd
+}
+
+/// Reads the effective UID from `/proc/self/status`.
+pub fn effective_uid() -> Option<u32> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("Uid:"))?;
+    line.split_whitespace().nth(2)?.parse().ok()
+}
+
+/// Printed with the exit status after each item of [`Privileged::run_each`].
+const STATUS_MARKER: &str = "@@apparmor-tui-status ";
+
+/// The `sh -c` script behind [`Privileged::run_each`]. The command is
+/// quoted into the script; the items arrive as positional parameters.
+fn each_script(program: &str, args: &[&str]) -> String {
+    let command: Vec<String> = std::iter::once(program).chain(args.iter().copied()).map(shell_quote).collect();
+    format!("for item do\n  {} \"$item\" 2>&1\n  printf '\\n{}%d\\n' $?\ndone", command.join(" "), STATUS_MARKER)
+}
+
+/// `word` in single quotes, for `sh`.
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// Splits the output of [`each_script`] into per-item results.
+fn parse_each(stdout: &str) -> Vec<(bool, String)> {
+    let mut results = Vec::new();
+    let mut text = String::new();
+    for line in stdout.lines() {
+        if let Some(code) = line.strip_prefix(STATUS_MARKER) {
+            results.push((code.trim() == "0", text.trim().to_string()));
+            text.clear();
+        } else {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+    results
+}
+
+pub struct Privileged {
+    pub escalation: Escalation,
+}
+
+impl Privileged {
+    pub fn new(escalation: Escalation) -> Privileged {
+        Privileged { escalation }
+    }
+
+    /// Runs `program` as root. The TUI is suspended while the command runs
+    /// unless we are already root, so a password prompt gets a usable terminal.
+    pub fn run<S: AsRef<OsStr>>(&self, program: &str, args: &[S]) -> Result<ExitStatus> {
+        self.spawn(program, args, self.escalation != Escalation::None)
+    }
+
+    fn spawn<S: AsRef<OsStr>>(&self, program: &str, args: &[S], suspend: bool) -> Result<ExitStatus> {
+        let mut cmd = self.escalation.command(program, args);
+        let status = if suspend { tui::suspended(|| cmd.status())? } else { cmd.status() };
+        status.with_context(|| format!("Failed to execute {}", self.describe(program)))
+    }
+
+    /// Runs `program` as root and captures its stdout and stderr. A password
+    /// prompt still goes to the terminal.
+    pub fn output<S: AsRef<OsStr>>(&self, program: &str, args: &[S]) -> Result<Output> {
+        let mut cmd = self.escalation.command(program, args);
+        let output = if self.escalation == Escalation::None { cmd.output() } else { tui::suspended(|| cmd.output())? };
+        output.with_context(|| format!("Failed to execute {}", self.describe(program)))
+    }
+
+    /// Runs `program ARGS ITEM` for each item inside one escalated shell, so
+    /// a batch costs a single password prompt. Returns whether each item
+    /// succeeded and its output.
+    pub fn run_each<S: AsRef<OsStr>>(&self, program: &str, args: &[&str], items: &[S]) -> Result<Vec<(bool, String)>> {
+        let script = each_script(program, args);
+        let mut sh_args: Vec<&OsStr> = vec!["-c".as_ref(), script.as_ref(), "sh".as_ref()];
+        sh_args.extend(items.iter().map(AsRef::as_ref));
+        let output = self.output("sh", &sh_args)?;
+
+        let results = parse_each(&String::from_utf8_lossy(&output.stdout));
+        if results.len() != items.len() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!(
+                "{} stopped after {} of {} items: {}",
+                self.describe(program),
+                results.len(),
+                items.len(),
+                stderr.trim()
+            ));
+        }
+        Ok(results)
+    }
+
+    /// Like [`Privileged::run`], but fails unless the command exits successfully.
+    pub fn run_checked<S: AsRef<OsStr>>(&self, program: &str, args: &[S]) -> Result<()> {
+        let status = self.run(program, args)?;
+        if !status.success() {
+            return Err(anyhow!("Command failed: {} ({})", self.describe(program), status));
+        }
+        Ok(())
+    }
+
+    /// Replaces the contents of a root-owned file through `tee`.
+    pub fn write_file(&self, path: &Path, content: &str) -> Result<()> {
+        let mut cmd = self.escalation.command("tee", &[path]);
+        cmd.stdin(Stdio::piped()).stdout(Stdio::null());
+        let mut write = || -> std::io::Result<ExitStatus> {
+            let mut child = cmd.spawn()?;
+            let mut stdin = child.stdin.take().expect("stdin is piped");
+            let written = stdin.write_all(content.as_bytes());
+            drop(stdin);
+            let status = child.wait()?;
+            written.map(|_| status)
+        };
+        let status = if self.escalation == Escalation::None { write() } else { tui::suspended(write)? };
+        let status = status.with_context(|| format!("Failed to write {}", path.display()))?;
+        if !status.success() {
+            return Err(anyhow!("Failed to write {}: {} exited with {}", path.display(), self.describe("tee"), status));
+        }
+        Ok(())
+    }
+
+    fn describe(&self, program: &str) -> String {
+        match self.escalation {
+            Escalation::Sudo => format!("sudo {}", program),
+            Escalation::Pkexec => format!("pkexec {}", program),
+            Escalation::Doas => format!("doas {}", program),
+            Escalation::None => program.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_escalation_names() {
+        assert_eq!(Escalation::parse("sudo"), Some(Escalation::Sudo));
+        assert_eq!(Escalation::parse("pkexec"), Some(Escalation::Pkexec));
+        assert_eq!(Escalation::parse("doas"), Some(Escalation::Doas));
+        assert_eq!(Escalation::parse("none"), Some(Escalation::None));
+        assert_eq!(Escalation::parse("su"), None);
+        assert_eq!(Escalation::parse("auto"), None);
+    }
+
+    #[test]
+    fn configured_escalation_wins() {
+        assert_eq!(Escalation::detect(Some(Escalation::Doas)), Escalation::Doas);
+        assert_eq!(Escalation::detect(Some(Escalation::None)), Escalation::None);
+        let expected = if effective_uid() == Some(0) { Escalation::None } else { Escalation::Sudo };
+        assert_eq!(Escalation::detect(None), expected);
+    }
+
+    #[test]
+    fn splits_output_on_status_markers() {
+        let stdout = format!("ok\n\n{m}0\n\nline one\nline two\n{m}1\n\n{m}0\n", m = STATUS_MARKER);
+        assert_eq!(
+            parse_each(&stdout),
+            vec![(true, "ok".to_string()), (false, "line one\nline two".to_string()), (true, String::new())]
+        );
+        assert_eq!(parse_each("output cut short\n"), vec![]);
+    }
+
+    #[test]
+    fn quotes_the_command_into_the_script() {
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        let script = each_script("apparmor_parser", &["-r", "$(reboot)"]);
+        assert!(script.contains(r#"'apparmor_parser' '-r' '$(reboot)' "$item""#), "{}", script);
+    }
+
+    #[test]
+    fn runs_each_item_without_shell_expansion() {
+        let privileged = Privileged::new(Escalation::None);
+        let check = r#"printf '%s|%s\n' "$1" "$0"; [ "$1" != fail ]"#;
+        let items = ["a b; echo injected", "$(echo expanded)", "it's", "fail"];
+        let results = privileged.run_each("sh", &["-c", check, "it's `quoted`"], &items).unwrap();
+        assert_eq!(
+            results,
+            vec![
+                (true, "a b; echo injected|it's `quoted`".to_string()),
+                (true, "$(echo expanded)|it's `quoted`".to_string()),
+                (true, "it's|it's `quoted`".to_string()),
+                (false, "fail|it's `quoted`".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Which processes run under which profile, read from `/proc`.
+
+use crate::profile::{Process, Profile};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// A process and the AppArmor label it is confined by. `exe`, `unit` and
+/// `sockets` are only looked up by [`inspect_unconfined`], and need root for
+/// other users' processes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    /// Short command name from `/proc/<pid>/comm`.
+    pub command: String,
+    pub user: String,
+    /// Label without the mode, e.g. `firefox` or `unconfined`.
+    pub label: String,
+    /// Mode the label is in, or "unconfined" for labels without one.
+    pub mode: String,
+    /// Executable path, from `exe` or else `cmdline`.
+    pub exe: Option<String>,
+    /// systemd unit from the cgroup path, e.g. `cups.service`.
+    pub unit: Option<String>,
+    /// Inodes of the sockets the process holds open.
+    pub sockets: Vec<u64>,
+    /// Whether the open files could be listed.
+    pub inspected: bool,
+}
+
+impl ProcessInfo {
+    pub fn is_confined(&self) -> bool {
+        self.mode != "unconfined"
+    }
+
+    /// The loaded profile this process runs under: the label itself, or
+    /// for a hat or stacked label the profile it starts with.
+    pub fn profile<'a>(&self, profiles: &'a [Profile]) -> Option<&'a Profile> {
+        let exact = profiles.iter().find(|p| p.name == self.label);
+        let top = self.label.split("//").next().unwrap_or_default();
+        exact.or_else(|| profiles.iter().find(|p| p.name == top))
+    }
+}
+
+/// Lists every process under `proc_root` whose label can be read, by PID.
+/// This runs on every refresh, so it reads no more than the label and
+/// owner.
+pub fn scan(proc_root: &Path) -> Result<Vec<ProcessInfo>> {
+    let entries = fs::read_dir(proc_root).with_context(|| format!("Failed to read {}", proc_root.display()))?;
+    let users = fs::read_to_string("/etc/passwd").map(|passwd| parse_passwd(&passwd)).unwrap_or_default();
+    let mut processes = Vec::new();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse().ok()) else {
+            continue;
+        };
+        // Processes exit while we look; skip the ones that are gone.
+        let dir = entry.path();
+        let Some(current) = read_label(&dir) else { continue };
+        let (label, mode) = parse_label(&current);
+        let command = fs::read_to_string(dir.join("comm")).unwrap_or_default().trim_end().to_string();
+        let uid = fs::read_to_string(dir.join("status")).ok().and_then(|status| status_uid(&status));
+        let user = match uid {
+            Some(uid) => users.get(&uid).cloned().unwrap_or_else(|| uid.to_string()),
+            None => String::new(),
+        };
+        processes.push(ProcessInfo { pid, command, user, label, mode, ..ProcessInfo::default() });
+    }
+    processes.sort_by_key(|p| p.pid);
+    Ok(processes)
+}
+
+/// Fills in the executable, unit and sockets of the unconfined processes,
+/// for the report of what should be confined.
+pub fn inspect_unconfined(proc_root: &Path, processes: &mut [ProcessInfo]) {
+    for process in processes.iter_mut().filter(|p| !p.is_confined()) {
+        inspect(&proc_root.join(process.pid.to_string()), process);
+    }
+}
+
+/// Fills in the executable, unit and sockets of one process.
+fn inspect(dir: &Path, process: &mut ProcessInfo) {
+    process.exe = fs::read_link(dir.join("exe")).ok().map(|exe| exe.to_string_lossy().into_owned()).or_else(|| {
+        // Kernel threads have an empty cmdline.
+        let cmdline = fs::read(dir.join("cmdline")).ok()?;
+        let argv0 = cmdline.split(|&b| b == 0).next()?;
+        argv0.starts_with(b"/").then(|| String::from_utf8_lossy(argv0).into_owned())
+    });
+    process.unit = fs::read_to_string(dir.join("cgroup")).ok().and_then(|cgroup| cgroup_unit(&cgroup));
+    if let Ok(fds) = fs::read_dir(dir.join("fd")) {
+        process.inspected = true;
+        for fd in fds.filter_map(|fd| fd.ok()) {
+            let Ok(target) = fs::read_link(fd.path()) else { continue };
+            let target = target.to_string_lossy();
+            if let Some(inode) = target.strip_prefix("socket:[").and_then(|rest| rest.strip_suffix(']')) {
+                process.sockets.extend(inode.parse::<u64>());
+            }
+        }
+    }
+}
+
+/// The innermost `.service` in a `/proc/<pid>/cgroup` path. Units under
+/// `user@UID.service` are user units and get a `--user` prefix.
+pub fn cgroup_unit(cgroup: &str) -> Option<String> {
+    let path = cgroup.lines().find_map(|line| line.strip_prefix("0::"))?;
+    let unit = path.split('/').rfind(|part| part.ends_with(".service"))?;
+    if path.contains("/user@") && !unit.starts_with("user@") {
+        Some(format!("--user {}", unit))
+    } else {
+        Some(unit.to_string())
+    }
+}
+
+/// Newer kernels have an AppArmor-specific file; `attr/current` may belong
+/// to another LSM when several are stacked.
+fn read_label(dir: &Path) -> Option<String> {
+    fs::read_to_string(dir.join("attr/apparmor/current"))
+        .or_else(|_| fs::read_to_string(dir.join("attr/current")))
+        .ok()
+}
+
+/// Splits `name (mode)` as found in `attr/current`. `unconfined` has no
+/// mode.
+pub fn parse_label(current: &str) -> (String, String) {
+    let current = current.trim_end_matches(['\n', '\0']);
+    match current.rsplit_once(" (") {
+        Some((label, mode)) if mode.ends_with(')') => (label.to_string(), mode.trim_end_matches(')').to_string()),
+        _ => (current.to_string(), "unconfined".to_string()),
+    }
+}
+
+/// The real UID from a `/proc/<pid>/status` file.
+fn status_uid(status: &str) -> Option<u32> {
+    let line = status.lines().find(|line| line.starts_with("Uid:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn parse_passwd(passwd: &str) -> HashMap<u32, String> {
+    passwd
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?.parse().ok()?;
+            Some((uid, name.to_string()))
+        })
+        .collect()
+}
+
+/// Replaces the processes of each profile with the confined ones found in
+/// `/proc`, which is more current than what `aa-status` reported.
+pub fn attach(profiles: &mut [Profile], processes: &[ProcessInfo]) {
+    let mut by_profile: HashMap<String, Vec<Process>> = HashMap::new();
+    for process in processes.iter().filter(|p| p.is_confined()) {
+        if let Some(profile) = process.profile(profiles) {
+            by_profile.entry(profile.name.clone()).or_default().push(Process {
+                pid: process.pid,
+                exe: process.command.clone(),
+                status: process.mode.clone(),
+            });
+        }
+    }
+    for profile in profiles {
+        profile.processes = by_profile.remove(&profile.name).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::Mode;
+
+    #[test]
+    fn labels_map_to_profiles() {
+        assert_eq!(parse_label("unconfined\n"), ("unconfined".to_string(), "unconfined".to_string()));
+        assert_eq!(parse_label("snap.lxd.daemon (complain)\n"), ("snap.lxd.daemon".to_string(), "complain".to_string()));
+
+        let mut profiles = vec![Profile::new("firefox", Mode::Enforce), Profile::new("cupsd", Mode::Enforce)];
+        let process = |pid, current: &str| {
+            let (label, mode) = parse_label(current);
+            ProcessInfo { pid, command: "x".to_string(), user: "root".to_string(), label, mode, ..ProcessInfo::default() }
+        };
+        let processes = [process(1, "unconfined"), process(7, "firefox//browser (enforce)"), process(9, "firefox (enforce)")];
+        assert_eq!(processes[1].profile(&profiles).unwrap().name, "firefox");
+        attach(&mut profiles, &processes);
+        assert_eq!(profiles[0].processes.iter().map(|p| p.pid).collect::<Vec<_>>(), [7, 9]);
+        assert!(profiles[1].processes.is_empty());
+        assert_eq!(status_uid("Name:\tx\nUid:\t1000\t1000\t1000\t1000\n"), Some(1000));
+        assert_eq!(cgroup_unit("0::/system.slice/cups.service\n").as_deref(), Some("cups.service"));
+        assert_eq!(
+            cgroup_unit("0::/user.slice/user-1000.slice/user@1000.service/app.slice/foo.service\n").as_deref(),
+            Some("--user foo.service")
+        );
+    }
+
+    #[test]
+    fn only_unconfined_processes_are_inspected() {
+        let root = std::env::temp_dir().join(format!("apparmor-tui-test-{}-proc", std::process::id()));
+        for (pid, label) in [(1, "unconfined\n"), (2, "firefox (enforce)\n")] {
+            let dir = root.join(pid.to_string());
+            fs::create_dir_all(dir.join("attr")).unwrap();
+            fs::create_dir_all(dir.join("fd")).unwrap();
+            fs::write(dir.join("attr/current"), label).unwrap();
+            fs::write(dir.join("comm"), "cupsd\n").unwrap();
+            fs::write(dir.join("cgroup"), "0::/system.slice/cups.service\n").unwrap();
+            std::os::unix::fs::symlink("/usr/sbin/cupsd", dir.join("exe")).unwrap();
+            std::os::unix::fs::symlink("socket:[4242]", dir.join("fd/3")).unwrap();
+        }
+        let scanned = scan(&root);
+        let mut processes = scanned.as_ref().unwrap().clone();
+        inspect_unconfined(&root, &mut processes);
+        fs::remove_dir_all(&root).unwrap();
+
+        let scanned = scanned.unwrap();
+        assert_eq!(scanned.iter().map(|p| (p.pid, p.mode.as_str())).collect::<Vec<_>>(), [(1, "unconfined"), (2, "enforce")]);
+        assert!(scanned.iter().all(|p| p.exe.is_none() && p.sockets.is_empty() && !p.inspected));
+        assert_eq!(processes[0].exe.as_deref(), Some("/usr/sbin/cupsd"));
+        assert_eq!(processes[0].unit.as_deref(), Some("cups.service"));
+        assert_eq!(processes[0].sockets, [4242]);
+        assert!(processes[0].inspected);
+        assert_eq!(processes[1], scanned[1]);
+    }
+}
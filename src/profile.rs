@@ -0,0 +1,100 @@
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Mode {
+    Enforce,
+    Complain,
+    Audit,
+    Disable,
+    Kill,
+    /// Denials are sent to a userspace prompting daemon.
+    Prompt,
+    /// Loaded, but doesn't restrict the process.
+    Unconfined,
+    /// A mode this version doesn't know, such as `mixed` for a label
+    /// stacking profiles in different modes.
+    Unknown,
+}
+
+impl Mode {
+    /// Every mode, in the order they are listed and counted.
+    pub const ALL: [Mode; 8] = [
+        Mode::Enforce,
+        Mode::Complain,
+        Mode::Prompt,
+        Mode::Audit,
+        Mode::Kill,
+        Mode::Unconfined,
+        Mode::Disable,
+        Mode::Unknown,
+    ];
+
+    /// Maps the mode names used by `aa-status` and securityfs.
+    pub fn parse(name: &str) -> Option<Mode> {
+        match name.trim() {
+            "enforce" => Some(Mode::Enforce),
+            "complain" => Some(Mode::Complain),
+            "audit" => Some(Mode::Audit),
+            "disable" | "disabled" => Some(Mode::Disable),
+            "kill" => Some(Mode::Kill),
+            "prompt" => Some(Mode::Prompt),
+            "unconfined" => Some(Mode::Unconfined),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Mode::Enforce => "enforce",
+            Mode::Complain => "complain",
+            Mode::Audit => "audit",
+            Mode::Disable => "disable",
+            Mode::Kill => "kill",
+            Mode::Prompt => "prompt",
+            Mode::Unconfined => "unconfined",
+            Mode::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Process {
+    pub pid: u32,
+    pub exe: String,
+    /// Confinement status as reported for the process, e.g. "enforce".
+    pub status: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub mode: Mode,
+    /// The mode the policy files will load the profile in at next boot,
+    /// when its file is known.
+    pub disk_mode: Option<Mode>,
+    pub processes: Vec<Process>,
+}
+
+impl Profile {
+    pub fn new(name: impl Into<String>, mode: Mode) -> Profile {
+        Profile {
+            name: name.into(),
+            mode,
+            disk_mode: None,
+            processes: Vec::new(),
+        }
+    }
+
+    /// Whether the loaded mode differs from what the files say, e.g. after
+    /// `apparmor_parser -C` or a write to securityfs. A profile that isn't
+    /// loaded has no mode to compare, so it never differs.
+    pub fn mode_differs(&self) -> bool {
+        self.mode != Mode::Disable && self.disk_mode.is_some_and(|disk| disk != self.mode)
+    }
+}
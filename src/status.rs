@@ -0,0 +1,282 @@
+//! Loading the set of loaded profiles from securityfs or `aa-status`.
+
+use crate::json::{self, Value};
+use crate::profile::{Mode, Process, Profile};
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Where securityfs is normally mounted.
+pub const SECURITYFS_ROOT: &str = "/sys/kernel/security";
+
+const AA_STATUS: &str = "aa-status";
+
+/// Reads the profile list straight from securityfs below `securityfs_root`,
+/// and only spawns `aa-status` when that file can't be read.
+pub fn load_profiles(securityfs_root: &Path) -> Result<Vec<Profile>> {
+    load_profiles_with(securityfs_root, AA_STATUS)
+}
+
+fn load_profiles_with(securityfs_root: &Path, aa_status: &str) -> Result<Vec<Profile>> {
+    match load_securityfs(securityfs_root) {
+        Ok(profiles) => Ok(profiles),
+        Err(_) => load_aa_status(aa_status),
+    }
+}
+
+/// Parses `<root>/apparmor/profiles`, which has one `name (mode)` per line.
+pub fn load_securityfs(securityfs_root: &Path) -> Result<Vec<Profile>> {
+    let path = securityfs_root.join("apparmor").join("profiles");
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(parse_securityfs(&content))
+}
+
+/// Profiles in a mode [`Mode`] doesn't know are kept as [`Mode::Unknown`].
+pub fn parse_securityfs(input: &str) -> Vec<Profile> {
+    input
+        .lines()
+        .filter_map(|line| {
+            let (name, mode) = line.trim_end().rsplit_once(" (")?;
+            let mode = Mode::parse(mode.strip_suffix(')')?).unwrap_or(Mode::Unknown);
+            Some(Profile::new(name, mode))
+        })
+        .collect()
+}
+
+/// Loads profiles from `aa-status --json`, falling back to the plain text
+/// output on versions that predate the JSON interface.
+fn load_aa_status(program: &str) -> Result<Vec<Profile>> {
+    let output = Command::new(program)
+        .arg("--json")
+        .output()
+        .context("Failed to execute aa-status")?;
+
+    if output.status.success() {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if let Ok(profiles) = parse_json(&stdout) {
+            return Ok(profiles);
+        }
+    }
+
+    let output = Command::new(program)
+        .output()
+        .context("Failed to execute aa-status")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("aa-status failed: {}", stderr.trim()));
+    }
+
+    Ok(parse_text(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses the document printed by `aa-status --json` / `--pretty-json`.
+pub fn parse_json(input: &str) -> Result<Vec<Profile>> {
+    let doc = json::parse(input)?;
+    let entries = doc
+        .get("profiles")
+        .and_then(Value::as_object)
+        .ok_or_else(|| anyhow!("aa-status JSON has no \"profiles\" object"))?;
+
+    let mut profiles: Vec<Profile> = entries
+        .iter()
+        .filter_map(|(name, mode)| Some(Profile::new(name.clone(), Mode::parse(mode.as_str()?).unwrap_or(Mode::Unknown))))
+        .collect();
+
+    let processes = doc.get("processes").and_then(Value::as_object).unwrap_or(&[]);
+    for (exe, list) in processes {
+        for entry in list.as_array().unwrap_or(&[]) {
+            let Some(pid) = entry.get("pid").and_then(json_pid) else {
+                continue;
+            };
+            let profile = entry.get("profile").and_then(Value::as_str).unwrap_or(exe);
+            let status = entry.get("status").and_then(Value::as_str).unwrap_or_default();
+            attach_process(&mut profiles, profile, Process {
+                pid,
+                exe: exe.clone(),
+                status: status.to_string(),
+            });
+        }
+    }
+
+    Ok(profiles)
+}
+
+fn json_pid(value: &Value) -> Option<u32> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        Value::Number(n) => Some(*n as u32),
+        _ => None,
+    }
+}
+
+fn attach_process(profiles: &mut [Profile], name: &str, process: Process) {
+    if let Some(profile) = profiles.iter_mut().find(|p| p.name == name) {
+        profile.processes.push(process);
+    }
+}
+
+enum Section {
+    Profiles(Mode),
+    Processes(String),
+    Other,
+}
+
+/// Parses the human readable `aa-status` output. Only used as a fallback
+/// since the section headers are English sentences.
+pub fn parse_text(input: &str) -> Vec<Profile> {
+    let mut profiles = Vec::new();
+    let mut processes = Vec::new();
+    let mut section = Section::Other;
+
+    for line in input.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        if !line.starts_with(char::is_whitespace) {
+            section = text_section(trimmed);
+            continue;
+        }
+
+        match &section {
+            Section::Profiles(mode) => profiles.push(Profile::new(trimmed, *mode)),
+            Section::Processes(status) => {
+                if let Some(entry) = parse_process_line(trimmed, status) {
+                    processes.push(entry);
+                }
+            }
+            Section::Other => {}
+        }
+    }
+
+    for (profile, process) in processes {
+        attach_process(&mut profiles, &profile, process);
+    }
+    profiles
+}
+
+fn text_section(header: &str) -> Section {
+    let words: Vec<&str> = header.trim_end_matches('.').split_whitespace().collect();
+    match words.as_slice() {
+        [_, "profiles", "are", "in", mode, "mode"] => Section::Profiles(Mode::parse(mode).unwrap_or(Mode::Unknown)),
+        [_, "processes", "are", "in", mode, "mode"] => Section::Processes(mode.to_string()),
+        [_, "processes", "are", "unconfined", ..] => Section::Processes("unconfined".to_string()),
+        _ => Section::Other,
+    }
+}
+
+/// Parses `exe (pid) [profile]`; the profile is omitted when it equals exe.
+fn parse_process_line(line: &str, status: &str) -> Option<(String, Process)> {
+    let open = line.rfind(" (")?;
+    let close = open + line[open..].find(')')?;
+    let exe = line[..open].to_string();
+    let pid = line[open + 2..close].parse().ok()?;
+    let profile = match line[close + 1..].trim() {
+        "" => exe.clone(),
+        name => name.to_string(),
+    };
+    Some((profile, Process {
+        pid,
+        exe,
+        status: status.to_string(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn testdata(path: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata").join(path)
+    }
+
+    fn modes(profiles: &[Profile]) -> Vec<(&str, Mode)> {
+        profiles.iter().map(|p| (p.name.as_str(), p.mode)).collect()
+    }
+
+    #[test]
+    fn reads_securityfs_and_keeps_unknown_modes() {
+        let profiles = load_profiles_with(&testdata("securityfs"), "/nonexistent/aa-status").unwrap();
+        assert_eq!(
+            modes(&profiles),
+            [
+                ("/usr/sbin/cupsd", Mode::Enforce),
+                ("/usr/sbin/cupsd//third_party", Mode::Enforce),
+                ("firefox", Mode::Complain),
+                ("snap.lxd.daemon", Mode::Unconfined),
+                ("unix-chkpwd", Mode::Kill),
+                ("signal-desktop", Mode::Prompt),
+                ("stacked//&other", Mode::Unknown),
+                ("user-profile", Mode::Unknown),
+            ]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_aa_status() {
+        let missing = testdata("no-securityfs");
+        let aa_status = testdata("aa-status/aa-status");
+        let profiles = load_profiles_with(&missing, aa_status.to_str().unwrap()).unwrap();
+        assert_eq!(profiles.len(), 8);
+        assert_eq!(profiles.iter().find(|p| p.name == "stacked").unwrap().mode, Mode::Unknown);
+
+        let old = testdata("aa-status/aa-status-nojson");
+        let from_text = load_profiles_with(&missing, old.to_str().unwrap()).unwrap();
+        assert_eq!(modes(&from_text), modes(&profiles));
+
+        let err = load_profiles_with(&missing, "/nonexistent/aa-status").unwrap_err();
+        assert!(err.to_string().contains("Failed to execute aa-status"), "{}", err);
+    }
+
+    fn processes(profiles: &[Profile], name: &str) -> Vec<(u32, String, String)> {
+        let profile = profiles.iter().find(|p| p.name == name).unwrap();
+        profile.processes.iter().map(|p| (p.pid, p.exe.clone(), p.status.clone())).collect()
+    }
+
+    #[test]
+    fn parses_aa_status_json() {
+        let profiles = parse_json(include_str!("../testdata/aa-status/status.json")).unwrap();
+        assert_eq!(profiles.len(), 8);
+        assert_eq!(modes(&profiles)[..2], [("/usr/bin/man", Mode::Enforce), ("/usr/sbin/cups-browsed", Mode::Enforce)]);
+        assert_eq!(processes(&profiles, "/usr/sbin/cupsd"), [(1234, "/usr/sbin/cupsd".to_string(), "enforce".to_string())]);
+        let firefox = processes(&profiles, "firefox");
+        assert_eq!(firefox.iter().map(|p| p.0).collect::<Vec<_>>(), [4321, 4330]);
+        assert_eq!(firefox[0].1, "/usr/lib/firefox/firefox");
+        // Numeric PIDs, and no profile when it is the executable.
+        let old = parse_json(r#"{"profiles": {"/usr/bin/ping": "enforce"}, "processes": {"/usr/bin/ping": [{"pid": 77, "status": "enforce"}]}}"#);
+        assert_eq!(processes(&old.unwrap(), "/usr/bin/ping")[0].0, 77);
+        assert!(parse_json(r#"{"version": "2"}"#).is_err());
+        assert!(parse_json("apparmor module is loaded.").is_err());
+    }
+
+    #[test]
+    fn parses_aa_status_text() {
+        let profiles = parse_text(include_str!("../testdata/aa-status/status.txt"));
+        assert_eq!(
+            modes(&profiles),
+            [
+                ("/usr/bin/man", Mode::Enforce),
+                ("/usr/sbin/cups-browsed", Mode::Enforce),
+                ("/usr/sbin/cupsd", Mode::Enforce),
+                ("/usr/sbin/cupsd//third_party", Mode::Enforce),
+                ("man_filter", Mode::Enforce),
+                ("firefox", Mode::Complain),
+                ("lsb_release", Mode::Kill),
+                ("stacked", Mode::Unknown),
+            ]
+        );
+        assert_eq!(processes(&profiles, "/usr/sbin/cupsd"), [(1234, "/usr/sbin/cupsd".to_string(), "enforce".to_string())]);
+        assert_eq!(
+            processes(&profiles, "firefox"),
+            [
+                (4321, "/usr/lib/firefox/firefox".to_string(), "complain".to_string()),
+                (4330, "/usr/lib/firefox/firefox".to_string(), "complain".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,471 @@
+//! The built-in policy editor: a line buffer with undo/redo, search and
+//! completion. Drawing lives in `ui`; loading and saving go through the app.
+
+use crate::highlight::RULE_KEYWORDS;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::path::PathBuf;
+
+/// Capability names accepted by `capability` rules.
+pub const CAPABILITIES: &[&str] = &[
+    "audit_control", "audit_read", "audit_write", "block_suspend", "bpf", "checkpoint_restore", "chown",
+    "dac_override", "dac_read_search", "fowner", "fsetid", "ipc_lock", "ipc_owner", "kill", "lease",
+    "linux_immutable", "mac_admin", "mac_override", "mknod", "net_admin", "net_bind_service", "net_broadcast",
+    "net_raw", "perfmon", "setfcap", "setgid", "setpcap", "setuid", "sys_admin", "sys_boot", "sys_chroot",
+    "sys_module", "sys_nice", "sys_pacct", "sys_ptrace", "sys_rawio", "sys_resource", "sys_time",
+    "sys_tty_config", "syslog", "wake_alarm",
+];
+
+const MAX_UNDO: usize = 1000;
+
+/// What the app should do after a key.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EditorAction {
+    None,
+    Save,
+    Close,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum EditKind {
+    Insert,
+    Delete,
+    Other,
+}
+
+struct Snapshot {
+    lines: Vec<String>,
+    cursor: (usize, usize),
+}
+
+/// Open completion popup.
+pub struct Completion {
+    /// Column where the completed word starts.
+    pub start: usize,
+    pub candidates: Vec<String>,
+    pub selected: usize,
+}
+
+pub struct TextEditor {
+    pub path: PathBuf,
+    pub lines: Vec<String>,
+    /// Cursor line and column, both 0-based; the column counts chars.
+    pub cursor: (usize, usize),
+    /// First visible line and column.
+    pub scroll: (usize, usize),
+    /// Search prompt contents while it is open.
+    pub search: Option<String>,
+    /// Last searched text, highlighted and used by next/previous.
+    pub pattern: String,
+    pub completion: Option<Completion>,
+    /// Set after Esc on a modified buffer; a second Esc discards.
+    pub confirm_close: bool,
+    saved: Vec<String>,
+    trailing_newline: bool,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    last_edit: Option<EditKind>,
+    /// Completion vocabulary.
+    words: Vec<String>,
+}
+
+impl TextEditor {
+    /// Opens `content` with the cursor on `line` (1-based). `abstractions`
+    /// are names under `abstractions/`, offered as `<abstractions/NAME>`.
+    pub fn new(path: PathBuf, content: &str, line: usize, abstractions: &[String]) -> TextEditor {
+        let mut lines: Vec<String> = content.split('\n').map(|l| l.strip_suffix('\r').unwrap_or(l).to_string()).collect();
+        let trailing_newline = content.ends_with('\n');
+        if trailing_newline {
+            lines.pop();
+        }
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        let mut words: Vec<String> = RULE_KEYWORDS.iter().chain(CAPABILITIES).map(|w| w.to_string()).collect();
+        words.extend(["deny", "audit", "allow", "owner", "flags=("].map(str::to_string));
+        words.extend(abstractions.iter().map(|name| format!("<abstractions/{}>", name)));
+        words.sort();
+        words.dedup();
+        let row = line.saturating_sub(1).min(lines.len() - 1);
+        TextEditor {
+            path,
+            saved: lines.clone(),
+            lines,
+            cursor: (row, 0),
+            scroll: (row.saturating_sub(5), 0),
+            search: None,
+            pattern: String::new(),
+            completion: None,
+            confirm_close: false,
+            trailing_newline,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last_edit: None,
+            words,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = self.lines.join("\n");
+        if self.trailing_newline {
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn modified(&self) -> bool {
+        self.lines != self.saved
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = self.lines.clone();
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> EditorAction {
+        if key.code != KeyCode::Esc {
+            self.confirm_close = false;
+        }
+        if self.completion.is_some() && self.handle_completion_key(key) {
+            return EditorAction::None;
+        }
+        if self.search.is_some() {
+            self.handle_search_key(key);
+            return EditorAction::None;
+        }
+
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('s') if ctrl => return EditorAction::Save,
+            KeyCode::Char('z') if ctrl => self.undo(),
+            KeyCode::Char('y') if ctrl => self.redo(),
+            KeyCode::Char('f') if ctrl => self.search = Some(String::new()),
+            KeyCode::Char('n') if ctrl => self.find(true),
+            KeyCode::Char('p') if ctrl => self.find(false),
+            KeyCode::F(3) if key.modifiers.contains(KeyModifiers::SHIFT) => self.find(false),
+            KeyCode::F(3) => self.find(true),
+            KeyCode::Char(' ') if ctrl => self.complete(),
+            KeyCode::Esc if self.modified() && !self.confirm_close => self.confirm_close = true,
+            KeyCode::Esc => return EditorAction::Close,
+            KeyCode::Up => self.move_to(self.cursor.0.saturating_sub(1), self.cursor.1),
+            KeyCode::Down => self.move_to(self.cursor.0 + 1, self.cursor.1),
+            KeyCode::PageUp => self.move_to(self.cursor.0.saturating_sub(20), self.cursor.1),
+            KeyCode::PageDown => self.move_to(self.cursor.0 + 20, self.cursor.1),
+            KeyCode::Home if ctrl => self.move_to(0, 0),
+            KeyCode::End if ctrl => self.move_to(usize::MAX, usize::MAX),
+            KeyCode::Home => self.move_to(self.cursor.0, 0),
+            KeyCode::End => self.move_to(self.cursor.0, usize::MAX),
+            KeyCode::Left => self.left(),
+            KeyCode::Right => self.right(),
+            KeyCode::Tab => self.complete(),
+            KeyCode::Enter => self.newline(),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Char(c) if !ctrl => self.insert(c),
+            _ => {}
+        }
+        EditorAction::None
+    }
+
+    /// Returns whether the popup consumed the key.
+    fn handle_completion_key(&mut self, key: KeyEvent) -> bool {
+        let Some(completion) = &mut self.completion else { return false };
+        let len = completion.candidates.len();
+        match key.code {
+            KeyCode::Down => completion.selected = (completion.selected + 1) % len,
+            KeyCode::Up => completion.selected = (completion.selected + len - 1) % len,
+            KeyCode::Enter | KeyCode::Tab => {
+                let candidate = completion.candidates[completion.selected].clone();
+                let start = completion.start;
+                self.completion = None;
+                self.replace_word(start, &candidate);
+            }
+            KeyCode::Esc => self.completion = None,
+            _ => {
+                self.completion = None;
+                return false;
+            }
+        }
+        true
+    }
+
+    fn handle_search_key(&mut self, key: KeyEvent) {
+        let Some(search) = &mut self.search else { return };
+        match key.code {
+            KeyCode::Esc => self.search = None,
+            KeyCode::Enter => {
+                self.pattern = self.search.take().unwrap_or_default();
+                self.find(true);
+            }
+            KeyCode::Backspace => {
+                search.pop();
+            }
+            KeyCode::Char(c) => search.push(c),
+            _ => {}
+        }
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+        self.lines[row].chars().count()
+    }
+
+    fn move_to(&mut self, row: usize, col: usize) {
+        let row = row.min(self.lines.len() - 1);
+        self.cursor = (row, col.min(self.line_len(row)));
+        self.last_edit = None;
+    }
+
+    fn left(&mut self) {
+        let (row, col) = self.cursor;
+        if col > 0 {
+            self.move_to(row, col - 1);
+        } else if row > 0 {
+            self.move_to(row - 1, usize::MAX);
+        }
+    }
+
+    fn right(&mut self) {
+        let (row, col) = self.cursor;
+        if col < self.line_len(row) {
+            self.move_to(row, col + 1);
+        } else if row + 1 < self.lines.len() {
+            self.move_to(row + 1, 0);
+        }
+    }
+
+    /// Records an undo point. Runs of typing or deleting share one point,
+    /// broken at whitespace.
+    fn checkpoint(&mut self, kind: EditKind, boundary: bool) {
+        if self.last_edit != Some(kind) || kind == EditKind::Other || boundary {
+            self.undo.push(Snapshot { lines: self.lines.clone(), cursor: self.cursor });
+            if self.undo.len() > MAX_UNDO {
+                self.undo.remove(0);
+            }
+        }
+        self.redo.clear();
+        self.last_edit = Some(kind);
+    }
+
+    fn undo(&mut self) {
+        if let Some(snapshot) = self.undo.pop() {
+            let current = Snapshot { lines: std::mem::replace(&mut self.lines, snapshot.lines), cursor: self.cursor };
+            self.redo.push(current);
+            self.cursor = snapshot.cursor;
+            self.last_edit = None;
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(snapshot) = self.redo.pop() {
+            let current = Snapshot { lines: std::mem::replace(&mut self.lines, snapshot.lines), cursor: self.cursor };
+            self.undo.push(current);
+            self.cursor = snapshot.cursor;
+            self.last_edit = None;
+        }
+    }
+
+    fn insert(&mut self, c: char) {
+        self.checkpoint(EditKind::Insert, c.is_whitespace());
+        let (row, col) = self.cursor;
+        let at = byte_index(&self.lines[row], col);
+        self.lines[row].insert(at, c);
+        self.cursor.1 += 1;
+    }
+
+    /// Splits the line at the cursor, keeping the current indentation.
+    fn newline(&mut self) {
+        self.checkpoint(EditKind::Other, true);
+        let (row, col) = self.cursor;
+        let at = byte_index(&self.lines[row], col);
+        let rest = self.lines[row].split_off(at);
+        let indent: String = self.lines[row].chars().take_while(|c| c.is_whitespace()).collect();
+        self.cursor = (row + 1, indent.chars().count());
+        self.lines.insert(row + 1, indent + rest.trim_start());
+    }
+
+    fn backspace(&mut self) {
+        let (row, col) = self.cursor;
+        if col > 0 {
+            self.checkpoint(EditKind::Delete, false);
+            let at = byte_index(&self.lines[row], col - 1);
+            self.lines[row].remove(at);
+            self.cursor.1 -= 1;
+        } else if row > 0 {
+            self.checkpoint(EditKind::Delete, true);
+            let line = self.lines.remove(row);
+            self.cursor = (row - 1, self.line_len(row - 1));
+            self.lines[row - 1].push_str(&line);
+        }
+    }
+
+    fn delete(&mut self) {
+        let (row, col) = self.cursor;
+        if col < self.line_len(row) {
+            self.checkpoint(EditKind::Delete, false);
+            let at = byte_index(&self.lines[row], col);
+            self.lines[row].remove(at);
+        } else if row + 1 < self.lines.len() {
+            self.checkpoint(EditKind::Delete, true);
+            let line = self.lines.remove(row + 1);
+            self.lines[row].push_str(&line);
+        }
+    }
+
+    /// Moves to the next (or previous) match of `pattern`, wrapping around.
+    /// Matching ignores ASCII case.
+    fn find(&mut self, forward: bool) {
+        if self.pattern.is_empty() {
+            return;
+        }
+        let count = self.lines.len();
+        let (row, col) = self.cursor;
+        for step in 0..=count {
+            let r = if forward { (row + step) % count } else { (row + count - step % count) % count };
+            let matches = self.matches(r);
+            let found = if forward {
+                matches.into_iter().find(|&(start, _)| step > 0 || start > col)
+            } else {
+                matches.into_iter().rev().find(|&(start, _)| step > 0 || start < col)
+            };
+            if let Some((start, _)) = found {
+                self.move_to(r, start);
+                return;
+            }
+        }
+    }
+
+    /// Char ranges of `pattern` on line `row`.
+    pub fn matches(&self, row: usize) -> Vec<(usize, usize)> {
+        if self.pattern.is_empty() {
+            return Vec::new();
+        }
+        let line = self.lines[row].to_ascii_lowercase();
+        let pattern = self.pattern.to_ascii_lowercase();
+        let width = pattern.chars().count();
+        line.match_indices(&pattern)
+            .map(|(at, _)| {
+                let start = line[..at].chars().count();
+                (start, start + width)
+            })
+            .collect()
+    }
+
+    /// Completes the word before the cursor, or indents when there is none.
+    fn complete(&mut self) {
+        let (row, col) = self.cursor;
+        let before: Vec<char> = self.lines[row].chars().take(col).collect();
+        let start = before.iter().rposition(|c| c.is_whitespace()).map_or(0, |i| i + 1);
+        let prefix: String = before[start..].iter().collect();
+        if prefix.is_empty() {
+            self.insert(' ');
+            self.insert(' ');
+            return;
+        }
+        let candidates: Vec<String> =
+            self.words.iter().filter(|w| w.starts_with(&prefix) && **w != prefix).cloned().collect();
+        match candidates.len() {
+            0 => {}
+            1 => self.replace_word(start, &candidates[0]),
+            _ => self.completion = Some(Completion { start, candidates, selected: 0 }),
+        }
+    }
+
+    fn replace_word(&mut self, start: usize, word: &str) {
+        self.checkpoint(EditKind::Other, true);
+        let (row, col) = self.cursor;
+        let line = &mut self.lines[row];
+        let range = byte_index(line, start)..byte_index(line, col);
+        line.replace_range(range, word);
+        self.cursor.1 = start + word.chars().count();
+    }
+
+    /// Adjusts `scroll` so the cursor is inside a `height` x `width` view.
+    pub fn scroll_to_cursor(&mut self, height: usize, width: usize) {
+        let (row, col) = self.cursor;
+        if row < self.scroll.0 {
+            self.scroll.0 = row;
+        } else if height > 0 && row >= self.scroll.0 + height {
+            self.scroll.0 = row + 1 - height;
+        }
+        if col < self.scroll.1 {
+            self.scroll.1 = col;
+        } else if width > 0 && col >= self.scroll.1 + width {
+            self.scroll.1 = col + 1 - width;
+        }
+    }
+}
+
+fn byte_index(line: &str, col: usize) -> usize {
+    line.char_indices().nth(col).map_or(line.len(), |(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(content: &str) -> TextEditor {
+        TextEditor::new(PathBuf::from("/etc/apparmor.d/test"), content, 1, &["base".to_string(), "bash".to_string()])
+    }
+
+    fn typing(editor: &mut TextEditor, text: &str) {
+        for c in text.chars() {
+            editor.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn undo_and_redo_whole_words() {
+        let mut editor = editor("profile test {\n}\n");
+        editor.handle_key(KeyEvent::from(KeyCode::End));
+        editor.handle_key(KeyEvent::from(KeyCode::Enter));
+        typing(&mut editor, "capability kill,");
+        assert_eq!(editor.text(), "profile test {\ncapability kill,\n}\n");
+        editor.handle_key(ctrl('z'));
+        assert_eq!(editor.lines[1], "capability");
+        editor.handle_key(ctrl('z'));
+        editor.handle_key(ctrl('z'));
+        assert_eq!(editor.text(), "profile test {\n}\n");
+        assert!(!editor.modified());
+        editor.handle_key(ctrl('y'));
+        editor.handle_key(ctrl('y'));
+        editor.handle_key(ctrl('y'));
+        assert_eq!(editor.lines[1], "capability kill,");
+    }
+
+    #[test]
+    fn completes_keywords_capabilities_and_abstractions() {
+        let mut editor = editor("\n");
+        typing(&mut editor, "capab");
+        editor.handle_key(KeyEvent::from(KeyCode::Tab));
+        typing(&mut editor, " net_bind_s");
+        editor.handle_key(KeyEvent::from(KeyCode::Tab));
+        assert_eq!(editor.lines[0], "capability net_bind_service");
+
+        let mut editor = self::editor("\n");
+        typing(&mut editor, "include <abstractions/ba");
+        editor.handle_key(KeyEvent::from(KeyCode::Tab));
+        let completion = editor.completion.as_ref().unwrap();
+        assert_eq!(completion.candidates, ["<abstractions/base>", "<abstractions/bash>"]);
+        editor.handle_key(KeyEvent::from(KeyCode::Down));
+        editor.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(editor.lines[0], "include <abstractions/bash>");
+    }
+
+    #[test]
+    fn search_wraps_around() {
+        let mut editor = editor("/etc/passwd r,\n/etc/group r,\n/etc/PASSWD w,\n");
+        editor.handle_key(ctrl('f'));
+        typing(&mut editor, "passwd");
+        editor.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(editor.cursor, (0, 5));
+        editor.handle_key(ctrl('n'));
+        assert_eq!(editor.cursor, (2, 5));
+        editor.handle_key(ctrl('n'));
+        assert_eq!(editor.cursor, (0, 5));
+        editor.handle_key(ctrl('p'));
+        assert_eq!(editor.cursor, (2, 5));
+        assert_eq!(editor.matches(0), [(5, 11)]);
+    }
+}
@@ -0,0 +1,54 @@
+//! Terminal setup, plus suspending the TUI while an external program owns
+//! the terminal (sudo password prompts, editors).
+
+use anyhow::Result;
+use crossterm::{
+    cursor, execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{backend::CrosstermBackend, Terminal};
+use std::io::{self, Stdout};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub type Tui = Terminal<CrosstermBackend<Stdout>>;
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static NEEDS_CLEAR: AtomicBool = AtomicBool::new(false);
+
+pub fn enter() -> Result<Tui> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    ACTIVE.store(true, Ordering::SeqCst);
+    Ok(Terminal::new(CrosstermBackend::new(stdout))?)
+}
+
+pub fn leave(terminal: &mut Tui) -> Result<()> {
+    ACTIVE.store(false, Ordering::SeqCst);
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    Ok(())
+}
+
+/// Runs `f` with the terminal back in cooked mode on the main screen. When
+/// the TUI isn't running this just calls `f`.
+pub fn suspended<T>(f: impl FnOnce() -> T) -> Result<T> {
+    if !ACTIVE.load(Ordering::SeqCst) {
+        return Ok(f());
+    }
+
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, cursor::Show)?;
+    let result = f();
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+    NEEDS_CLEAR.store(true, Ordering::SeqCst);
+    Ok(result)
+}
+
+/// Whether the screen was handed to another program since the last call,
+/// so the next frame has to be drawn from scratch.
+pub fn take_needs_clear() -> bool {
+    NEEDS_CLEAR.swap(false, Ordering::SeqCst)
+}
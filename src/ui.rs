@@ -0,0 +1,772 @@
+use crate::app::{App, DiffReview, Focus, PromptKind, View, MODE_KEYS};
+use crate::audit::{AuditEvent, Verdict};
+use crate::diff::RowKind;
+use crate::filter;
+use crate::flags;
+use crate::listing::{self, ListRow, SortKey};
+use crate::highlight;
+use crate::messages::{self, Level, Message};
+use crate::profile::Mode;
+use crate::unconfined;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub fn draw(f: &mut Frame, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(f.area());
+
+    match app.view {
+        View::Profiles => draw_profiles(f, app, chunks[0]),
+        View::Messages => draw_messages(f, app, chunks[0]),
+        View::Logprof => draw_logprof(f, app, chunks[0]),
+        View::CheckFailed => {
+            if app.editor.is_some() {
+                draw_editor(f, app, chunks[0]);
+            } else {
+                draw_profiles(f, app, chunks[0]);
+            }
+            draw_check_failed(f, app, chunks[0]);
+        }
+        View::Editor => draw_editor(f, app, chunks[0]),
+        View::Diff => draw_diff(f, app, chunks[0]),
+        View::Batch => {
+            draw_profiles(f, app, chunks[0]);
+            draw_batch(f, app, chunks[0]);
+        }
+        View::Processes => draw_processes(f, app, chunks[0]),
+        View::Unconfined => draw_unconfined(f, app, chunks[0]),
+        View::ModeMenu => {
+            draw_profiles(f, app, chunks[0]);
+            draw_mode_menu(f, app, chunks[0]);
+        }
+        View::Flags => {
+            draw_profiles(f, app, chunks[0]);
+            draw_flags(f, app, chunks[0]);
+        }
+    }
+    draw_status_bar(f, app, chunks[1]);
+}
+
+pub fn mode_color(mode: Mode) -> Color {
+    match mode {
+        Mode::Enforce => Color::Green,
+        Mode::Complain => Color::Yellow,
+        Mode::Audit => Color::Cyan,
+        Mode::Disable => Color::Gray,
+        Mode::Kill => Color::Red,
+        Mode::Prompt => Color::Magenta,
+        Mode::Unconfined => Color::LightBlue,
+        Mode::Unknown => Color::White,
+    }
+}
+
+fn draw_profiles(f: &mut Frame, app: &mut App, area: Rect) {
+    let area = if app.log.visible {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(area);
+        draw_log(f, app, chunks[1]);
+        chunks[0]
+    } else {
+        area
+    };
+    // Mode counts double as the colour legend.
+    let mut legend = Vec::new();
+    for (mode, count) in listing::mode_counts(&app.profiles) {
+        legend.push(Span::styled(format!("■ {} {}  ", mode, count), Style::default().fg(mode_color(mode))));
+    }
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(area);
+    f.render_widget(Paragraph::new(Line::from(legend)), chunks[0]);
+    let area = chunks[1];
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(area);
+    draw_detail(f, app, chunks[1]);
+    let area = chunks[0];
+
+    let denials = if app.layout.sort == SortKey::Denials { app.denial_counts() } else { HashMap::new() };
+    let indent = if app.layout.group_by_mode { "  " } else { "" };
+    let marking = !app.marked.is_empty();
+    let items: Vec<ListItem> = app
+        .rows
+        .iter()
+        .map(|row| match *row {
+            ListRow::Group { mode, count, collapsed } => {
+                let arrow = if collapsed { "▸" } else { "▾" };
+                let style = Style::default().fg(mode_color(mode)).add_modifier(Modifier::BOLD);
+                ListItem::new(Line::styled(format!("{} {} ({})", arrow, mode, count), style))
+            }
+            ListRow::Profile(i) => {
+                let profile = &app.profiles[i];
+                let style = Style::default().fg(mode_color(profile.mode));
+                let positions = filter::fuzzy_match(&app.search, &profile.name).map(|(_, p)| p).unwrap_or_default();
+                let mut line = highlight_matches(&profile.name, &positions, style);
+                line.spans.insert(0, Span::raw(indent));
+                if marking {
+                    let mark = if app.marked.contains(&profile.name) { "✓ " } else { "  " };
+                    line.spans.insert(1, Span::styled(mark, Style::default().fg(Color::LightMagenta)));
+                }
+                let count = match app.layout.sort {
+                    SortKey::Processes => profile.processes.len(),
+                    SortKey::Denials => denials.get(&profile.name).copied().unwrap_or(0),
+                    _ => 0,
+                };
+                if count > 0 {
+                    line.spans.push(Span::styled(format!(" ({})", count), Style::default().fg(Color::DarkGray)));
+                }
+                if let Some(disk) = profile.disk_mode.filter(|_| profile.mode_differs()) {
+                    line.spans.push(Span::styled(format!(" ⚠ disk: {}", disk), Style::default().fg(Color::LightYellow)));
+                }
+                ListItem::new(line)
+            }
+        })
+        .collect();
+
+    let mut title = format!("AppArmor Profiles (by {})", app.layout.sort.as_str());
+    if !app.filter.is_empty() {
+        title += &format!(" [{}] {}/{}", app.filter.text, app.visible.len(), app.profiles.len());
+    }
+    if marking {
+        title += &format!(" {} marked", app.marked.len());
+    }
+    let list = List::new(items)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED))
+        .highlight_symbol("> ");
+
+    f.render_stateful_widget(list, area, &mut app.state);
+}
+
+/// Styles `text`, underlining the chars at `positions` (search matches).
+fn highlight_matches(text: &str, positions: &[usize], style: Style) -> Line<'static> {
+    let matched = style.add_modifier(Modifier::UNDERLINED | Modifier::BOLD).fg(Color::LightYellow);
+    let spans: Vec<Span> = text
+        .chars()
+        .enumerate()
+        .map(|(i, c)| Span::styled(c.to_string(), if positions.contains(&i) { matched } else { style }))
+        .collect();
+    Line::from(spans)
+}
+
+fn field_line<'a>(label: &'a str, value: String) -> Line<'a> {
+    Line::from(vec![
+        Span::styled(format!("{:<12}", label), Style::default().fg(Color::DarkGray)),
+        Span::raw(value),
+    ])
+}
+
+fn draw_detail(f: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().title("Details (PgUp/PgDn scroll)").borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let (Some(profile), Some(detail)) = (app.selected(), app.detail.as_ref()) else {
+        return;
+    };
+
+    let none = || "-".to_string();
+    let mut info = vec![
+        Line::from(vec![
+            Span::styled(format!("{:<12}", "Mode"), Style::default().fg(Color::DarkGray)),
+            Span::styled(profile.mode.to_string(), Style::default().fg(mode_color(profile.mode))),
+            Span::styled(
+                if profile.mode == Mode::Disable { "  not loaded; e/c or M to enable" } else { "" },
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]),
+        field_line(
+            "On disk",
+            match profile.disk_mode {
+                Some(disk) if profile.mode_differs() => format!("{} ⚠ not what is loaded (P persist, D revert)", disk),
+                disk => disk.map_or_else(none, |mode| mode.to_string()),
+            },
+        ),
+        field_line(
+            "File",
+            detail.location.as_ref().map_or_else(none, |l| format!("{}:{}", l.path.display(), l.line)),
+        ),
+        field_line("Attachment", detail.attachment.clone().unwrap_or_else(none)),
+        field_line("Flags", if detail.flags.is_empty() { none() } else { detail.flags.join(", ") }),
+        field_line("Includes", if detail.includes.is_empty() { none() } else { detail.includes.join(", ") }),
+        field_line(
+            "Rules",
+            detail.rule_counts.iter().map(|(kind, n)| format!("{} {}", kind, n)).collect::<Vec<_>>().join(", "),
+        ),
+    ];
+    if let Some(error) = &detail.error {
+        info.push(Line::styled(error.as_str(), Style::default().fg(Color::Red)));
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(info.len() as u16 + 1), Constraint::Min(1)])
+        .split(inner);
+    f.render_widget(Paragraph::new(info), chunks[0]);
+
+    let header_line = detail.location.as_ref().map(|l| l.line);
+    let lines: Vec<Line> = detail
+        .source
+        .lines()
+        .enumerate()
+        .map(|(n, line)| {
+            let number_style = match header_line {
+                Some(header) if header == n + 1 => Style::default().fg(Color::Yellow),
+                _ => Style::default().fg(Color::DarkGray),
+            };
+            let mut spans = vec![Span::styled(format!("{:>4} ", n + 1), number_style)];
+            spans.extend(highlight::highlight_line(line).spans);
+            Line::from(spans)
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines).scroll((app.detail_scroll, 0)), chunks[1]);
+}
+
+fn event_line(event: &AuditEvent, show_profile: bool, marked: bool) -> Line<'_> {
+    let (verdict, color) = match event.verdict {
+        Verdict::Denied => ("DENIED ", Color::Red),
+        Verdict::Allowed => ("ALLOWED", Color::Yellow),
+    };
+    let time = event.timestamp.map_or_else(|| "--:--:-- ---".to_string(), |t| messages::clock(t as u64));
+    let target = event
+        .name
+        .as_deref()
+        .or_else(|| event.field("capname"))
+        .or_else(|| event.field("family"))
+        .unwrap_or("");
+
+    let mut spans = vec![
+        Span::styled(if marked { "* " } else { "  " }, Style::default().fg(Color::Cyan)),
+        Span::styled(time, Style::default().fg(Color::DarkGray)),
+        Span::raw(" "),
+        Span::styled(verdict, Style::default().fg(color)),
+        Span::raw(" "),
+    ];
+    if show_profile {
+        spans.push(Span::styled(event.profile.as_str(), Style::default().fg(Color::Cyan)));
+        spans.push(Span::raw(" "));
+    }
+    spans.push(Span::styled(event.operation.as_str(), Style::default().add_modifier(Modifier::BOLD)));
+    spans.push(Span::raw(format!(" {}", target)));
+    if let Some(mask) = event.denied_mask.as_deref().or(event.requested_mask.as_deref()) {
+        spans.push(Span::styled(format!(" [{}]", mask), Style::default().fg(Color::Magenta)));
+    }
+    if let Some(pid) = event.pid {
+        spans.push(Span::styled(
+            format!(" pid={} comm={}", pid, event.comm.as_deref().unwrap_or("?")),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    Line::from(spans)
+}
+
+fn draw_log(f: &mut Frame, app: &mut App, area: Rect) {
+    let show_all = app.log.show_all;
+    let events = app.log_events();
+    let title = match app.selected() {
+        _ if show_all => "Audit log: all profiles (f: selected only)".to_string(),
+        Some(profile) => format!("Audit log: {} (f: all profiles)", profile.name),
+        None => "Audit log".to_string(),
+    };
+
+    // Follow the tail unless an event has been selected.
+    let mut state = app.log.state.clone();
+    let visible = area.height.saturating_sub(2) as usize;
+    let items: Vec<ListItem> = events
+        .iter()
+        .map(|(seq, event)| ListItem::new(event_line(event, show_all, app.log.marked.contains(seq))))
+        .collect();
+    if state.selected().is_none() {
+        *state.offset_mut() = items.len().saturating_sub(visible);
+    }
+
+    let border = if app.focus == Focus::Log { Color::Cyan } else { Color::Reset };
+    let list = List::new(items)
+        .block(Block::default().title(title).borders(Borders::ALL).border_style(Style::default().fg(border)))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_stateful_widget(list, area, &mut state);
+    app.log.state = state;
+}
+
+fn draw_logprof(f: &mut Frame, app: &mut App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .review
+        .suggestions
+        .iter()
+        .map(|suggestion| {
+            let check = if suggestion.accepted { "[x] " } else { "[ ] " };
+            let option = &suggestion.options[suggestion.choice];
+            let mut spans = vec![
+                Span::raw(check),
+                Span::styled(format!("{:<30} ", suggestion.profile), Style::default().fg(Color::Cyan)),
+                Span::styled(option.rule.as_str(), Style::default().add_modifier(Modifier::BOLD)),
+            ];
+            if suggestion.options.len() > 1 {
+                spans.push(Span::styled(
+                    format!("  ‹{} {}/{}›", option.label, suggestion.choice + 1, suggestion.options.len()),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            spans.push(Span::styled(format!("  ({} events)", suggestion.events), Style::default().fg(Color::DarkGray)));
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let target = if app.review.to_local { "local/ override" } else { "profile" };
+    let title = format!(
+        "Proposed rules → {} (space accept, ←/→ exact/glob/abstraction, t target, w write, Esc cancel)",
+        target
+    );
+    let list = List::new(items)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_stateful_widget(list, area, &mut app.review.state);
+}
+
+fn draw_editor(f: &mut Frame, app: &mut App, area: Rect) {
+    let Some(editor) = &mut app.editor else { return };
+    let modified = if editor.modified() { " [+]" } else { "" };
+    let block = Block::default().title(format!("{}{}", editor.path.display(), modified)).borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner);
+    let gutter = editor.lines.len().to_string().len() as u16 + 1;
+    let cols = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(gutter), Constraint::Min(1)])
+        .split(rows[0]);
+    let text_area = cols[1];
+    editor.scroll_to_cursor(text_area.height as usize, text_area.width as usize);
+    let (top, left) = editor.scroll;
+    let bottom = (top + text_area.height as usize).min(editor.lines.len());
+
+    let numbers: Vec<Line> = (top..bottom)
+        .map(|n| {
+            let style = if n == editor.cursor.0 { Style::default().fg(Color::Yellow) } else { Style::default().fg(Color::DarkGray) };
+            Line::styled(format!("{:>width$} ", n + 1, width = gutter as usize - 1), style)
+        })
+        .collect();
+    f.render_widget(Paragraph::new(numbers), cols[0]);
+
+    // Tabs are shown as single spaces so columns stay aligned with the buffer.
+    let lines: Vec<Line> = editor.lines[top..bottom].iter().map(|line| highlight::highlight_line(&line.replace('\t', " "))).collect();
+    f.render_widget(Paragraph::new(lines).scroll((0, left as u16)), text_area);
+
+    let buffer = f.buffer_mut();
+    for row in top..bottom {
+        let y = text_area.y + (row - top) as u16;
+        for (start, end) in editor.matches(row) {
+            for col in start.max(left)..end.min(left + text_area.width as usize) {
+                let x = text_area.x + (col - left) as u16;
+                if let Some(cell) = buffer.cell_mut((x, y)) {
+                    cell.set_style(Style::default().bg(Color::Yellow).fg(Color::Black));
+                }
+            }
+        }
+    }
+
+    let cursor_x = text_area.x + (editor.cursor.1 - left) as u16;
+    let cursor_y = text_area.y + (editor.cursor.0 - top) as u16;
+    let info = if let Some(search) = &editor.search {
+        f.set_cursor_position((rows[1].x + 6 + search.chars().count() as u16, rows[1].y));
+        Line::from(format!("Find: {}", search))
+    } else {
+        f.set_cursor_position((cursor_x, cursor_y));
+        if editor.confirm_close {
+            Line::styled("Unsaved changes; Esc again to discard them", Style::default().fg(Color::Red))
+        } else {
+            Line::styled(
+                format!(
+                    "Ln {}, Col {}  ^S save  ^Z/^Y undo/redo  ^F find  ^N/^P next/prev  Tab complete  Esc close",
+                    editor.cursor.0 + 1,
+                    editor.cursor.1 + 1
+                ),
+                Style::default().fg(Color::DarkGray),
+            )
+        }
+    };
+    f.render_widget(Paragraph::new(info), rows[1]);
+
+    if let Some(completion) = &editor.completion {
+        let width = completion.candidates.iter().map(|c| c.chars().count()).max().unwrap_or(0) as u16 + 2;
+        let height = completion.candidates.len().min(8) as u16 + 2;
+        let x = cursor_x.min(area.right().saturating_sub(width));
+        let y = if cursor_y + 1 + height <= area.bottom() { cursor_y + 1 } else { cursor_y.saturating_sub(height) };
+        let popup = Rect { x, y, width: width.min(area.width), height: height.min(area.height) };
+        let items: Vec<ListItem> = completion.candidates.iter().map(|c| ListItem::new(c.as_str())).collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(completion.selected));
+        f.render_widget(Clear, popup);
+        f.render_stateful_widget(list, popup, &mut state);
+    }
+}
+
+fn draw_diff(f: &mut Frame, app: &mut App, area: Rect) {
+    let queued = app.writes.len();
+    let Some(review) = app.writes.front_mut() else { return };
+    let more = if queued > 1 { format!(" (+{} more files)", queued - 1) } else { String::new() };
+    let title = format!(
+        "{}{} (↑/↓ hunk, space accept/reject, a all, s side-by-side, w write, Esc cancel)",
+        review.path.display(),
+        more
+    );
+    let lines = diff_lines(review, area.width.saturating_sub(2) as usize);
+
+    let height = area.height.saturating_sub(2) as usize;
+    let header = lines.iter().position(|(hunk, _)| *hunk == review.selected).unwrap_or(0);
+    let scroll = review.scroll.unwrap_or_else(|| header.saturating_sub(height / 4)).min(lines.len().saturating_sub(height));
+    review.scroll = Some(scroll);
+
+    let visible: Vec<Line> = lines.into_iter().skip(scroll).take(height).map(|(_, line)| line).collect();
+    let paragraph = Paragraph::new(visible).block(Block::default().title(title).borders(Borders::ALL));
+    f.render_widget(paragraph, area);
+}
+
+/// Renders a diff as unified or side-by-side lines, each tagged with its hunk.
+fn diff_lines(review: &DiffReview, width: usize) -> Vec<(usize, Line<'static>)> {
+    let rows = review.diff.rows();
+    let mut lines = Vec::new();
+    let half = width.saturating_sub(1) / 2;
+    let mut i = 0;
+    while i < rows.len() {
+        let row = &rows[i];
+        let accepted = review.accepted[row.hunk];
+        let selected = row.hunk == review.selected;
+        match row.kind {
+            RowKind::Header => {
+                let check = if accepted { "[x]" } else { "[ ]" };
+                let mut style = Style::default().fg(Color::Cyan);
+                if selected {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                lines.push((row.hunk, Line::styled(format!("{} {}", check, review.diff.header(row.hunk)), style)));
+                i += 1;
+            }
+            RowKind::Context if review.side_by_side => {
+                let left = side(row.old, half, Style::default());
+                let right = side(row.new, half, Style::default());
+                lines.push((row.hunk, Line::from([left, vec![Span::raw("│")], right].concat())));
+                i += 1;
+            }
+            RowKind::Context => {
+                let text = row.old.map_or("", |(_, text)| text);
+                lines.push((row.hunk, Line::raw(format!(" {}", text))));
+                i += 1;
+            }
+            RowKind::Change => {
+                let end = rows[i..].iter().position(|r| r.kind != RowKind::Change).map_or(rows.len(), |n| i + n);
+                // Rejected hunks are dimmed; they won't be written.
+                let dim = |color: Color| if accepted { Style::default().fg(color) } else { Style::default().fg(Color::DarkGray) };
+                if review.side_by_side {
+                    for row in &rows[i..end] {
+                        let left = side(row.old, half, dim(Color::Red));
+                        let right = side(row.new, half, dim(Color::Green));
+                        lines.push((row.hunk, Line::from([left, vec![Span::raw("│")], right].concat())));
+                    }
+                } else {
+                    for (_, text) in rows[i..end].iter().filter_map(|r| r.old) {
+                        lines.push((row.hunk, Line::styled(format!("-{}", text), dim(Color::Red))));
+                    }
+                    for (_, text) in rows[i..end].iter().filter_map(|r| r.new) {
+                        lines.push((row.hunk, Line::styled(format!("+{}", text), dim(Color::Green))));
+                    }
+                }
+                i = end;
+            }
+        }
+    }
+    lines
+}
+
+/// One half of a side-by-side row: line number and text, padded to `width`.
+fn side(line: Option<(usize, &str)>, width: usize, style: Style) -> Vec<Span<'static>> {
+    let Some((n, text)) = line else { return vec![Span::raw(" ".repeat(width))] };
+    let number = format!("{:>4} ", n + 1);
+    let room = width.saturating_sub(number.len());
+    let text: String = text.chars().take(room).collect();
+    let pad = room.saturating_sub(text.chars().count());
+    vec![
+        Span::styled(number, Style::default().fg(Color::DarkGray)),
+        Span::styled(text, style),
+        Span::raw(" ".repeat(pad)),
+    ]
+}
+
+/// Dialog listing why an edited file failed validation.
+fn draw_check_failed(f: &mut Frame, app: &App, area: Rect) {
+    let Some(edit) = &app.pending_edit else { return };
+    let mut lines = vec![Line::from(format!("{} does not compile:", edit.path.display())), Line::default()];
+    for diagnostic in &edit.validation.diagnostics {
+        let color = if diagnostic.warning { Color::Yellow } else { Color::Red };
+        lines.push(Line::styled(diagnostic.to_string(), Style::default().fg(color)));
+    }
+    lines.push(Line::default());
+    let mut options = vec![Span::raw("e re-edit  d/Esc discard changes (restore backup)")];
+    if edit.validation.parser_rejected {
+        options.push(Span::styled("  load anyway unavailable: apparmor_parser rejects it", Style::default().fg(Color::DarkGray)));
+    } else {
+        options.push(Span::raw("  l load anyway"));
+    }
+    lines.push(Line::from(options));
+
+    let width = area.width.saturating_sub(4).min(100);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().title("Validation failed").borders(Borders::ALL).border_style(Style::default().fg(Color::Red)));
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+fn draw_processes(f: &mut Frame, app: &mut App, area: Rect) {
+    let row = |pid: &str, user: &str, command: &str, mode: &str, label: &str| {
+        format!("{:>7}  {:<10.10}  {:<16.16}  {:<10}  {}", pid, user, command, mode, label)
+    };
+    let current = app.selected().map(|p| p.name.clone());
+    let shown = app.shown_processes();
+    let mut items = Vec::new();
+    for process in &shown {
+        let color = Mode::parse(&process.mode).map_or(Color::DarkGray, mode_color);
+        let mut style = Style::default().fg(color);
+        if current.is_some() && process.profile(&app.profiles).map(|p| &p.name) == current.as_ref() {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        let text = row(&process.pid.to_string(), &process.user, &process.command, &process.mode, &process.label);
+        items.push(ListItem::new(Line::styled(text, style)));
+    }
+    let confined = app.processes.iter().filter(|p| p.is_confined()).count();
+    let title = format!(
+        "Processes: {} confined of {} (Enter go to profile, u {} unconfined, r refresh, Esc back)",
+        confined,
+        app.processes.len(),
+        if app.show_unconfined { "hide" } else { "show" }
+    );
+    let block = Block::default().title(title).borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(inner);
+    let header = Line::styled(format!("  {}", row("PID", "USER", "COMMAND", "MODE", "LABEL")), Style::default().add_modifier(Modifier::BOLD));
+    f.render_widget(Paragraph::new(header), chunks[0]);
+    let list = List::new(items)
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, chunks[1], &mut app.process_state);
+}
+
+fn draw_unconfined(f: &mut Frame, app: &mut App, area: Rect) {
+    let hint = match (app.show_restart, app.finding_state.selected().and_then(|i| app.findings.get(i))) {
+        (true, Some(finding)) => finding.restart_hint(),
+        _ => Vec::new(),
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(if hint.is_empty() { 0 } else { hint.len() as u16 + 2 })])
+        .split(area);
+
+    let items: Vec<ListItem> = app
+        .findings
+        .iter()
+        .map(|finding| {
+            let process = &finding.process;
+            let exe = process.exe.as_deref().unwrap_or(&process.command);
+            let (reason, color) = match &finding.reason {
+                unconfined::Reason::ProfileNotApplied(profile) => (format!("profile {} not applied", profile), Color::Yellow),
+                unconfined::Reason::NoProfile => ("no profile".to_string(), Color::Red),
+            };
+            let mut spans = vec![
+                Span::raw(format!("{:>7}  {:<10.10}  ", process.pid, process.user)),
+                Span::raw(exe.to_string()),
+                Span::styled(format!("  {}", reason), Style::default().fg(color)),
+            ];
+            if !finding.listening.is_empty() {
+                spans.push(Span::styled(format!("  {}", finding.listening.join(", ")), Style::default().fg(Color::DarkGray)));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let mut title = format!("Unconfined: {} to review (Enter restart needed, r refresh, Esc back)", app.findings.len());
+    if app.uninspected > 0 {
+        title += &format!(" — {} processes not inspected, run as root to see their sockets", app.uninspected);
+    }
+    let list = List::new(items)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, chunks[0], &mut app.finding_state);
+
+    if !hint.is_empty() {
+        let lines: Vec<Line> = hint.into_iter().map(Line::from).collect();
+        let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false }).block(Block::default().title("Restart needed").borders(Borders::ALL));
+        f.render_widget(paragraph, chunks[1]);
+    }
+}
+
+fn draw_mode_menu(f: &mut Frame, app: &App, area: Rect) {
+    let current = app.selected().filter(|_| app.marked.is_empty()).map(|p| p.mode);
+    let mut lines = Vec::new();
+    for (key, mode) in MODE_KEYS {
+        let mut style = Style::default().fg(mode_color(mode));
+        if current == Some(mode) {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        lines.push(Line::from(vec![Span::raw(format!(" {}  ", key)), Span::styled(mode.as_str(), style)]));
+    }
+    let title = match app.marked.len() {
+        0 => format!("Mode of {}", app.selected().map_or("", |p| p.name.as_str())),
+        n => format!("Mode of {} marked", n),
+    };
+    let width = (title.chars().count() as u16 + 4).max(24).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let paragraph = Paragraph::new(lines).block(Block::default().title(title).borders(Borders::ALL));
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+/// Checklist of the header flags of one profile.
+fn draw_flags(f: &mut Frame, app: &App, area: Rect) {
+    let Some(checklist) = &app.checklist else { return };
+    let mut lines = Vec::new();
+    for (i, (flag, on)) in checklist.flags.iter().enumerate() {
+        let mut style = Style::default();
+        if i == checklist.selected {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        let mode = Mode::parse(flag).filter(|_| flags::MODE_FLAGS.contains(&flag.as_str()));
+        let name_style = mode.map_or(style, |mode| style.fg(mode_color(mode)));
+        lines.push(Line::from(vec![
+            Span::styled(if *on { " [x] " } else { " [ ] " }, style),
+            Span::styled(flag.as_str(), name_style),
+        ]));
+    }
+    lines.push(Line::default());
+    lines.push(Line::styled(format!(" flags=({})", checklist.result().join(", ")), Style::default().fg(Color::DarkGray)));
+
+    let title = format!("Flags of {} (space toggle, Enter write, Esc cancel)", checklist.profile);
+    let width = (title.chars().count() as u16 + 4).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let paragraph = Paragraph::new(lines).block(Block::default().title(title).borders(Borders::ALL));
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+/// Per-profile results of the last batch action.
+fn draw_batch(f: &mut Frame, app: &App, area: Rect) {
+    let Some(batch) = &app.batch else { return };
+    let failed = batch.results.iter().filter(|r| !r.ok).count();
+    let mut lines = Vec::new();
+    for result in batch.results.iter().skip(batch.scroll) {
+        let (mark, color) = if result.ok { ("✓", Color::Green) } else { ("✗", Color::Red) };
+        let mut spans = vec![Span::styled(format!("{} ", mark), Style::default().fg(color)), Span::raw(result.profile.as_str())];
+        if let Some(first) = result.message.lines().next() {
+            spans.push(Span::styled(format!("  {}", first), Style::default().fg(Color::DarkGray)));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    let width = area.width.saturating_sub(4).min(100);
+    let height = (batch.results.len() as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let title = format!("{}: {} ok, {} failed (↑/↓ scroll, Esc close)", batch.action, batch.results.len() - failed, failed);
+    let color = if failed > 0 { Color::Red } else { Color::Green };
+    let paragraph = Paragraph::new(lines).block(Block::default().title(title).borders(Borders::ALL).border_style(Style::default().fg(color)));
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+fn message_line(message: &Message) -> Line<'_> {
+    let style = match message.level {
+        Level::Info => Style::default(),
+        Level::Error => Style::default().fg(Color::Red),
+    };
+    Line::from(vec![
+        Span::styled(message.timestamp(), Style::default().fg(Color::DarkGray)),
+        Span::raw(" "),
+        Span::styled(message.text.as_str(), style),
+    ])
+}
+
+fn draw_messages(f: &mut Frame, app: &App, area: Rect) {
+    let visible = area.height.saturating_sub(2) as usize;
+    let end = app.messages.history.len() - app.messages.scroll.min(app.messages.history.len());
+    let start = end.saturating_sub(visible);
+    let lines: Vec<Line> = app.messages.history[start..end].iter().map(message_line).collect();
+
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().title("Messages (↑/↓ scroll, Esc back)").borders(Borders::ALL));
+    f.render_widget(paragraph, area);
+}
+
+/// How long the status bar shows a new message before the key help
+/// comes back. Older messages stay in the history view.
+const MESSAGE_SHOWN: Duration = Duration::from_secs(5);
+
+fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
+    if let Some(prompt) = &app.prompt {
+        let label = match prompt.kind {
+            PromptKind::Search => "/",
+            PromptKind::Filter => "Filter (text, /regex/, mode:X): ",
+        };
+        let mut spans = vec![Span::raw(label), Span::raw(prompt.input.as_str())];
+        if let Some(error) = &prompt.error {
+            spans.push(Span::styled(format!("  {}", error), Style::default().fg(Color::Red)));
+        }
+        let x = area.x + (label.chars().count() + prompt.input.chars().count()) as u16;
+        f.set_cursor_position((x.min(area.right().saturating_sub(1)), area.y));
+        f.render_widget(Paragraph::new(Line::from(spans)), area);
+        return;
+    }
+    let recent = app.messages.last().filter(|message| message.time.elapsed().is_ok_and(|age| age < MESSAGE_SHOWN));
+    let line = match recent {
+        Some(message) => message_line(message),
+        None => Line::styled(
+            "q quit  e/c/a/d mode (M more)  space mark (A all, I invert, Esc clear)  p processes  U unconfined  r refresh  L load profile  R reload all  v edit  i edit inline  t flags  P/D persist/revert mode  / search (n/N)  f filter  s sort  G group (Enter fold)  l log (Tab focus, space mark, g rules)  m messages",
+            Style::default().fg(Color::DarkGray),
+        ),
+    };
+    f.render_widget(Paragraph::new(line), area);
+}
@@ -0,0 +1,225 @@
+//! Processes that should be confined but aren't: ones a loaded profile
+//! attaches to that started before it was loaded, and network listeners
+//! no profile attaches to (what `aa-unconfined` reports).
+
+use crate::procs::ProcessInfo;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// A listening socket from `/proc/net/{tcp,udp}{,6}`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Listener {
+    pub protocol: &'static str,
+    /// `address:port`
+    pub address: String,
+    pub inode: u64,
+}
+
+pub const PROTOCOLS: [&str; 4] = ["tcp", "tcp6", "udp", "udp6"];
+
+/// Parses one `/proc/net/<protocol>` table, keeping listening TCP sockets
+/// and unconnected UDP ones.
+pub fn parse_net(content: &str, protocol: &'static str) -> Vec<Listener> {
+    let listening = if protocol.starts_with("tcp") { "0A" } else { "07" };
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.get(3) != Some(&listening) {
+                return None;
+            }
+            let address = parse_address(fields.get(1)?)?;
+            let inode = fields.get(9)?.parse().ok().filter(|&inode| inode != 0)?;
+            Some(Listener { protocol, address, inode })
+        })
+        .collect()
+}
+
+/// `0100007F:0277` is 127.0.0.1:631. Addresses are stored as host-order
+/// 32-bit words, the port in network order.
+fn parse_address(field: &str) -> Option<String> {
+    let (address, port) = field.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut bytes = Vec::new();
+    for word in 0..address.len() / 8 {
+        let word = u32::from_str_radix(&address[word * 8..word * 8 + 8], 16).ok()?;
+        bytes.extend(word.to_le_bytes());
+    }
+    match bytes.len() {
+        4 => Some(format!("{}:{}", Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]), port)),
+        16 => Some(format!("[{}]:{}", Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?), port)),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reason {
+    /// This loaded profile attaches to the executable; the process started
+    /// before it was loaded.
+    ProfileNotApplied(String),
+    /// Listens on the network and no loaded profile attaches to it.
+    NoProfile,
+}
+
+#[derive(Clone, Debug)]
+pub struct Finding {
+    pub process: ProcessInfo,
+    /// Listening sockets, e.g. `tcp 0.0.0.0:22`.
+    pub listening: Vec<String>,
+    pub reason: Reason,
+}
+
+impl Finding {
+    /// What it takes to get the process confined.
+    pub fn restart_hint(&self) -> Vec<String> {
+        let process = &self.process;
+        let exe = process.exe.as_deref().unwrap_or(&process.command);
+        let restart = match &process.unit {
+            Some(unit) => format!("systemctl restart {}", unit),
+            None => format!("restart {} (pid {})", exe, process.pid),
+        };
+        match &self.reason {
+            Reason::ProfileNotApplied(profile) => vec![
+                format!("{} (pid {}) started before profile {} was loaded.", exe, process.pid, profile),
+                format!("Confinement applies from the next exec: {}", restart),
+            ],
+            Reason::NoProfile => vec![
+                format!("No loaded profile attaches to {}.", exe),
+                format!("Create one (aa-genprof {}), load it, then {}", exe, restart),
+            ],
+        }
+    }
+}
+
+/// Cross-references unconfined processes with the attachments of loaded
+/// profiles, given as `(profile, attachment)`, and with `listeners`.
+pub fn report(processes: &[ProcessInfo], listeners: &[Listener], attachments: &[(String, String)]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for process in processes.iter().filter(|p| !p.is_confined()) {
+        // Kernel threads have no executable and can't be confined.
+        let Some(exe) = &process.exe else { continue };
+        let listening: Vec<String> = listeners
+            .iter()
+            .filter(|l| process.sockets.contains(&l.inode))
+            .map(|l| format!("{} {}", l.protocol, l.address))
+            .collect();
+        let profile = attachments.iter().find(|(_, attachment)| attachment_matches(attachment, exe));
+        let reason = match profile {
+            Some((profile, _)) => Reason::ProfileNotApplied(profile.clone()),
+            None if !listening.is_empty() => Reason::NoProfile,
+            None => continue,
+        };
+        findings.push(Finding { process: process.clone(), listening, reason });
+    }
+    findings
+}
+
+/// Matches an executable path against a profile attachment: `*` and `?`
+/// stop at `/`, `**` doesn't, plus `[...]` classes and `{a,b}`.
+pub fn attachment_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.trim_matches('"').chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    glob(&pattern, &path)
+}
+
+fn glob(p: &[char], t: &[char]) -> bool {
+    match p.first() {
+        None => t.is_empty(),
+        Some('*') if p.get(1) == Some(&'*') => (0..=t.len()).any(|i| glob(&p[2..], &t[i..])),
+        Some('*') => (0..=t.len()).take_while(|&i| i == 0 || t[i - 1] != '/').any(|i| glob(&p[1..], &t[i..])),
+        Some('?') => t.first().is_some_and(|&c| c != '/') && glob(&p[1..], &t[1..]),
+        Some('[') => {
+            let Some(end) = p.iter().skip(2).position(|&c| c == ']').map(|i| i + 2) else {
+                return t.first() == Some(&'[') && glob(&p[1..], &t[1..]);
+            };
+            let Some(&c) = t.first() else { return false };
+            let (negated, class) = match p[1] {
+                '^' => (true, &p[2..end]),
+                _ => (false, &p[1..end]),
+            };
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if class.get(i + 1) == Some(&'-') && i + 2 < class.len() {
+                    matched |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    matched |= class[i] == c;
+                    i += 1;
+                }
+            }
+            matched != negated && glob(&p[end + 1..], &t[1..])
+        }
+        Some('{') => {
+            // Split the alternatives at top-level commas.
+            let mut depth = 0;
+            let mut starts = vec![1];
+            let mut end = None;
+            for (i, &c) in p.iter().enumerate().skip(1) {
+                match c {
+                    '{' => depth += 1,
+                    '}' if depth == 0 => {
+                        end = Some(i);
+                        break;
+                    }
+                    '}' => depth -= 1,
+                    ',' if depth == 0 => starts.push(i + 1),
+                    _ => {}
+                }
+            }
+            let Some(end) = end else { return t.first() == Some(&'{') && glob(&p[1..], &t[1..]) };
+            starts.push(end + 1);
+            starts.windows(2).any(|w| {
+                let alternative: Vec<char> = p[w[0]..w[1] - 1].iter().chain(&p[end + 1..]).copied().collect();
+                glob(&alternative, t)
+            })
+        }
+        Some('\\') if p.len() > 1 => t.first() == Some(&p[1]) && glob(&p[2..], &t[1..]),
+        Some(c) => t.first() == Some(c) && glob(&p[1..], &t[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_listening_sockets() {
+        let tcp = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n\
+                   \x20  0: 0100007F:0277 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 2345 1\n\
+                   \x20  1: 0100007F:0277 0100007F:A000 01 00000000:00000000 00:00000000 00000000     0        0 2346 1\n";
+        assert_eq!(parse_net(tcp, "tcp"), [Listener { protocol: "tcp", address: "127.0.0.1:631".to_string(), inode: 2345 }]);
+        assert_eq!(parse_address("00000000000000000000000001000000:0016").as_deref(), Some("[::1]:22"));
+    }
+
+    #[test]
+    fn attachments_glob() {
+        assert!(attachment_matches("/usr/sbin/cupsd", "/usr/sbin/cupsd"));
+        assert!(attachment_matches("/usr/lib/firefox/firefox{,.sh,-bin}", "/usr/lib/firefox/firefox-bin"));
+        assert!(attachment_matches("/usr/{,s}bin/*d", "/usr/sbin/sshd"));
+        assert!(!attachment_matches("/usr/bin/*", "/usr/bin/x/y"));
+        assert!(attachment_matches("/snap/**/bin/[a-c]ups", "/snap/core/1/bin/cups"));
+        assert!(!attachment_matches("/snap/**/bin/[^c]ups", "/snap/core/1/bin/cups"));
+    }
+
+    #[test]
+    fn reports_stale_and_exposed_processes() {
+        let process = |pid, exe: &str, sockets: Vec<u64>| ProcessInfo {
+            pid,
+            label: "unconfined".to_string(),
+            mode: "unconfined".to_string(),
+            exe: Some(exe.to_string()),
+            sockets,
+            ..ProcessInfo::default()
+        };
+        let processes = [process(1, "/usr/sbin/cupsd", vec![]), process(2, "/usr/sbin/sshd", vec![7]), process(3, "/usr/bin/bash", vec![])];
+        let listeners = [Listener { protocol: "tcp", address: "0.0.0.0:22".to_string(), inode: 7 }];
+        let attachments = [("/usr/sbin/cupsd".to_string(), "/usr/sbin/cupsd".to_string())];
+        let findings = report(&processes, &listeners, &attachments);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].reason, Reason::ProfileNotApplied("/usr/sbin/cupsd".to_string()));
+        assert_eq!(findings[1].reason, Reason::NoProfile);
+        assert_eq!(findings[1].listening, ["tcp 0.0.0.0:22"]);
+        assert!(findings[1].restart_hint()[1].contains("aa-genprof /usr/sbin/sshd"));
+    }
+}